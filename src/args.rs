@@ -0,0 +1,158 @@
+//! Parsing of command-line style `--heartbeat-*` arguments.
+
+use crate::{ConfigError, HeartbeatConfig};
+
+impl HeartbeatConfig {
+    /// Build a config from command-line style arguments.
+    ///
+    /// # Flags
+    ///
+    /// - `--heartbeat-url <URL>` (required): heartbeat URL
+    /// - `--heartbeat-interval <SECS>` (optional): interval in seconds,
+    ///   default 60
+    /// - `--heartbeat-timeout <SECS>` (optional): timeout in seconds,
+    ///   default 10
+    /// - `--heartbeat-jitter <SECS>` (optional): maximum random delay before
+    ///   each heartbeat in seconds, default 0
+    ///
+    /// Values follow the flag either as the next argument or after `=`, as
+    /// in `--heartbeat-interval=30`. All other arguments, including the
+    /// program name, are ignored, so the process arguments can be passed
+    /// as they are. Later flags override earlier ones. All other fields
+    /// keep their [`Default`] values.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::InvalidValue`] for non-numeric durations,
+    /// [`ConfigError::Malformed`] for a flag without a value, and
+    /// [`ConfigError::MissingUrl`] if no non-empty `--heartbeat-url` is
+    /// given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let args = [
+    ///     "my-service",
+    ///     "--verbose",
+    ///     "--heartbeat-url",
+    ///     "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN",
+    ///     "--heartbeat-interval=30",
+    /// ];
+    /// let config = HeartbeatConfig::from_args(args.into_iter().map(String::from))?;
+    /// assert_eq!(config.interval_secs, 30);
+    /// // Or, in a binary: HeartbeatConfig::from_args(std::env::args())
+    /// # Ok::<(), betteruptime_heartbeat::ConfigError>(())
+    /// ```
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if !matches!(
+                flag.as_str(),
+                "--heartbeat-url"
+                    | "--heartbeat-interval"
+                    | "--heartbeat-timeout"
+                    | "--heartbeat-jitter"
+            ) {
+                continue;
+            }
+
+            let value = inline
+                .or_else(|| args.next())
+                .ok_or_else(|| ConfigError::Malformed(flag.clone()))?;
+            let parse_secs = || {
+                value.trim().parse::<u64>().map_err(|_| ConfigError::InvalidValue {
+                    key: flag.clone(),
+                    value: value.clone(),
+                })
+            };
+
+            match flag.as_str() {
+                "--heartbeat-url" => config.url = value.trim().to_string(),
+                "--heartbeat-interval" => config.interval_secs = parse_secs()?,
+                "--heartbeat-timeout" => config.timeout_secs = parse_secs()?,
+                _ => config.jitter_secs = parse_secs()?,
+            }
+        }
+
+        if config.url.is_empty() {
+            return Err(ConfigError::MissingUrl);
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| (*arg).to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_from_args_reads_all_flags() {
+        let config = HeartbeatConfig::from_args(args(&[
+            "service",
+            "--port",
+            "8080",
+            "--heartbeat-url",
+            "https://example.com/heartbeat",
+            "--heartbeat-interval=30",
+            "--heartbeat-timeout",
+            "5",
+            "--heartbeat-jitter",
+            "3",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!(config.interval_secs, 30);
+        assert_eq!(config.timeout_secs, 5);
+        assert_eq!(config.jitter_secs, 3);
+    }
+
+    #[test]
+    fn test_from_args_uses_defaults() {
+        let config =
+            HeartbeatConfig::from_args(args(&["--heartbeat-url=https://example.com/heartbeat"]))
+                .unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!(config.interval_secs, 60);
+        assert_eq!(config.timeout_secs, 10);
+        assert_eq!(config.jitter_secs, 0);
+    }
+
+    #[test]
+    fn test_from_args_rejects_invalid_values() {
+        assert_eq!(
+            HeartbeatConfig::from_args(args(&[
+                "--heartbeat-url",
+                "https://example.com/heartbeat",
+                "--heartbeat-interval",
+                "soon",
+            ]))
+            .unwrap_err(),
+            ConfigError::InvalidValue {
+                key: "--heartbeat-interval".to_string(),
+                value: "soon".to_string(),
+            }
+        );
+        assert_eq!(
+            HeartbeatConfig::from_args(args(&["--heartbeat-url"])).unwrap_err(),
+            ConfigError::Malformed("--heartbeat-url".to_string())
+        );
+        assert_eq!(
+            HeartbeatConfig::from_args(args(&["--heartbeat-interval", "30"])).unwrap_err(),
+            ConfigError::MissingUrl
+        );
+    }
+}
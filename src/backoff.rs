@@ -0,0 +1,118 @@
+//! Delay computation for in-interval retries.
+
+use std::time::Duration;
+
+/// How the delay between retries of a failed heartbeat grows.
+///
+/// `base` is [`HeartbeatConfig::retry_base_delay_ms`](crate::HeartbeatConfig::retry_base_delay_ms)
+/// and every delay is capped at the heartbeat interval (`cap`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BackoffStrategy {
+    /// Wait `base` before every retry.
+    Fixed,
+    /// Double the delay for each retry: `min(cap, base * 2^n)` before retry `n`
+    /// (counting from 0).
+    #[default]
+    Exponential,
+    /// AWS-style decorrelated jitter: `min(cap, random_between(base, previous * 3))`,
+    /// where `previous` is the last delay (initially `base`). Spreads retries of
+    /// many clients that failed at the same time better than plain
+    /// exponential backoff.
+    DecorrelatedJitter,
+}
+
+/// Produces successive retry delays for one heartbeat.
+#[derive(Debug)]
+pub struct Backoff {
+    strategy: BackoffStrategy,
+    base: Duration,
+    cap: Duration,
+    previous: Duration,
+    retry: u32,
+    rng: fastrand::Rng,
+}
+
+impl Backoff {
+    pub const fn new(
+        strategy: BackoffStrategy,
+        base: Duration,
+        cap: Duration,
+        rng: fastrand::Rng,
+    ) -> Self {
+        Self { strategy, base, cap, previous: base, retry: 0, rng }
+    }
+
+    /// Delay before the next retry.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = match self.strategy {
+            BackoffStrategy::Fixed => self.base,
+            BackoffStrategy::Exponential => {
+                self.base.saturating_mul(2_u32.saturating_pow(self.retry))
+            }
+            BackoffStrategy::DecorrelatedJitter => {
+                let low = self.base.as_millis();
+                let high = self.previous.saturating_mul(3).as_millis().max(low);
+                let millis = self.rng.u128(low..=high);
+                Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX))
+            }
+        }
+        .min(self.cap);
+
+        self.previous = delay;
+        self.retry = self.retry.saturating_add(1);
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(strategy: BackoffStrategy, seed: u64, count: usize) -> Vec<Duration> {
+        let mut backoff = Backoff::new(
+            strategy,
+            Duration::from_millis(100),
+            Duration::from_secs(5),
+            fastrand::Rng::with_seed(seed),
+        );
+        (0..count).map(|_| backoff.next_delay()).collect()
+    }
+
+    #[test]
+    fn test_fixed_backoff_repeats_base() {
+        assert_eq!(delays(BackoffStrategy::Fixed, 1, 3), vec![Duration::from_millis(100); 3]);
+    }
+
+    #[test]
+    fn test_exponential_backoff_doubles_up_to_cap() {
+        let expected: Vec<Duration> =
+            [100, 200, 400, 800, 1_600, 3_200, 5_000, 5_000].map(Duration::from_millis).to_vec();
+        assert_eq!(delays(BackoffStrategy::Exponential, 1, 8), expected);
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_within_bounds() {
+        for seed in 0..50 {
+            let delays = delays(BackoffStrategy::DecorrelatedJitter, seed, 20);
+            let mut previous = Duration::from_millis(100);
+
+            for delay in delays {
+                let upper = (previous * 3).min(Duration::from_secs(5));
+                assert!(
+                    delay >= Duration::from_millis(100) && delay <= upper,
+                    "seed {seed}: {delay:?} outside [100ms, {upper:?}]"
+                );
+                previous = delay;
+            }
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_is_deterministic_for_seed() {
+        assert_eq!(
+            delays(BackoffStrategy::DecorrelatedJitter, 42, 10),
+            delays(BackoffStrategy::DecorrelatedJitter, 42, 10)
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! Heartbeats from a dedicated thread, for services without a tokio runtime.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::Client;
+use tracing::subscriber::NoSubscriber;
+
+use crate::backoff::Backoff;
+use crate::runner::{AttemptError, check_etag, load_above_limit, local_address};
+use crate::status::{SkipReason, Status};
+use crate::{
+    FirstTickPolicy, HeartbeatConfig, HeartbeatStats, HeartbeatStatusHandle, HttpVersion,
+    intercept, loop_span, request,
+};
+
+/// Handle of a heartbeat thread started with [`spawn_blocking`].
+///
+/// Dropping the handle detaches the thread, which then sends heartbeats
+/// until the process exits; call [`stop`](Self::stop) to end it.
+#[derive(Debug)]
+pub struct BlockingHeartbeatHandle {
+    thread: JoinHandle<()>,
+    stop: mpsc::Sender<()>,
+    status: Arc<Status>,
+}
+
+impl BlockingHeartbeatHandle {
+    /// A status handle observing the thread's heartbeats.
+    #[must_use]
+    pub fn status(&self) -> HeartbeatStatusHandle {
+        HeartbeatStatusHandle::new(Arc::clone(&self.status))
+    }
+
+    /// Current statistics.
+    #[must_use]
+    pub fn stats(&self) -> HeartbeatStats {
+        self.status.snapshot()
+    }
+
+    /// Whether the thread has exited.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the thread and wait for it to exit, returning the final
+    /// statistics. A heartbeat in flight is finished first, bounded by the
+    /// configured timeout.
+    pub fn stop(self) -> HeartbeatStats {
+        let _ = self.stop.send(());
+        if self.thread.join().is_err() {
+            tracing::error!("Heartbeat thread panicked");
+        }
+        self.status.snapshot()
+    }
+}
+
+/// Send heartbeats from a dedicated [`std::thread`] using reqwest's blocking
+/// client, for services that do not run a tokio runtime. Requires the
+/// `blocking` feature.
+///
+/// The config is passed through the [config
+/// interceptor](crate::set_config_interceptor) and validated as in
+/// [`spawn`](crate::spawn); if either fails, an error is logged and `None`
+/// is returned. The thread sleeps between heartbeats and honours the
+/// interval, first tick policy, timeout, retries with backoff and
+/// `total_retry_budget`, the request settings (method, headers, query
+/// parameters, user-agent, body), [`success_statuses`](HeartbeatConfig::success_statuses),
+/// `expected_etag`, `skip_above_loadavg`, `startup_grace_secs`, the
+/// transition hook and the client settings `pool_max_idle_per_host`,
+/// `http_version` and `ip_family`. Ticks missed by a slow heartbeat are
+/// dropped. Failures are logged and counted, never panicking.
+///
+/// Everything else is ignored, in particular what needs async code or a
+/// [`HeartbeatHandle`](crate::HeartbeatHandle): `jitter_secs`,
+/// `startup_delay_secs`, the `clock`, `client_customizer` (which configures
+/// an async client), the body validator, the stop conditions
+/// (`abort_after_failures` and the failure policies), lifecycle events,
+/// readiness marks, `startup_url`, the liveness and stats files, and
+/// suppression windows.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_blocking};
+///
+/// let heartbeat = spawn_blocking(HeartbeatConfig {
+///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///     ..HeartbeatConfig::default()
+/// });
+///
+/// // ... run the worker threads ...
+///
+/// if let Some(heartbeat) = heartbeat {
+///     heartbeat.stop();
+/// }
+/// ```
+#[must_use = "dropping the handle leaves the thread running without a way to stop it"]
+pub fn spawn_blocking(config: HeartbeatConfig) -> Option<BlockingHeartbeatHandle> {
+    let span = loop_span(&config);
+    let config = match intercept::apply(config) {
+        Ok(config) => config,
+        Err(e) => {
+            span.in_scope(|| tracing::error!("Heartbeat not started: {}", e));
+            return None;
+        }
+    };
+
+    let status = Arc::new(Status::new(&config));
+    let (stop, stopped) = mpsc::channel();
+    let thread_status = Arc::clone(&status);
+    let thread = thread::Builder::new()
+        .name("heartbeat".to_string())
+        .spawn(move || {
+            let _entered = span.enter();
+            if config.silent {
+                let _silence = tracing::subscriber::set_default(NoSubscriber::default());
+                run(&config, &thread_status, &stopped);
+            } else {
+                run(&config, &thread_status, &stopped);
+            }
+        })
+        .inspect_err(|e| tracing::error!("Failed to start heartbeat thread: {}", e))
+        .ok()?;
+
+    Some(BlockingHeartbeatHandle { thread, stop, status })
+}
+
+/// The heartbeat loop of the thread, until `stopped` receives.
+fn run(config: &HeartbeatConfig, status: &Status, stopped: &mpsc::Receiver<()>) {
+    let client = match build_client(config) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Failed to create HTTP client for heartbeat: {}", e);
+            return;
+        }
+    };
+    tracing::info!(
+        "Heartbeat thread started: interval={}s, timeout={}s",
+        config.interval_secs,
+        config.timeout_secs
+    );
+
+    let interval = Duration::from_secs(config.interval_secs);
+    let mut next = Instant::now();
+    if config.first_tick_policy == FirstTickPolicy::Skip {
+        next += interval;
+    }
+    for sequence in 0.. {
+        if wait(stopped, next.saturating_duration_since(Instant::now())) {
+            break;
+        }
+        if let Some(load) = load_above_limit(config) {
+            tracing::warn!("Heartbeat skipped, load average {:.2} is above the limit", load);
+            status.record_skips(SkipReason::HighLoad, 1);
+        } else if send(&client, config, status, stopped, sequence) {
+            break;
+        }
+        // Ticks missed by a slow heartbeat are dropped.
+        next += interval;
+        while next < Instant::now() {
+            next += interval;
+            status.record_skips(SkipReason::Overlap, 1);
+        }
+    }
+    tracing::info!("Heartbeat thread stopped");
+}
+
+/// Send one heartbeat with retries, recording the result in `status`.
+/// Returns whether a stop was requested while waiting to retry.
+fn send(
+    client: &Client,
+    config: &HeartbeatConfig,
+    status: &Status,
+    stopped: &mpsc::Receiver<()>,
+    sequence: usize,
+) -> bool {
+    let started = Instant::now();
+    let interval = Duration::from_secs(config.interval_secs);
+    let mut backoff = Backoff::new(
+        config.backoff,
+        Duration::from_millis(config.retry_base_delay_ms),
+        interval,
+        fastrand::Rng::new(),
+    );
+    let mut retries = 0;
+
+    loop {
+        let (result, latency) = attempt(client, config, sequence);
+        let err = match result {
+            Ok(code) => {
+                tracing::debug!("Heartbeat sent successfully in {}ms", latency.as_millis());
+                status.record_success(code, latency);
+                return false;
+            }
+            Err(err) => err,
+        };
+
+        status.record_failed_attempt();
+        if retries < config.max_retries && !status.retry_budget_exhausted(config) {
+            let delay = backoff.next_delay();
+            if started.elapsed() + delay < interval {
+                retries += 1;
+                tracing::debug!(
+                    "Heartbeat attempt failed ({}), retry {}/{} in {:?}",
+                    err,
+                    retries,
+                    config.max_retries,
+                    delay
+                );
+                if wait(stopped, delay) {
+                    return true;
+                }
+                continue;
+            }
+        }
+
+        err.log_final();
+        status.record_failure(err.class(), err.status_code(), latency);
+        status.record_error(&err.to_error());
+        return false;
+    }
+}
+
+/// Send a single request and classify the response.
+fn attempt(
+    client: &Client,
+    config: &HeartbeatConfig,
+    sequence: usize,
+) -> (Result<u16, AttemptError>, Duration) {
+    let started = Instant::now();
+    let mut request = client.request(config.method.clone(), request::request_url(config));
+    for (name, value) in request::headers(config, sequence) {
+        request = request.header(name, value);
+    }
+    if let Some(body) = request::body(config, sequence) {
+        request = request.body(body);
+    }
+
+    let result = match request.send() {
+        Ok(response) if request::is_success(config, response.status()) => {
+            #[cfg(feature = "rustls-tls")]
+            if let Some(days) = config.warn_cert_expiry_days {
+                crate::cert::check(response.extensions().get(), days);
+            }
+            check_etag(config, response.headers()).map(|()| response.status().as_u16())
+        }
+        Ok(response) => Err(AttemptError::Status(response.status())),
+        Err(e) => Err(e.into()),
+    };
+    (result, started.elapsed())
+}
+
+/// Blocking counterpart of the loop's HTTP client for `config`.
+fn build_client(config: &HeartbeatConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .default_headers(request::client_headers(config));
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    match config.http_version.unwrap_or_default() {
+        HttpVersion::Http1 => builder = builder.http1_only(),
+        #[cfg(feature = "http2")]
+        HttpVersion::Http2 => builder = builder.http2_prior_knowledge(),
+        #[cfg(not(feature = "http2"))]
+        HttpVersion::Http2 => return Err(crate::runner::HTTP2_DISABLED.to_string()),
+        HttpVersion::Auto => {}
+    }
+    if let Some(address) = local_address(config.ip_family.unwrap_or_default()) {
+        builder = builder.local_address(address);
+    }
+    #[cfg(feature = "rustls-tls")]
+    if config.warn_cert_expiry_days.is_some() {
+        builder = builder.tls_info(true);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Sleep for `duration`, returning early with `true` if a stop was requested.
+/// Once the handle is dropped the thread just sleeps.
+fn wait(stopped: &mpsc::Receiver<()>, duration: Duration) -> bool {
+    match stopped.recv_timeout(duration) {
+        Ok(()) => true,
+        Err(RecvTimeoutError::Timeout) => false,
+        Err(RecvTimeoutError::Disconnected) => {
+            thread::sleep(duration);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockResponse, MockServer};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocking_loop_sends_counts_and_stops() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(503));
+        let heartbeat = spawn_blocking(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        let stats = tokio::task::spawn_blocking(move || heartbeat.stop()).await.unwrap();
+
+        assert_eq!((stats.failures, stats.successes), (1, 1));
+        assert_eq!(server.requests()[0].path, "/heartbeat");
+        assert!(spawn_blocking(HeartbeatConfig::default()).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocking_loop_shares_request_settings_and_load_skip() {
+        #[derive(Debug)]
+        struct LoadAfterFirst(std::sync::atomic::AtomicBool);
+
+        impl crate::LoadSource for LoadAfterFirst {
+            fn load_average(&self) -> Option<f64> {
+                let overloaded = self.0.swap(true, std::sync::atomic::Ordering::Relaxed);
+                Some(if overloaded { 8.5 } else { 0.5 })
+            }
+        }
+
+        let server = MockServer::start().await;
+        let heartbeat = spawn_blocking(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            user_agent: Some("worker/1.0".to_string()),
+            accept: Some("text/plain".to_string()),
+            headers: vec![("X-Team".to_string(), "billing".to_string())],
+            app_name: Some("billing-worker".to_string()),
+            skip_above_loadavg: Some(4.0),
+            load_source: Some(Arc::new(LoadAfterFirst(false.into()))),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        let requests = server.wait_for_requests(1, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(1_200)).await;
+        let status = heartbeat.status();
+        let final_stats = tokio::task::spawn_blocking(move || heartbeat.stop()).await.unwrap();
+
+        let request = &requests[0];
+        assert_eq!(request.header("user-agent"), Some("worker/1.0"));
+        assert_eq!(request.header("accept"), Some("text/plain"));
+        assert_eq!(request.header("x-team"), Some("billing"));
+        assert_eq!(request.header(request::CLIENT_HEADER), Some("billing-worker"));
+        // The second tick was skipped for the load.
+        assert_eq!(final_stats.successes, 1);
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(status.skip_counts().get(&SkipReason::HighLoad), Some(&1));
+    }
+}
@@ -0,0 +1,263 @@
+//! Builder for [`HeartbeatConfig`], from the defaults or the environment.
+
+use std::time::Duration;
+
+use crate::{ClientCustomizer, ConfigError, HeartbeatConfig, TransitionHook};
+
+/// Builder for a [`HeartbeatConfig`].
+///
+/// Start from the defaults with [`new`](Self::new) or from the
+/// `HEARTBEAT_*` environment variables with [`from_env`](Self::from_env),
+/// override what the code should decide, then [`build`](Self::build).
+/// Settings without a dedicated method can be changed with
+/// [`configure`](Self::configure).
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::HeartbeatConfigBuilder;
+///
+/// let config = HeartbeatConfigBuilder::from_env()
+///     .url("https://uptime.betterstack.com/api/v1/heartbeat/TOKEN")
+///     .interval_secs(30)
+///     .app_name("billing")
+///     .build()
+///     .expect("valid heartbeat config");
+///
+/// assert_eq!(config.interval_secs, 30);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HeartbeatConfigBuilder {
+    config: HeartbeatConfig,
+}
+
+impl HeartbeatConfigBuilder {
+    /// A builder starting from [`HeartbeatConfig::default`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder pre-filled from the environment variables read by
+    /// [`HeartbeatConfig::from_env`].
+    ///
+    /// Unlike that function this never fails: an unset `HEARTBEAT_URL`
+    /// leaves the URL empty, so [`build`](Self::build) reports it missing
+    /// unless [`url`](Self::url) sets one.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self { config: HeartbeatConfig::env_defaults() }
+    }
+
+    /// Set the heartbeat URL.
+    #[must_use]
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.config.url = url.into();
+        self
+    }
+
+    /// Set the interval between heartbeats in seconds.
+    #[must_use]
+    pub const fn interval_secs(mut self, secs: u64) -> Self {
+        self.config.interval_secs = secs;
+        self
+    }
+
+    /// Set the request timeout in seconds.
+    #[must_use]
+    pub const fn timeout_secs(mut self, secs: u64) -> Self {
+        self.config.timeout_secs = secs;
+        self
+    }
+
+    /// Set the number of retries after a failed heartbeat.
+    #[must_use]
+    pub const fn max_retries(mut self, retries: u32) -> Self {
+        self.config.max_retries = retries;
+        self
+    }
+
+    /// Set the application name sent in the user agent.
+    #[must_use]
+    pub fn app_name(mut self, name: impl Into<String>) -> Self {
+        self.config.app_name = Some(name.into());
+        self
+    }
+
+    /// Add a query parameter to every heartbeat URL.
+    #[must_use]
+    pub fn query_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.query_params.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add a request header.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the HTTP method of each request.
+    #[must_use]
+    pub fn method(mut self, method: reqwest::Method) -> Self {
+        self.config.method = method;
+        self
+    }
+
+    /// Send each heartbeat as a `POST` with `template` as its body and a
+    /// `Content-Type: application/json` header, e.g. to attach runtime
+    /// details. The template's placeholders are described at
+    /// [`HeartbeatConfig::body_template`].
+    #[must_use]
+    pub fn json_body(self, template: impl Into<String>) -> Self {
+        let mut builder = self.method(reqwest::Method::POST);
+        builder.config.body_template = Some(template.into());
+        builder
+    }
+
+    /// Set the startup grace period.
+    #[must_use]
+    pub const fn startup_grace(mut self, grace: Duration) -> Self {
+        self.config.startup_grace_secs = grace.as_secs();
+        self
+    }
+
+    /// Suppress all logging.
+    #[must_use]
+    pub const fn silent(mut self, silent: bool) -> Self {
+        self.config.silent = silent;
+        self
+    }
+
+    /// Set the hook called when the health state changes.
+    #[must_use]
+    pub fn on_transition(mut self, hook: TransitionHook) -> Self {
+        self.config.on_transition = Some(hook);
+        self
+    }
+
+    /// Adjust the HTTP client's builder with `customize`, for reqwest options
+    /// without a config field. It runs after the crate's own settings,
+    /// including the timeout, and may override them.
+    #[must_use]
+    pub fn client_customizer<F>(mut self, customize: F) -> Self
+    where
+        F: Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync + 'static,
+    {
+        self.config.client_customizer = Some(ClientCustomizer::new(customize));
+        self
+    }
+
+    /// Change any other setting of the config being built.
+    #[must_use]
+    pub fn configure(mut self, f: impl FnOnce(&mut HeartbeatConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// Validate and return the config.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found by [`HeartbeatConfig::validate`].
+    pub fn build(self) -> Result<HeartbeatConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::HeartbeatClient;
+    use crate::test_util::MockServer;
+    use serial_test::serial;
+
+    #[test]
+    fn test_builder_fills_in_defaults() {
+        let config = HeartbeatConfig::builder("https://example.com/heartbeat")
+            .timeout_secs(5)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!(config.interval_secs, 60);
+        assert_eq!(config.timeout_secs, 5);
+        assert!(matches!(HeartbeatConfig::builder(" ").build(), Err(ConfigError::MissingUrl)));
+    }
+
+    #[tokio::test]
+    async fn test_json_body_posts_rendered_template() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig::builder(server.url())
+            .json_body(r#"{"service":"billing","seq":{{sequence}}}"#)
+            .build()
+            .unwrap();
+        HeartbeatClient::new(config).unwrap().report_success().await.unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, br#"{"service":"billing","seq":0}"#);
+    }
+
+    #[tokio::test]
+    async fn test_client_customizer_shapes_the_client() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig::builder(server.url())
+            .client_customizer(|builder| {
+                let mut headers = reqwest::header::HeaderMap::new();
+                headers.insert("x-region", reqwest::header::HeaderValue::from_static("eu-west"));
+                builder.default_headers(headers)
+            })
+            .build()
+            .unwrap();
+        HeartbeatClient::new(config).unwrap().report_success().await.unwrap();
+
+        assert_eq!(server.requests()[0].header("x-region"), Some("eu-west"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_env_with_override() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "120");
+            std::env::set_var("HEARTBEAT_TIMEOUT_SECS", "15");
+        }
+
+        let config = HeartbeatConfigBuilder::from_env()
+            .interval_secs(30)
+            .header("X-Team", "billing")
+            .build()
+            .unwrap();
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+        }
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!(config.timeout_secs, 15);
+        assert_eq!(config.interval_secs, 30);
+        assert_eq!(config.headers, vec![("X-Team".to_string(), "billing".to_string())]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_from_env_without_url_needs_one() {
+        // SAFETY: Tests run sequentially
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+
+        let builder = HeartbeatConfigBuilder::from_env();
+        assert!(matches!(builder.clone().build(), Err(ConfigError::MissingUrl)));
+        assert!(builder.url("https://example.com/heartbeat").build().is_ok());
+    }
+}
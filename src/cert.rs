@@ -0,0 +1,94 @@
+//! Expiry of the heartbeat endpoint's TLS certificate, checked when
+//! [`HeartbeatConfig::warn_cert_expiry_days`](crate::HeartbeatConfig::warn_cert_expiry_days)
+//! is set.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::tls::TlsInfo;
+use x509_parser::parse_x509_certificate;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Day of the last warning per certificate serial number, so that a loop
+/// sending a heartbeat every few seconds warns once a day, not every time.
+static LAST_WARNED: Mutex<BTreeMap<Vec<u8>, u64>> = Mutex::new(BTreeMap::new());
+
+/// Warn if the peer certificate of a successful response expires within
+/// `days`. Plain HTTP responses and certificates that cannot be parsed are
+/// ignored.
+pub fn check(tls: Option<&TlsInfo>, days: u32) {
+    if let Some(der) = tls.and_then(TlsInfo::peer_certificate) {
+        warn_if_expiring(der, days, SystemTime::now());
+    }
+}
+
+/// Warn if the DER certificate `der` expires within `days` of `now`, at most
+/// once per certificate and day.
+fn warn_if_expiring(der: &[u8], days: u32, now: SystemTime) {
+    let Ok((_, certificate)) = parse_x509_certificate(der) else {
+        tracing::debug!("Could not read the heartbeat endpoint's TLS certificate expiry");
+        return;
+    };
+    let not_after = u64::try_from(certificate.validity().not_after.timestamp())
+        .map_or(UNIX_EPOCH, |secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let left = not_after.duration_since(now).ok();
+    if left.is_some_and(|left| left >= Duration::from_secs(u64::from(days) * SECS_PER_DAY))
+        || !first_warning_today(certificate.raw_serial(), now)
+    {
+        return;
+    }
+    if let Some(left) = left {
+        tracing::warn!(
+            "Heartbeat endpoint TLS certificate expires in {} days",
+            left.as_secs() / SECS_PER_DAY
+        );
+    } else {
+        tracing::warn!("Heartbeat endpoint TLS certificate has expired");
+    }
+}
+
+/// Record a warning about the certificate with `serial` on the day of `now`,
+/// returning whether it is the first that day.
+fn first_warning_today(serial: &[u8], now: SystemTime) -> bool {
+    let day = now.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / SECS_PER_DAY);
+    LAST_WARNED.lock().unwrap_or_else(PoisonError::into_inner).insert(serial.to_vec(), day)
+        != Some(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::capture_logs;
+    use tracing::Level;
+
+    #[test]
+    fn test_short_lived_certificate_triggers_warning() {
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.not_before = rcgen::date_time_ymd(2029, 12, 1);
+        params.not_after = rcgen::date_time_ymd(2030, 1, 10);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let der = params.self_signed(&key).unwrap().der().to_vec();
+        // 2030-01-05T00:00:00Z, five days before expiry.
+        let now = UNIX_EPOCH + Duration::from_secs(1_893_801_600);
+
+        let (logs, _guard) = capture_logs();
+        warn_if_expiring(&der, 3, now);
+        assert!(logs.events().is_empty());
+        warn_if_expiring(&der, 7, now);
+        // Repeated on the same day.
+        warn_if_expiring(&der, 7, now + Duration::from_secs(60));
+        warn_if_expiring(&der, 7, now + Duration::from_secs(6 * SECS_PER_DAY));
+
+        let warnings: Vec<String> =
+            logs.events().into_iter().filter(|e| e.level == Level::WARN).map(|e| e.text).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "Heartbeat endpoint TLS certificate expires in 5 days",
+                "Heartbeat endpoint TLS certificate has expired",
+            ]
+        );
+    }
+}
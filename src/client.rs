@@ -0,0 +1,198 @@
+//! Explicit start, success and failure reports for a single monitor.
+
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::request::{self, FAIL_SEGMENT, START_SEGMENT};
+use crate::runner::{build_client, send_heartbeat};
+use crate::status::Status;
+use crate::{HeartbeatConfig, HeartbeatError};
+
+/// Client sending individual heartbeats on demand, e.g. from a cron job.
+///
+/// Better Uptime records a ping to `{url}/start` as the start of a run,
+/// a ping to `{url}` as its success and a ping to `{url}/fail` as its
+/// failure. Each report is one request with the configured timeout,
+/// retries and headers; trailing slashes in the URL are handled. The HTTP
+/// client is created once and reused.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatClient, HeartbeatConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), betteruptime_heartbeat::HeartbeatError> {
+///     let client = HeartbeatClient::new(HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     })?;
+///
+///     client.report_start().await?;
+///     match std::process::Command::new("./backup.sh").status() {
+///         Ok(status) if status.success() => client.report_success().await,
+///         _ => client.report_fail().await,
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeartbeatClient {
+    config: HeartbeatConfig,
+    client: reqwest::Client,
+}
+
+impl HeartbeatClient {
+    /// Create a client reporting to the monitor of `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Config`] if the config is invalid and
+    /// [`HeartbeatError::Client`] if the HTTP client cannot be created.
+    pub fn new(config: HeartbeatConfig) -> Result<Self, HeartbeatError> {
+        config.validate()?;
+        let client = build_client(&config).map_err(HeartbeatError::Client)?;
+        Ok(Self { config, client })
+    }
+
+    /// Mark a run as started by pinging `{url}/start`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Timeout`], [`HeartbeatError::Connect`],
+    /// [`HeartbeatError::Http`] or [`HeartbeatError::Other`] if the ping was
+    /// not accepted.
+    pub async fn report_start(&self) -> Result<(), HeartbeatError> {
+        self.report(Some(START_SEGMENT)).await
+    }
+
+    /// Mark a run as successful by pinging `{url}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Timeout`], [`HeartbeatError::Connect`],
+    /// [`HeartbeatError::Http`] or [`HeartbeatError::Other`] if the ping was
+    /// not accepted.
+    pub async fn report_success(&self) -> Result<(), HeartbeatError> {
+        self.report(None).await
+    }
+
+    /// Mark a run as failed by pinging `{url}/fail`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Timeout`], [`HeartbeatError::Connect`],
+    /// [`HeartbeatError::Http`] or [`HeartbeatError::Other`] if the ping was
+    /// not accepted.
+    pub async fn report_fail(&self) -> Result<(), HeartbeatError> {
+        self.report(Some(FAIL_SEGMENT)).await
+    }
+
+    async fn report(&self, segment: Option<&str>) -> Result<(), HeartbeatError> {
+        let config = segment.map_or_else(
+            || self.config.clone(),
+            |segment| request::endpoint_config(&self.config, segment),
+        );
+
+        let status = Status::default();
+        let send = send_heartbeat(&self.client, &config, &status, 0);
+        let outcome = if config.silent {
+            send.with_subscriber(NoSubscriber::default()).await
+        } else {
+            send.await
+        };
+        outcome.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::test_util::{MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn test_reports_hit_their_endpoints() {
+        let server = MockServer::start().await;
+        let client = HeartbeatClient::new(HeartbeatConfig {
+            url: format!("{}/", server.url()),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(client.report_start().await, Ok(()));
+        assert_eq!(client.report_success().await, Ok(()));
+        assert_eq!(client.report_fail().await, Ok(()));
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, vec!["/heartbeat/start", "/heartbeat/", "/heartbeat/fail"]);
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_follow_path_suffix() {
+        let server = MockServer::start().await;
+        let client = HeartbeatClient::new(HeartbeatConfig {
+            url: server.url(),
+            path_suffix: Some("eu-west".to_string()),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(client.report_success().await, Ok(()));
+        assert_eq!(client.report_fail().await, Ok(()));
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, vec!["/heartbeat/eu-west", "/heartbeat/eu-west/fail"]);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_report_is_an_error() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(404));
+        let client = HeartbeatClient::new(HeartbeatConfig {
+            url: server.url(),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            client.report_fail().await,
+            Err(HeartbeatError::Http(reqwest::StatusCode::NOT_FOUND))
+        );
+        assert_eq!(
+            HeartbeatClient::new(HeartbeatConfig::default()).unwrap_err(),
+            HeartbeatError::Config(crate::ConfigError::MissingUrl)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors_tell_timeouts_from_rejections() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200).delay(Duration::from_secs(2)));
+        server.push_response(MockResponse::status(500));
+        let client = HeartbeatClient::new(HeartbeatConfig {
+            url: server.url(),
+            timeout_secs: 1,
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(client.report_success().await, Err(HeartbeatError::Timeout));
+        let err = client.report_success().await.unwrap_err();
+        assert_eq!(err, HeartbeatError::Http(reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(err.status_code(), Some(500));
+
+        let refused = HeartbeatClient::new(HeartbeatConfig {
+            url: "http://127.0.0.1:1/heartbeat/secret-token".to_string(),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+        let err = refused.report_success().await.unwrap_err();
+        assert!(matches!(err, HeartbeatError::Connect(_)));
+        // The reqwest error is kept as the source, without the URL.
+        let source = std::error::Error::source(&err).expect("connect error has a source");
+        assert!(source.downcast_ref::<Arc<reqwest::Error>>().is_some());
+        assert!(!format!("{err} {source}").contains("secret-token"));
+    }
+}
@@ -0,0 +1,39 @@
+//! Time source of the heartbeat schedule.
+
+use std::fmt;
+
+use tokio::time::Instant;
+
+use crate::BoxFuture;
+
+/// Source of the current time and of timed waits for heartbeat scheduling.
+///
+/// The loop computes every send time from [`now`](Self::now) and waits for
+/// it, and for the jitter before a heartbeat, with
+/// [`sleep_until`](Self::sleep_until), so a test can substitute a
+/// clock it advances by hand, such as `test_util::ManualClock` (`test-util`
+/// feature), and check
+/// exact send times without real delays. Request timeouts and retry delays
+/// always use tokio's timer. Production code uses [`TokioClock`].
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current instant.
+    fn now(&self) -> Instant;
+
+    /// Resolve once [`now`](Self::now) has reached `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()>;
+}
+
+/// The default [`Clock`], backed by [`tokio::time`], including its paused
+/// test time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
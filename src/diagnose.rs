@@ -0,0 +1,368 @@
+//! Self-test diagnosing common heartbeat misconfigurations.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::runner::{build_client, send_heartbeat};
+use crate::status::Status;
+use crate::{ConfigError, HeartbeatConfig, IpFamily};
+
+/// A check performed by [`diagnose`], in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// The config, including the URL, passes [`HeartbeatConfig::validate`].
+    Url,
+    /// The URL's host resolves to at least one usable address.
+    Dns,
+    /// A connection to the host can be opened, including the TLS handshake
+    /// for `https` URLs. Checked with a `HEAD` request to the root of the
+    /// URL's origin, which records no heartbeat.
+    Connection,
+    /// A heartbeat sent to the URL succeeds.
+    Ping,
+}
+
+/// Result of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The check passed.
+    Pass,
+    /// The check failed; see the hint.
+    Fail,
+    /// The check was not run because an earlier one failed.
+    Skipped,
+}
+
+/// A check and its result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticCheck {
+    /// What was checked.
+    pub kind: CheckKind,
+    /// Whether it passed.
+    pub outcome: CheckOutcome,
+    /// What was found, e.g. the resolved addresses or the error.
+    pub detail: String,
+    /// How to fix a failed check.
+    pub hint: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn pass(kind: CheckKind, detail: impl Into<String>) -> Self {
+        Self { kind, outcome: CheckOutcome::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn fail(kind: CheckKind, detail: impl Into<String>, hint: &str) -> Self {
+        Self {
+            kind,
+            outcome: CheckOutcome::Fail,
+            detail: detail.into(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn skipped(kind: CheckKind) -> Self {
+        Self {
+            kind,
+            outcome: CheckOutcome::Skipped,
+            detail: "skipped after an earlier failure".to_string(),
+            hint: None,
+        }
+    }
+}
+
+/// Results of [`diagnose`], one entry per [`CheckKind`] in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticsReport {
+    /// All checks, including skipped ones.
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl DiagnosticsReport {
+    /// Whether every check passed.
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome == CheckOutcome::Pass)
+    }
+
+    /// The result of the check of `kind`.
+    #[must_use]
+    pub fn check(&self, kind: CheckKind) -> Option<&DiagnosticCheck> {
+        self.checks.iter().find(|check| check.kind == kind)
+    }
+}
+
+impl fmt::Display for DiagnosticsReport {
+    /// One line per check, followed by its hint if it failed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{:?}: {:?} ({})", check.kind, check.outcome, check.detail)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "  hint: {hint}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check `config` for common misconfigurations, e.g. for a `doctor`
+/// command.
+///
+/// Runs the checks of [`CheckKind`] in order; once one fails the rest are
+/// skipped. The final check sends one real heartbeat to the configured URL,
+/// with the configured headers, timeout and retries. It is not counted in
+/// the statistics of any loop. The URL only appears redacted in the report.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, diagnose};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig::from_env().unwrap_or_default();
+///     let report = diagnose(&config).await;
+///     print!("{report}");
+///     std::process::exit(i32::from(!report.passed()));
+/// }
+/// ```
+pub async fn diagnose(config: &HeartbeatConfig) -> DiagnosticsReport {
+    let config = config.clone().resolve_interval();
+    let mut checks = Vec::new();
+
+    let url = check_url(&config, &mut checks);
+    let resolved = match &url {
+        Some(url) => check_dns(&config, url, &mut checks).await,
+        None => false,
+    };
+    let client = match &url {
+        Some(url) if resolved => check_connection(&config, url, &mut checks).await,
+        _ => None,
+    };
+    if let Some(client) = client {
+        checks.push(check_ping(&config, &client).await);
+    }
+
+    for kind in [CheckKind::Url, CheckKind::Dns, CheckKind::Connection, CheckKind::Ping] {
+        if checks.iter().all(|check| check.kind != kind) {
+            checks.push(DiagnosticCheck::skipped(kind));
+        }
+    }
+    DiagnosticsReport { checks }
+}
+
+fn check_url(config: &HeartbeatConfig, checks: &mut Vec<DiagnosticCheck>) -> Option<reqwest::Url> {
+    let check = match config.validate() {
+        Err(e) => {
+            let url_error = match &e {
+                ConfigError::MissingUrl => true,
+                ConfigError::InvalidValue { key, .. } => key == "url",
+                _ => false,
+            };
+            let hint = if url_error {
+                "Set HEARTBEAT_URL to the heartbeat URL shown in the Better Uptime monitor's settings"
+            } else {
+                "Fix the reported setting"
+            };
+            DiagnosticCheck::fail(CheckKind::Url, e.to_string(), hint)
+        }
+        Ok(()) => match reqwest::Url::parse(&config.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                checks.push(DiagnosticCheck::pass(CheckKind::Url, config.redacted_url()));
+                return Some(url);
+            }
+            _ => DiagnosticCheck::fail(
+                CheckKind::Url,
+                format!("unsupported URL {}", config.redacted_url()),
+                "Use an http:// or https:// URL",
+            ),
+        },
+    };
+    checks.push(check);
+    None
+}
+
+async fn check_dns(
+    config: &HeartbeatConfig,
+    url: &reqwest::Url,
+    checks: &mut Vec<DiagnosticCheck>,
+) -> bool {
+    let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+    let port = url.port_or_known_default().unwrap_or(443);
+    let lookup = tokio::net::lookup_host((host, port));
+
+    let check = match tokio::time::timeout(Duration::from_secs(config.timeout_secs), lookup).await {
+        Err(_) => DiagnosticCheck::fail(
+            CheckKind::Dns,
+            format!("resolving {host} timed out"),
+            "Check the DNS configuration of this machine",
+        ),
+        Ok(Err(e)) => DiagnosticCheck::fail(
+            CheckKind::Dns,
+            format!("resolving {host} failed: {e}"),
+            "Check the host name in the URL and the DNS configuration of this machine",
+        ),
+        Ok(Ok(addrs)) => {
+            let family = config.ip_family.unwrap_or_default();
+            let usable = addrs
+                .filter(|addr| match family {
+                    IpFamily::V4Only => addr.is_ipv4(),
+                    IpFamily::V6Only => addr.is_ipv6(),
+                    IpFamily::Auto => true,
+                })
+                .count();
+            if usable == 0 {
+                DiagnosticCheck::fail(
+                    CheckKind::Dns,
+                    format!("{host} has no address usable with {family:?}"),
+                    "Change ip_family or use a host with an address of that family",
+                )
+            } else {
+                DiagnosticCheck::pass(
+                    CheckKind::Dns,
+                    format!("{host} resolved to {usable} address(es)"),
+                )
+            }
+        }
+    };
+
+    let passed = check.outcome == CheckOutcome::Pass;
+    checks.push(check);
+    passed
+}
+
+/// Returns the client used if the connection check passed.
+async fn check_connection(
+    config: &HeartbeatConfig,
+    url: &reqwest::Url,
+    checks: &mut Vec<DiagnosticCheck>,
+) -> Option<reqwest::Client> {
+    let client = match build_client(config) {
+        Ok(client) => client,
+        Err(e) => {
+            checks.push(DiagnosticCheck::fail(
+                CheckKind::Connection,
+                format!("failed to create HTTP client: {e}"),
+                "Check the TLS and proxy settings of this machine",
+            ));
+            return None;
+        }
+    };
+
+    // Any response, whatever its status, proves the connection works. The
+    // heartbeat URL itself would already count as a heartbeat.
+    let mut origin = url.clone();
+    origin.set_path("/");
+    origin.set_query(None);
+    let check = match client.head(origin).send().await {
+        Ok(_) => DiagnosticCheck::pass(CheckKind::Connection, "connected"),
+        Err(e) if e.is_timeout() => DiagnosticCheck::fail(
+            CheckKind::Connection,
+            format!("connection timed out after {}s", config.timeout_secs),
+            "Check that outbound traffic to the host is allowed by firewalls and proxies",
+        ),
+        Err(e) => DiagnosticCheck::fail(
+            CheckKind::Connection,
+            format!("connection failed: {}", e.without_url()),
+            "Check outbound access to the host and, for https URLs, that the system clock \
+             and CA certificates are correct",
+        ),
+    };
+
+    let passed = check.outcome == CheckOutcome::Pass;
+    checks.push(check);
+    passed.then_some(client)
+}
+
+async fn check_ping(config: &HeartbeatConfig, client: &reqwest::Client) -> DiagnosticCheck {
+    let outcome = send_heartbeat(client, config, &Status::default(), 0).await;
+    match outcome.status_code {
+        _ if outcome.success => DiagnosticCheck::pass(CheckKind::Ping, "heartbeat accepted"),
+        Some(code @ (401 | 403 | 404)) => DiagnosticCheck::fail(
+            CheckKind::Ping,
+            format!("heartbeat rejected with status {code}"),
+            "The heartbeat token is not recognized; copy the URL from the monitor again",
+        ),
+        Some(code) => DiagnosticCheck::fail(
+            CheckKind::Ping,
+            format!("heartbeat failed with status {code}"),
+            "The endpoint is reachable but did not accept the heartbeat; check the URL and \
+             success_statuses",
+        ),
+        None => DiagnosticCheck::fail(
+            CheckKind::Ping,
+            "heartbeat failed without a response",
+            "The connection works but the heartbeat request did not complete; check \
+             timeout_secs",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    fn outcomes(report: &DiagnosticsReport) -> Vec<(CheckKind, CheckOutcome)> {
+        report.checks.iter().map(|check| (check.kind, check.outcome)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_bad_url_is_flagged() {
+        let config =
+            HeartbeatConfig { url: "not a url/TOKEN".to_string(), ..HeartbeatConfig::default() };
+
+        let report = diagnose(&config).await;
+
+        assert!(!report.passed());
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (CheckKind::Url, CheckOutcome::Fail),
+                (CheckKind::Dns, CheckOutcome::Skipped),
+                (CheckKind::Connection, CheckOutcome::Skipped),
+                (CheckKind::Ping, CheckOutcome::Skipped),
+            ]
+        );
+        assert!(report.check(CheckKind::Url).unwrap().hint.is_some());
+        assert!(!report.to_string().contains("TOKEN"));
+    }
+
+    #[tokio::test]
+    async fn test_unresolvable_host_is_flagged() {
+        let config = HeartbeatConfig {
+            url: "https://heartbeat.example.invalid/TOKEN".to_string(),
+            timeout_secs: 5,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = diagnose(&config).await;
+
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (CheckKind::Url, CheckOutcome::Pass),
+                (CheckKind::Dns, CheckOutcome::Fail),
+                (CheckKind::Connection, CheckOutcome::Skipped),
+                (CheckKind::Ping, CheckOutcome::Skipped),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reachable_endpoint_passes() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig { url: server.url(), ..HeartbeatConfig::default() };
+
+        let report = diagnose(&config).await;
+
+        assert!(report.passed(), "{report}");
+        let requests: Vec<_> = server.requests().into_iter().map(|r| (r.method, r.path)).collect();
+        assert_eq!(
+            requests,
+            vec![
+                ("HEAD".to_string(), "/".to_string()),
+                ("GET".to_string(), "/heartbeat".to_string())
+            ]
+        );
+    }
+}
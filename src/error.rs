@@ -0,0 +1,161 @@
+//! Error types.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Error returned when a heartbeat configuration is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// No heartbeat URL was given, or it is empty.
+    MissingUrl,
+    /// A key that is not recognized.
+    UnknownKey(String),
+    /// A value that could not be parsed for `key`.
+    InvalidValue {
+        /// The key the value was given for.
+        key: String,
+        /// The rejected value.
+        value: String,
+    },
+    /// Input that does not follow the expected syntax.
+    Malformed(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingUrl => write!(f, "heartbeat URL is missing or empty"),
+            Self::UnknownKey(key) => write!(f, "unknown heartbeat config key `{key}`"),
+            Self::InvalidValue { key, value } => {
+                write!(f, "invalid value `{value}` for heartbeat config key `{key}`")
+            }
+            Self::Malformed(input) => write!(f, "malformed heartbeat config `{input}`"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Description of a failed heartbeat, with the error that caused it if any.
+///
+/// It displays as the description; the cause is the
+/// [`source`](std::error::Error::source) of the [`HeartbeatError`].
+/// Neither contains the heartbeat URL. Two details are equal if their
+/// descriptions are.
+#[derive(Debug, Clone)]
+pub struct ErrorDetail {
+    description: String,
+    cause: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl ErrorDetail {
+    /// A detail without an underlying error.
+    pub fn new(description: impl Into<String>) -> Self {
+        Self { description: description.into(), cause: None }
+    }
+
+    /// A detail caused by `cause`, which must not contain the heartbeat URL.
+    pub fn with_cause(
+        description: impl Into<String>,
+        cause: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self { description: description.into(), cause: Some(Arc::new(cause)) }
+    }
+
+    /// The description, without the heartbeat URL.
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl PartialEq for ErrorDetail {
+    fn eq(&self, other: &Self) -> bool {
+        self.description == other.description
+    }
+}
+
+impl Eq for ErrorDetail {}
+
+impl fmt::Display for ErrorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+impl From<String> for ErrorDetail {
+    fn from(description: String) -> Self {
+        Self::new(description)
+    }
+}
+
+impl From<&str> for ErrorDetail {
+    fn from(description: &str) -> Self {
+        Self::new(description)
+    }
+}
+
+/// Error returned when a heartbeat cannot be started or sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeartbeatError {
+    /// The configuration is invalid.
+    Config(ConfigError),
+    /// The config interceptor rejected the configuration, with its reason.
+    Rejected(String),
+    /// The HTTP client could not be created.
+    Client(String),
+    /// A heartbeat got no response within the configured timeout.
+    Timeout,
+    /// A heartbeat could not connect to the host, e.g. because its name
+    /// does not resolve or the connection was refused, with the reason.
+    Connect(ErrorDetail),
+    /// A heartbeat got a response with a status not counted as success.
+    Http(reqwest::StatusCode),
+    /// A heartbeat failed in another way, e.g. its response body was
+    /// rejected, with the reason.
+    Other(ErrorDetail),
+}
+
+impl HeartbeatError {
+    /// Status code of the response for [`HeartbeatError::Http`], `None`
+    /// otherwise.
+    #[must_use]
+    pub const fn status_code(&self) -> Option<u16> {
+        match self {
+            Self::Http(status) => Some(status.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HeartbeatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "invalid heartbeat config: {e}"),
+            Self::Rejected(reason) => write!(f, "heartbeat config rejected: {reason}"),
+            Self::Client(e) => write!(f, "failed to create HTTP client for heartbeat: {e}"),
+            Self::Timeout => write!(f, "heartbeat timed out"),
+            Self::Connect(e) => write!(f, "heartbeat could not connect: {e}"),
+            Self::Http(status) => write!(f, "heartbeat failed with status {status}"),
+            Self::Other(e) => write!(f, "heartbeat failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HeartbeatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Config(e) => Some(e),
+            Self::Connect(detail) | Self::Other(detail) => {
+                detail.cause.as_deref().map(|cause| cause as &(dyn std::error::Error + 'static))
+            }
+            Self::Rejected(_) | Self::Client(_) | Self::Timeout | Self::Http(_) => None,
+        }
+    }
+}
+
+impl From<ConfigError> for HeartbeatError {
+    fn from(e: ConfigError) -> Self {
+        Self::Config(e)
+    }
+}
@@ -0,0 +1,34 @@
+//! Lifecycle events sent to [`HeartbeatConfig::events_tx`].
+
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::HeartbeatConfig;
+
+/// A change in the state of a heartbeat loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// The loop's task was spawned.
+    Spawned,
+    /// The first heartbeat succeeded.
+    FirstActive,
+    /// The loop was paused with [`HeartbeatHandle::pause`](crate::HeartbeatHandle::pause).
+    Paused,
+    /// The loop was resumed with [`HeartbeatHandle::resume`](crate::HeartbeatHandle::resume).
+    Resumed,
+    /// The loop was asked to stop, or decided to stop by itself.
+    Stopping,
+    /// The loop has exited; no further heartbeats are sent.
+    Stopped,
+}
+
+/// Send `event` without waiting. Events are dropped if the channel is full
+/// or its receiver is gone, so a slow or absent consumer never stalls the loop.
+pub fn emit(config: &HeartbeatConfig, event: LifecycleEvent) {
+    let Some(tx) = &config.events_tx else {
+        return;
+    };
+
+    if let Err(TrySendError::Full(event)) = tx.try_send(event) {
+        tracing::debug!("Lifecycle event channel full, dropping {:?}", event);
+    }
+}
@@ -0,0 +1,159 @@
+//! Separate failure thresholds for transport and HTTP errors.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{CallbackContext, HeartbeatConfig};
+
+/// Kind of a failed heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// No response was received: connection errors and timeouts, which
+    /// usually mean network trouble.
+    Transport,
+    /// A response was received but not counted as success, e.g. a `404`
+    /// that usually means a wrong URL.
+    Http,
+}
+
+/// Callback invoked when a [`FailurePolicy`] threshold is reached, with the
+/// failure class and the length of its streak.
+///
+/// Cloning is cheap; clones share the same function.
+#[derive(Clone)]
+pub struct FailureHook(Arc<FailureFn>);
+
+type FailureFn = dyn Fn(FailureClass, u32, &CallbackContext) + Send + Sync;
+
+impl FailureHook {
+    /// Wrap a function receiving the class and streak length.
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(FailureClass, u32) + Send + Sync + 'static,
+    {
+        Self(Arc::new(move |class, streak, _: &CallbackContext| hook(class, streak)))
+    }
+
+    /// Wrap a function receiving details of the failure that completed the
+    /// streak, with the class in [`CallbackContext::failure`] and the streak
+    /// length in [`CallbackContext::consecutive_failures`].
+    pub fn with_context<F>(hook: F) -> Self
+    where
+        F: Fn(&CallbackContext) + Send + Sync + 'static,
+    {
+        Self(Arc::new(move |_, _, context: &CallbackContext| hook(context)))
+    }
+
+    fn call(&self, class: FailureClass, streak: u32, context: &CallbackContext) {
+        (self.0)(class, streak, context);
+    }
+}
+
+impl fmt::Debug for FailureHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FailureHook(..)")
+    }
+}
+
+/// What happens when a [`FailurePolicy`] threshold is reached.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FailureAction {
+    /// Only log an error.
+    Log,
+    /// Log an error and call the hook. Cannot be serialized or
+    /// deserialized with the `serde` feature.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Call(FailureHook),
+    /// Log an error and stop the loop with
+    /// [`StopReason::FailurePolicy`](crate::StopReason::FailurePolicy).
+    Stop,
+}
+
+/// Action taken after `threshold` consecutive failures of one
+/// [`FailureClass`].
+///
+/// Each class has its own streak, counted since the last success and
+/// unaffected by failures of the other class. The action fires once per
+/// streak, when it reaches `threshold`.
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::{FailureAction, FailureHook, FailurePolicy, HeartbeatConfig};
+///
+/// let config = HeartbeatConfig {
+///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///     // A wrong URL will not fix itself.
+///     http_failure_policy: Some(FailurePolicy::new(3, FailureAction::Stop)),
+///     transport_failure_policy: Some(FailurePolicy::new(
+///         10,
+///         FailureAction::Call(FailureHook::new(|_, streak| {
+///             eprintln!("heartbeat unreachable {streak} times in a row");
+///         })),
+///     )),
+///     ..HeartbeatConfig::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FailurePolicy {
+    /// Number of consecutive failures of the class that triggers `action`.
+    pub threshold: u32,
+    /// What to do when the threshold is reached.
+    pub action: FailureAction,
+}
+
+impl FailurePolicy {
+    /// Take `action` after `threshold` consecutive failures.
+    #[must_use]
+    pub const fn new(threshold: u32, action: FailureAction) -> Self {
+        Self { threshold, action }
+    }
+}
+
+/// Consecutive failures per class, kept by the loop.
+#[derive(Debug, Default)]
+pub struct FailureStreaks {
+    transport: u32,
+    http: u32,
+}
+
+impl FailureStreaks {
+    /// Count a heartbeat outcome, `None` for a success, and run the
+    /// configured action if a streak just reached its threshold. Returns the
+    /// class whose policy asks the loop to stop, if any.
+    pub fn record(
+        &mut self,
+        config: &HeartbeatConfig,
+        failure: Option<FailureClass>,
+        status_code: Option<u16>,
+    ) -> Option<FailureClass> {
+        let Some(class) = failure else {
+            *self = Self::default();
+            return None;
+        };
+        let (streak, policy) = match class {
+            FailureClass::Transport => (&mut self.transport, &config.transport_failure_policy),
+            FailureClass::Http => (&mut self.http, &config.http_failure_policy),
+        };
+        *streak = streak.saturating_add(1);
+
+        let policy = policy.as_ref().filter(|policy| policy.threshold == *streak)?;
+        tracing::error!("Heartbeat failed {} times in a row with {:?} errors", streak, class);
+        match &policy.action {
+            FailureAction::Log => None,
+            FailureAction::Call(hook) => {
+                let context = CallbackContext {
+                    url: config.redacted_url(),
+                    failure: Some(class),
+                    status_code,
+                    consecutive_failures: u64::from(*streak),
+                };
+                hook.call(class, *streak, &context);
+                None
+            }
+            FailureAction::Stop => Some(class),
+        }
+    }
+}
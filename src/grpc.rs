@@ -0,0 +1,109 @@
+//! Heartbeats sent as gRPC health checks, with the `grpc` feature.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+
+use crate::HeartbeatConfig;
+use crate::runner::AttemptError;
+
+/// `grpc.health.v1.Health/Check` call for one service on the gRPC server at
+/// a loop's URL.
+#[derive(Debug, Clone)]
+pub struct HealthProbe {
+    client: HealthClient<Channel>,
+    service: String,
+}
+
+impl HealthProbe {
+    /// Probe of `service` on the server at `config.url`, connecting on first
+    /// use. `https` URLs are checked against the webpki root certificates.
+    pub fn new(config: &HeartbeatConfig, service: String) -> Result<Self, tonic::transport::Error> {
+        let mut endpoint = Endpoint::from_shared(config.url.clone())?
+            .connect_timeout(Duration::from_secs(config.timeout_secs));
+        if endpoint.uri().scheme_str() == Some("https") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+        }
+        Ok(Self { client: HealthClient::new(endpoint.connect_lazy()), service })
+    }
+
+    /// The same probe for an updated `config`.
+    pub fn for_config(&self, config: &HeartbeatConfig) -> Result<Self, tonic::transport::Error> {
+        Self::new(config, self.service.clone())
+    }
+
+    /// Check the service once, bounded by the configured timeout.
+    ///
+    /// `SERVING` is reported as status 200, the HTTP status of every gRPC
+    /// response; the latency is the time until the response arrived.
+    pub async fn attempt(&self, config: &HeartbeatConfig) -> (Result<u16, AttemptError>, Duration) {
+        #[cfg(feature = "registry")]
+        crate::registry::acquire().await;
+        let started = Instant::now();
+        let deadline = Duration::from_secs(config.timeout_secs);
+        let request = HealthCheckRequest { service: self.service.clone() };
+
+        let result = match tokio::time::timeout(deadline, self.client.clone().check(request)).await
+        {
+            Ok(Ok(response)) => match response.into_inner().status() {
+                ServingStatus::Serving => Ok(200),
+                status => Err(AttemptError::NotServing(status.as_str_name())),
+            },
+            Ok(Err(status)) => Err(AttemptError::Grpc(Box::new(status))),
+            Err(_) => Err(AttemptError::DeadlineExceeded(deadline)),
+        };
+        (result, started.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FailureKind, FirstTickPolicy, HeartbeatStats, spawn_with_grpc_health};
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+    use tonic_health::ServingStatus as ReportedStatus;
+
+    #[tokio::test]
+    async fn test_serving_status_decides_the_outcome() {
+        let (reporter, service) = tonic_health::server::health_reporter();
+        reporter.set_service_status("billing", ReportedStatus::Serving).await;
+        let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = incoming.local_addr().unwrap();
+        let server =
+            tokio::spawn(Server::builder().add_service(service).serve_with_incoming(incoming));
+
+        let heartbeat = spawn_with_grpc_health(
+            HeartbeatConfig {
+                url: format!("http://{addr}"),
+                interval_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                max_retries: 0,
+                ..HeartbeatConfig::default()
+            },
+            "billing",
+        );
+        let wait_until = |done: fn(&HeartbeatStats) -> bool| {
+            let heartbeat = &heartbeat;
+            tokio::time::timeout(Duration::from_secs(5), async move {
+                while !done(&heartbeat.stats()) {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+        };
+        wait_until(|stats| stats.successes > 0).await.expect("health check should succeed");
+        reporter.set_service_status("billing", ReportedStatus::NotServing).await;
+        wait_until(|stats| stats.failures > 0).await.expect("health check should fail");
+        let failures = heartbeat.recent_failures();
+        let stats = heartbeat.close().await;
+        server.abort();
+
+        assert_eq!((stats.successes, stats.failures), (1, 1));
+        assert_eq!(failures[0].kind, FailureKind::Other);
+        assert!(failures[0].message.contains("NOT_SERVING"), "{}", failures[0].message);
+    }
+}
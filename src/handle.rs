@@ -0,0 +1,410 @@
+//! Control handle for a spawned heartbeat loop.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::{
+    BoxFuture, FailureRecord, HeartbeatConfig, HeartbeatObserver, HeartbeatStats,
+    HeartbeatStatusHandle, HeartbeatStatusSnapshot, HeartbeatTransport, NoopObserver, SkipReason,
+};
+
+/// State requested for the loop by its [`HeartbeatHandle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Run,
+    Pause,
+    Stop,
+}
+
+/// Latest config passed to [`HeartbeatHandle::update_config`].
+#[derive(Debug, Default)]
+pub struct ConfigUpdate {
+    /// Number of updates sent so far.
+    pub count: u64,
+    pub config: Option<HeartbeatConfig>,
+}
+
+/// Sending side of a loop's control channels, held by its handle.
+#[derive(Debug)]
+pub struct Controller {
+    state: watch::Sender<Control>,
+    updates: watch::Sender<ConfigUpdate>,
+    /// Number of `trigger` calls so far.
+    triggers: watch::Sender<u64>,
+}
+
+/// Health check of a loop started with
+/// [`spawn_with_health_check`](crate::spawn_with_health_check).
+pub type HealthCheck = Arc<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// Receiving side of a loop's control channels, owned by the loop.
+pub struct LoopControl {
+    pub state: watch::Receiver<Control>,
+    pub updates: watch::Receiver<ConfigUpdate>,
+    pub triggers: watch::Receiver<u64>,
+    /// Receives the outcome of every heartbeat.
+    pub observer: Arc<dyn HeartbeatObserver>,
+    /// Client to use instead of building one from the config.
+    pub client: Option<reqwest::Client>,
+    /// Check that must pass before each heartbeat.
+    pub health_check: Option<HealthCheck>,
+    /// Transport pinging instead of the HTTP client.
+    pub transport: Option<Arc<dyn HeartbeatTransport>>,
+    /// Service whose gRPC health is checked instead of sending HTTP
+    /// requests.
+    #[cfg(feature = "grpc")]
+    pub grpc_service: Option<String>,
+    /// Resolves when the loop should shut down, with whether to send a
+    /// final heartbeat first.
+    pub shutdown: Option<BoxFuture<'static, bool>>,
+}
+
+impl LoopControl {
+    /// Controls of a loop without a handle: it is never paused, stopped or
+    /// updated.
+    pub fn detached() -> Self {
+        loop_control().1
+    }
+}
+
+/// Create the control channels of a new loop.
+pub fn loop_control() -> (Controller, LoopControl) {
+    let (state, state_rx) = watch::channel(Control::Run);
+    let (updates, updates_rx) = watch::channel(ConfigUpdate::default());
+    let (triggers, triggers_rx) = watch::channel(0);
+    let control = LoopControl {
+        state: state_rx,
+        updates: updates_rx,
+        triggers: triggers_rx,
+        observer: Arc::new(NoopObserver),
+        client: None,
+        health_check: None,
+        transport: None,
+        #[cfg(feature = "grpc")]
+        grpc_service: None,
+        shutdown: None,
+    };
+    (Controller { state, updates, triggers }, control)
+}
+
+/// What happens to a loop when its [`HeartbeatHandle`] is dropped without
+/// being closed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HandleDropPolicy {
+    /// The loop keeps running until the runtime shuts down.
+    #[default]
+    Detach,
+    /// The loop is aborted, like a task whose `JoinHandle` is aborted; a
+    /// heartbeat in flight is cancelled.
+    Abort,
+}
+
+/// Handle to a heartbeat loop started with [`spawn_with_handle`](crate::spawn_with_handle).
+///
+/// Dropping the handle detaches the loop, which then keeps running until
+/// the runtime shuts down, unless
+/// [`HeartbeatConfig::on_handle_drop`](crate::HeartbeatConfig::on_handle_drop)
+/// is [`HandleDropPolicy::Abort`].
+#[derive(Debug)]
+pub struct HeartbeatHandle {
+    task: JoinHandle<()>,
+    status: HeartbeatStatusHandle,
+    controller: Controller,
+    /// Config of the startup ping, taken when it is sent.
+    startup: Mutex<Option<HeartbeatConfig>>,
+    /// Set with [`HandleDropPolicy::Abort`] until the handle is closed.
+    abort_on_drop: bool,
+    /// Number of `record_operation` calls so far.
+    operations: AtomicU64,
+    ops_per_heartbeat: Option<u64>,
+}
+
+impl HeartbeatHandle {
+    pub(crate) fn new(
+        task: JoinHandle<()>,
+        status: HeartbeatStatusHandle,
+        controller: Controller,
+        startup: Option<HeartbeatConfig>,
+        on_drop: HandleDropPolicy,
+        ops_per_heartbeat: Option<u64>,
+    ) -> Self {
+        Self {
+            task,
+            status,
+            controller,
+            startup: Mutex::new(startup),
+            abort_on_drop: on_drop == HandleDropPolicy::Abort,
+            operations: AtomicU64::new(0),
+            // Unvalidated spawns may pass zero, which never triggers.
+            ops_per_heartbeat: ops_per_heartbeat.filter(|&per| per > 0),
+        }
+    }
+
+    /// A status handle observing this loop.
+    #[must_use]
+    pub fn status(&self) -> HeartbeatStatusHandle {
+        self.status.clone()
+    }
+
+    /// Current statistics of the loop.
+    #[must_use]
+    pub fn stats(&self) -> HeartbeatStats {
+        self.status.stats()
+    }
+
+    /// Current state as a serializable snapshot.
+    #[must_use]
+    pub fn status_snapshot(&self) -> HeartbeatStatusSnapshot {
+        self.status.status_snapshot()
+    }
+
+    /// Whether the heartbeat is healthy, see [`HeartbeatStatusHandle::is_ready`].
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.status.is_ready()
+    }
+
+    /// Recent attempt latencies, oldest first, see
+    /// [`HeartbeatStatusHandle::latency_samples`].
+    #[must_use]
+    pub fn latency_samples(&self) -> Vec<Duration> {
+        self.status.latency_samples()
+    }
+
+    /// Scheduled heartbeats not sent, by reason, see
+    /// [`HeartbeatStatusHandle::skip_counts`].
+    #[must_use]
+    pub fn skip_counts(&self) -> HashMap<SkipReason, u64> {
+        self.status.skip_counts()
+    }
+
+    /// Recent failed heartbeats, oldest first, see
+    /// [`HeartbeatStatusHandle::recent_failures`].
+    #[must_use]
+    pub fn recent_failures(&self) -> Vec<FailureRecord> {
+        self.status.recent_failures()
+    }
+
+    /// Share of successful heartbeats within the last `window`, see
+    /// [`HeartbeatStatusHandle::availability_over`].
+    #[must_use]
+    pub fn availability_over(&self, window: Duration) -> Option<f64> {
+        self.status.availability_over(window)
+    }
+
+    /// Wall-clock time at which the next heartbeat is scheduled, see
+    /// [`HeartbeatStatusHandle::next_send_at`].
+    #[must_use]
+    pub fn next_send_at(&self) -> Option<SystemTime> {
+        self.status.next_send_at()
+    }
+
+    /// Report one passed readiness check.
+    ///
+    /// With [`HeartbeatConfig::ready_marks_required`](crate::HeartbeatConfig::ready_marks_required)
+    /// set, no heartbeat is sent until this has been called that many times,
+    /// e.g. once each by the database, cache and queue health checks. Calls
+    /// after the gate opened have no effect.
+    pub fn mark_ready(&self) {
+        self.status.mark_ready();
+    }
+
+    /// Send the one-time "startup complete" ping to
+    /// [`HeartbeatConfig::startup_url`](crate::HeartbeatConfig::startup_url).
+    ///
+    /// Call this once the application has finished initializing. The ping
+    /// is sent in the background with the heartbeat's request settings
+    /// (timeout, retries, headers, ...) and is not counted in the
+    /// statistics. Only the first call sends it; later calls, and calls
+    /// without a `startup_url`, do nothing.
+    pub fn signal_startup_complete(&self) {
+        let startup = self.startup.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(config) = startup {
+            let span = crate::loop_span(&config);
+            tokio::spawn(tracing::Instrument::instrument(
+                crate::runner::send_startup_ping(config),
+                span,
+            ));
+        }
+    }
+
+    /// Expect failures until `until`, e.g. during planned maintenance.
+    ///
+    /// Heartbeats are still sent, but failed ones are logged at info level
+    /// as expected and are not recorded: they count neither towards the
+    /// statistics nor the failure streaks that drive
+    /// [`HeartbeatConfig::abort_after_failures`](crate::HeartbeatConfig::abort_after_failures)
+    /// and failure policies. Successes are recorded as usual. Afterwards,
+    /// failures count normally again. Replaces any earlier window.
+    pub fn suppress_failures_until(&self, until: Instant) {
+        self.status.suppress_failures_until(until);
+    }
+
+    /// Send a heartbeat now, out of schedule.
+    ///
+    /// The heartbeat is sent like a scheduled one, without
+    /// [jitter](crate::HeartbeatConfig::jitter_secs), and counted in the
+    /// statistics. By default the schedule is unaffected and the next
+    /// heartbeat follows at its usual time; with
+    /// [`HeartbeatConfig::realign_after_trigger`](crate::HeartbeatConfig::realign_after_trigger)
+    /// it follows a full interval after the triggered one. Triggers
+    /// while a heartbeat is in flight, while the loop is paused or while it
+    /// waits for [`mark_ready`](Self::mark_ready) are combined into one,
+    /// sent as soon as the loop can.
+    pub fn trigger(&self) {
+        self.controller.triggers.send_modify(|count| *count += 1);
+    }
+
+    /// Count one completed unit of work and [`trigger`](Self::trigger) a
+    /// heartbeat on every
+    /// [`ops_per_heartbeat`](crate::HeartbeatConfig::ops_per_heartbeat)th
+    /// call. Does nothing if that is not set or zero.
+    pub fn record_operation(&self) {
+        let Some(per) = self.ops_per_heartbeat else {
+            return;
+        };
+        let operations = self.operations.fetch_add(1, Ordering::Relaxed) + 1;
+        if operations % per == 0 {
+            self.trigger();
+        }
+    }
+
+    /// Stop sending heartbeats until [`resume`](Self::resume) is called,
+    /// e.g. during planned maintenance.
+    ///
+    /// A heartbeat in flight is completed first. Better Uptime does not know
+    /// about the pause: once no heartbeat has arrived within the monitor's
+    /// period and grace time it reports the service as down, as it should
+    /// for a service taken offline. Use
+    /// [`suppress_failures_until`](Self::suppress_failures_until) instead
+    /// to keep sending while failures are expected.
+    pub fn pause(&self) {
+        self.controller.state.send_if_modified(|c| replace(c, Control::Run, Control::Pause));
+    }
+
+    /// Resume a paused loop. Its schedule restarts as if the loop had just
+    /// been spawned, including [`HeartbeatConfig::first_tick_policy`](crate::HeartbeatConfig::first_tick_policy),
+    /// or with [`HeartbeatConfig::keep_schedule_on_resume`](crate::HeartbeatConfig::keep_schedule_on_resume)
+    /// continues at the next heartbeat of the original schedule.
+    pub fn resume(&self) {
+        self.controller.state.send_if_modified(|c| replace(c, Control::Pause, Control::Run));
+    }
+
+    /// Replace the running loop's config.
+    ///
+    /// Updates are debounced: the loop applies the latest config passed
+    /// within [`HeartbeatConfig::config_debounce_ms`] of the first one and
+    /// logs how many were coalesced, so a flapping config source rebuilds
+    /// the HTTP client only once. The new config is
+    /// [validated](HeartbeatConfig::validate) and ignored with a warning if
+    /// it is invalid. Applying it restarts the schedule as if the loop had
+    /// just been spawned. `silent`, `on_transition`, `startup_grace_secs`,
+    /// `latency_samples`, `failure_history`, `availability_history_secs` and
+    /// `ops_per_heartbeat` keep the values the loop was spawned with.
+    pub fn update_config(&self, config: HeartbeatConfig) {
+        self.controller.updates.send_modify(|update| {
+            update.count += 1;
+            update.config = Some(config);
+        });
+    }
+
+    /// Abort the loop immediately, like [`JoinHandle::abort`].
+    ///
+    /// Unlike [`close`](Self::close), a heartbeat in flight is cancelled and
+    /// this does not wait for the loop to exit; use
+    /// [`is_finished`](Self::is_finished) to check that it has.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Whether the loop has exited, because it stopped by itself or was
+    /// aborted.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop the loop and wait until it has exited, returning the final
+    /// statistics.
+    ///
+    /// A heartbeat that is in flight when `close` is called is completed and
+    /// included in the returned statistics; no further heartbeats are sent.
+    /// Returns immediately if the loop has already stopped by itself.
+    pub async fn close(mut self) -> HeartbeatStats {
+        self.abort_on_drop = false;
+        self.controller.state.send_replace(Control::Stop);
+        let _ = (&mut self.task).await;
+        self.status.stats()
+    }
+}
+
+impl Drop for HeartbeatHandle {
+    fn drop(&mut self) {
+        if self.abort_on_drop {
+            self.task.abort();
+        }
+    }
+}
+
+/// Set `control` to `to` if it is `from`, returning whether it changed.
+fn replace(control: &mut Control, from: Control, to: Control) -> bool {
+    let changed = *control == from;
+    if changed {
+        *control = to;
+    }
+    changed
+}
+
+/// Resolve with the new requested state once it changes.
+///
+/// Never resolves if the handle is dropped first, so a detached loop keeps
+/// running.
+pub async fn control_changed(control: &mut watch::Receiver<Control>) -> Control {
+    if control.changed().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+    *control.borrow_and_update()
+}
+
+/// Resolve once a new config update has been sent.
+///
+/// Never resolves if the handle is dropped first.
+pub async fn config_updated(updates: &mut watch::Receiver<ConfigUpdate>) {
+    if updates.changed().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Resolve once a heartbeat has been triggered.
+///
+/// Never resolves if the handle is dropped first.
+pub async fn triggered(triggers: &mut watch::Receiver<u64>) {
+    if triggers.changed().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Resolve with whether to send a final heartbeat once shutdown is
+/// requested.
+///
+/// Never resolves for a loop without a shutdown signal.
+pub async fn shutdown_requested(shutdown: &mut Option<BoxFuture<'static, bool>>) -> bool {
+    match shutdown {
+        Some(shutdown) => shutdown.await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait while the loop is paused and return the state that ended the pause.
+///
+/// Dropping the handle of a paused loop resumes it.
+pub async fn wait_while_paused(control: &mut watch::Receiver<Control>) -> Control {
+    control.wait_for(|&c| c != Control::Pause).await.map_or(Control::Run, |c| *c)
+}
@@ -0,0 +1,54 @@
+//! Process-wide hook adjusting configs before a heartbeat is spawned.
+
+use std::sync::RwLock;
+
+use crate::{HeartbeatConfig, HeartbeatError};
+
+/// Function adjusting or rejecting a config, see [`set_config_interceptor`].
+pub type ConfigInterceptor = fn(HeartbeatConfig) -> Result<HeartbeatConfig, HeartbeatError>;
+
+static INTERCEPTOR: RwLock<Option<ConfigInterceptor>> = RwLock::new(None);
+
+/// Install a process-wide interceptor for heartbeat configs.
+///
+/// Every function starting a heartbeat loop, from [`spawn`](crate::spawn)
+/// and [`try_spawn`](crate::try_spawn) to [`run`](crate::run),
+/// [`spawn_shared`](crate::spawn_shared) and the other `spawn_with_*`
+/// variants, passes its config through the interceptor right before the
+/// loop starts, after [`HeartbeatConfig::resolve_interval`], e.g. to enforce
+/// an organization-wide minimum interval. The config it returns is then
+/// checked with [`HeartbeatConfig::validate`], so an interceptor cannot
+/// produce an invalid config; returning an error refuses the loop. Replaces
+/// any previously installed interceptor.
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::set_config_interceptor;
+///
+/// set_config_interceptor(|mut config| {
+///     config.interval_secs = config.interval_secs.max(30);
+///     Ok(config)
+/// });
+/// ```
+pub fn set_config_interceptor(interceptor: ConfigInterceptor) {
+    *INTERCEPTOR.write().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(interceptor);
+}
+
+/// Remove the interceptor installed with [`set_config_interceptor`].
+pub fn clear_config_interceptor() {
+    *INTERCEPTOR.write().unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+}
+
+/// Resolve the interval of `config`, run the installed interceptor, if any,
+/// then validate the result.
+pub fn apply(config: HeartbeatConfig) -> Result<HeartbeatConfig, HeartbeatError> {
+    let config = config.resolve_interval();
+    let interceptor = *INTERCEPTOR.read().unwrap_or_else(std::sync::PoisonError::into_inner);
+    let config = match interceptor {
+        Some(intercept) => intercept(config)?,
+        None => config,
+    };
+    config.validate()?;
+    Ok(config)
+}
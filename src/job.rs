@@ -0,0 +1,89 @@
+//! One-shot exit status reports for cron jobs.
+
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::request::{self, FAIL_SEGMENT};
+use crate::runner::{build_client, send_heartbeat};
+use crate::status::Status;
+use crate::{HeartbeatConfig, HeartbeatError};
+
+/// Report the exit status of a job with a single heartbeat.
+///
+/// An `exit_code` of `0` pings the configured URL; any other code pings its
+/// `/fail` variant, which Better Uptime records as a failed run. With
+/// [`HeartbeatConfig::include_exit_code`] set, a failed run pings
+/// `{url}/{exit_code}` instead, which records the failure together with its
+/// exit code. The heartbeat uses the configured timeout, retries and headers.
+///
+/// # Errors
+///
+/// Returns [`HeartbeatError::Config`] if the config is invalid,
+/// [`HeartbeatError::Client`] if the HTTP client cannot be created and
+/// one of the request errors, such as [`HeartbeatError::Http`], if the ping
+/// was not accepted.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, report_job};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         include_exit_code: true,
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let status = std::process::Command::new("./backup.sh").status().unwrap();
+///     let _ = report_job(config, status.code().unwrap_or(-1)).await;
+/// }
+/// ```
+pub async fn report_job(config: HeartbeatConfig, exit_code: i32) -> Result<(), HeartbeatError> {
+    let silent = config.silent;
+    let report = send_job_report(config, exit_code);
+    if silent { report.with_subscriber(NoSubscriber::default()).await } else { report.await }
+}
+
+async fn send_job_report(
+    mut config: HeartbeatConfig,
+    exit_code: i32,
+) -> Result<(), HeartbeatError> {
+    config.validate()?;
+    if exit_code != 0 {
+        let segment =
+            if config.include_exit_code { exit_code.to_string() } else { FAIL_SEGMENT.to_string() };
+        config = request::endpoint_config(&config, &segment);
+    }
+
+    let client = build_client(&config).map_err(HeartbeatError::Client)?;
+    let outcome = send_heartbeat(&client, &config, &Status::default(), 0).await;
+    outcome.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    #[tokio::test]
+    async fn test_exit_code_selects_url() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            include_exit_code: true,
+            ..HeartbeatConfig::default()
+        };
+
+        assert_eq!(report_job(config.clone(), 0).await, Ok(()));
+        assert_eq!(report_job(config.clone(), 1).await, Ok(()));
+        assert_eq!(
+            report_job(HeartbeatConfig { include_exit_code: false, ..config }, 2).await,
+            Ok(())
+        );
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, vec!["/heartbeat", "/heartbeat/1", "/heartbeat/fail"]);
+    }
+}
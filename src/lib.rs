@@ -23,6 +23,25 @@
 //! - Automatic error handling and retry (never panics)
 //! - Structured logging via `tracing`
 //!
+//! Optional functionality is behind cargo features. Only `rustls-tls`, for
+//! HTTPS with rustls and certificate expiry warnings, is enabled by default;
+//! turn it off and enable `native-tls` to use the platform's TLS library
+//! instead. The others are opt-in:
+//!
+//! - `http2`: HTTP/2 support ([`HttpVersion::Http2`], ALPN negotiation)
+//! - `serde`: persisted lifetime stats and `Serialize`/`Deserialize` for
+//!   [`HeartbeatConfig`]
+//! - `tower`: `HeartbeatService`, a `tower::Service` sending one heartbeat
+//!   per call
+//! - `otel`: W3C `traceparent` header from the active OpenTelemetry span
+//! - `registry`: process-wide coordination of all loops, such as a global
+//!   rate limit
+//! - `cancellation`: `spawn_with_shutdown`, stopping the loop with a
+//!   `CancellationToken`
+//! - `blocking`: `spawn_blocking`, heartbeats from a `std::thread`
+//! - `grpc`: `spawn_with_grpc_health`, heartbeats as gRPC health checks
+//! - `test-util`: `test_util::MockServer` for testing heartbeat wiring
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -33,10 +52,104 @@
 //! }
 //! ```
 
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+mod args;
+mod backoff;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod builder;
+#[cfg(feature = "rustls-tls")]
+mod cert;
+mod client;
+mod clock;
+mod diagnose;
+mod error;
+mod events;
+mod failure;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod handle;
+mod intercept;
+mod job;
+mod load;
+mod observer;
+#[cfg(feature = "serde")]
+mod persist;
+#[cfg(feature = "registry")]
+mod registry;
+mod request;
+mod runner;
+mod schedule;
+#[cfg(feature = "serde")]
+mod serde_method;
+#[cfg(feature = "tower")]
+mod service;
+mod shared;
+mod spec;
+mod status;
+#[cfg(test)]
+mod test_support;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod transport;
+mod validation;
+mod webhook;
+
+pub use backoff::BackoffStrategy;
+#[cfg(feature = "blocking")]
+pub use blocking::{BlockingHeartbeatHandle, spawn_blocking};
+pub use builder::HeartbeatConfigBuilder;
+pub use client::HeartbeatClient;
+pub use clock::{Clock, TokioClock};
+pub use diagnose::{CheckKind, CheckOutcome, DiagnosticCheck, DiagnosticsReport, diagnose};
+pub use error::{ConfigError, ErrorDetail, HeartbeatError};
+pub use events::LifecycleEvent;
+pub use failure::{FailureAction, FailureClass, FailureHook, FailurePolicy};
+use handle::LoopControl;
+pub use handle::{HandleDropPolicy, HeartbeatHandle};
+pub use intercept::{ConfigInterceptor, clear_config_interceptor, set_config_interceptor};
+pub use job::report_job;
+pub use load::{LoadSource, SystemLoad};
+pub use observer::{HeartbeatObserver, NoopObserver};
+#[cfg(feature = "registry")]
+pub use registry::{clear_global_rate_limit, set_global_rate_limit};
+pub use request::QueryConflictPolicy;
+use runner::heartbeat_loop;
+pub use runner::{ClientCustomizer, HeartbeatReport, run_report};
+#[cfg(feature = "tower")]
+pub use service::HeartbeatService;
+pub use shared::{SharedConfig, spawn_shared};
+use status::Status;
+pub use status::{
+    CallbackContext, FailureKind, FailureRecord, HealthTransition, HeartbeatStats,
+    HeartbeatStatusHandle, HeartbeatStatusSnapshot, SkipReason, StopReason, TransitionHook,
+};
+pub use transport::HeartbeatTransport;
+pub use validation::{BodyDecodePolicy, BodyValidator};
+pub use webhook::WebhookTarget;
+
+/// A boxed, sendable future, as returned by the crate's async callbacks.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 /// Configuration for heartbeat client.
 ///
+/// With the `serde` feature the config implements `Serialize` and
+/// `Deserialize`, so it can be read from the service's own config file.
+/// Keys are the field names and omitted fields take their [`Default`]
+/// values, e.g. 60 for `interval_secs` and 10 for `timeout_secs`. `method`
+/// is a name such as `"POST"`, enums are variant names such as
+/// `"FireImmediately"`. Hooks and other runtime objects (`body_validator`,
+/// `on_transition`, `events_tx`, `clock`, `load_source`,
+/// `client_customizer`) are skipped, as is a [`FailureAction::Call`]
+/// action, which fails serialization.
+///
 /// # Example
 ///
 /// ```rust
@@ -46,28 +159,530 @@ use std::time::Duration;
 ///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
 ///     interval_secs: 60,
 ///     timeout_secs: 10,
+///     ..HeartbeatConfig::default()
 /// };
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+#[allow(clippy::struct_excessive_bools)] // Independent on/off options, not a state machine
 pub struct HeartbeatConfig {
     /// Better Uptime heartbeat URL.
     pub url: String,
-    /// Interval between heartbeats in seconds (default: 60).
+    /// Interval between heartbeats in seconds (default: 60). Derived from
+    /// `monitor_period_secs` when that is set.
     pub interval_secs: u64,
+    /// Expected period of the Better Uptime monitor in seconds. When set,
+    /// `interval_secs` is computed as `monitor_period_secs * ping_fraction`
+    /// when the heartbeat starts, see [`HeartbeatConfig::resolve_interval`]
+    /// (default: none).
+    pub monitor_period_secs: Option<u64>,
+    /// Fraction of `monitor_period_secs` to ping at (default: 0.5, the
+    /// recommended half period).
+    pub ping_fraction: f64,
+    /// Upper bound of a random delay, in seconds, added before each
+    /// heartbeat so replicas started together do not ping in lockstep
+    /// (default: 0, no delay). Drawn afresh for every heartbeat from a
+    /// random source of the loop's own; the schedule itself is unaffected.
+    pub jitter_secs: u64,
     /// HTTP request timeout in seconds (default: 10).
     pub timeout_secs: u64,
+    /// Maximum idle connections kept per host (default: reqwest's default).
+    pub pool_max_idle_per_host: Option<usize>,
+    /// When the first heartbeat is sent (default: [`FirstTickPolicy::Skip`]).
+    pub first_tick_policy: FirstTickPolicy,
+    /// Seconds to wait after the loop starts before its schedule begins,
+    /// e.g. while caches warm up, so no heartbeat reports the service up
+    /// during boot; the first tick then follows `first_tick_policy`
+    /// (default: 0). Stopping the loop does not wait for the delay.
+    pub startup_delay_secs: u64,
+    /// Append the send time as a `ts={unix_millis}` query parameter to each
+    /// request, e.g. to correlate clock skew (default: false).
+    pub include_timestamp: bool,
+    /// Path segments appended to `url` on each request, e.g. `ok` or
+    /// `eu-west/ok`; slashes between the URL and the suffix are handled and
+    /// the URL's query is kept. Endpoints such as `/fail` and `/start` are
+    /// appended after it, as in `{url}/eu-west/fail` (default: none).
+    pub path_suffix: Option<String>,
+    /// Extra query parameters appended to `url` on each request (default:
+    /// none).
+    pub query_params: Vec<(String, String)>,
+    /// How `query_params` and `ts` are handled when `url` already has a
+    /// parameter with the same key (default: [`QueryConflictPolicy::Skip`]).
+    pub query_conflict_policy: QueryConflictPolicy,
+    /// Suppress all log output from the heartbeat task, regardless of the
+    /// installed subscriber (default: false).
+    pub silent: bool,
+    /// Mention the heartbeat URL, always in its
+    /// [redacted form](Self::redacted_url), in log events such as the
+    /// config source or a rejected config (default: true). Set it to false to
+    /// keep even the host out of the logs and tell loops apart by
+    /// [`name`](Self::name) instead; a rejected URL is then reported as
+    /// `***`. Failed requests are logged without the URL either way.
+    pub include_url_in_logs: bool,
+    /// Log a single summary line (heartbeats sent, successes, failures,
+    /// uptime and average latency) at info level when the loop exits,
+    /// however it exits (default: false).
+    pub log_summary: bool,
+    /// How many times a failed heartbeat is retried before waiting for the
+    /// next interval (default: 0).
+    pub max_retries: u32,
+    /// Base delay between retries in milliseconds (default: 500).
+    pub retry_base_delay_ms: u64,
+    /// How the retry delay grows (default: [`BackoffStrategy::Exponential`]).
+    pub backoff: BackoffStrategy,
+    /// Application name sent in the `X-Heartbeat-Client` header (default:
+    /// the running executable's file name, or `betteruptime-heartbeat` if it
+    /// cannot be determined).
+    pub app_name: Option<String>,
+    /// Name of this loop, e.g. the subsystem it monitors, recorded as the
+    /// `heartbeat.name` field of a span around every event the loop logs,
+    /// so the logs of several loops can be told apart and filtered
+    /// (default: none, no span).
+    pub name: Option<String>,
+    /// Compute every send time from the loop's start instant as
+    /// `start + n * interval` instead of chaining timer deadlines, and skip
+    /// slots missed because of a slow send rather than catching up
+    /// (default: false). Keeps very long-running loops aligned over days.
+    pub drift_compensation: bool,
+    /// What happens to ticks that pass while a slow heartbeat is still in
+    /// flight (default: [`OverlapPolicy::QueueOne`]). Ignored with
+    /// `drift_compensation`, which always skips them.
+    pub overlap_policy: OverlapPolicy,
+    /// Restart the schedule after a heartbeat sent with
+    /// [`HeartbeatHandle::trigger`], so the next one follows a full interval
+    /// later (default: false, the schedule continues as if the trigger had
+    /// not happened).
+    pub realign_after_trigger: bool,
+    /// Send a heartbeat after every this many calls to
+    /// [`HeartbeatHandle::record_operation`], for jobs whose progress is
+    /// better measured in completed units of work than in time; the timer
+    /// keeps running alongside (default: none, operations are not counted).
+    pub ops_per_heartbeat: Option<u64>,
+    /// Continue the original schedule when a loop paused with
+    /// [`HeartbeatHandle::pause`] is resumed, skipping the heartbeats that
+    /// fell into the pause (default: false, the schedule restarts as if the
+    /// loop had just been spawned).
+    pub keep_schedule_on_resume: bool,
+    /// Send the heartbeat that is due when the loop wakes up from a
+    /// suspected system suspend right away, without `jitter_secs` (default:
+    /// true). With false it is skipped and the loop waits for the next
+    /// scheduled heartbeat. A suspend is suspected, and logged at info
+    /// level, when the wait for a heartbeat took more than an interval
+    /// longer than scheduled, including when the system clock was set
+    /// forward by that much during the wait.
+    pub heartbeat_on_resume: bool,
+    /// What happens on a tick whose health check fails, for loops started
+    /// with [`spawn_with_health_check`] (default: [`UnhealthyPolicy::Skip`]).
+    pub unhealthy_policy: UnhealthyPolicy,
+    /// Async check of the body of each successful response; the heartbeat fails if
+    /// it returns `false` (default: none, the body is not read).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub body_validator: Option<BodyValidator>,
+    /// How a non-UTF-8 body is classified when `body_validator` is set
+    /// (default: [`BodyDecodePolicy::Fail`]).
+    pub body_decode_policy: BodyDecodePolicy,
+    /// `ETag` header every successful response must carry, compared
+    /// verbatim including quotes and any `W/` prefix, e.g. `"\"v42\""`; a
+    /// different or missing `ETag` fails the heartbeat, for endpoints whose
+    /// content changing means trouble (default: none, not checked).
+    pub expected_etag: Option<String>,
+    /// Warn when the endpoint's TLS certificate, as seen on a successful
+    /// heartbeat, expires within this many days, to catch a renewal that
+    /// failed before heartbeats do; the warning is logged once a day per
+    /// certificate (default: none, not checked). Requires
+    /// the `rustls-tls` feature; a client passed to [`spawn_with_client`]
+    /// must be built with `tls_info(true)`.
+    #[cfg(feature = "rustls-tls")]
+    pub warn_cert_expiry_days: Option<u32>,
+    /// `User-Agent` header sent with each request (default: reqwest's,
+    /// which sends none).
+    pub user_agent: Option<String>,
+    /// User-agents rotated round-robin, one per heartbeat; overrides
+    /// `user_agent` when non-empty. Retries reuse their heartbeat's value
+    /// (default: empty).
+    pub user_agents: Vec<String>,
+    /// `Accept` header sent with each request, e.g. `application/json`
+    /// (default: none).
+    pub accept: Option<String>,
+    /// HTTP method of each request (default: `GET`).
+    #[cfg_attr(feature = "serde", serde(with = "serde_method"))]
+    pub method: reqwest::Method,
+    /// Extra headers sent with each request, e.g. an `Authorization` header
+    /// required by a custom webhook (default: none).
+    pub headers: Vec<(String, String)>,
+    /// Body sent with each request (default: none). The placeholders
+    /// `{{app_name}}`, `{{timestamp}}` (send time in Unix milliseconds) and
+    /// `{{sequence}}` (heartbeat number, from zero) are replaced on every
+    /// request. Sent with `Content-Type: application/json` unless
+    /// [`headers`](Self::headers) has a `Content-Type`.
+    pub body_template: Option<String>,
+    /// Response status codes counted as a successful heartbeat (default:
+    /// empty, any 2xx status).
+    pub success_statuses: Vec<u16>,
+    /// Count a 3xx response as a successful heartbeat, for endpoints that
+    /// redirect on purpose (default: false).
+    ///
+    /// Only matters when redirects are not followed, e.g. with a client
+    /// built with `reqwest::redirect::Policy::none()` and passed to
+    /// [`spawn_with_client`]; the built-in client follows them. Ignored if
+    /// `success_statuses` is set.
+    pub treat_redirect_as_success: bool,
+    /// Called when heartbeats change between succeeding and failing
+    /// (default: none).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_transition: Option<TransitionHook>,
+    /// Stop the loop with [`StopReason::TooManyFailures`] after this many
+    /// consecutive failed heartbeats, e.g. so a supervisor restarts the
+    /// process (default: none, the loop never stops).
+    pub abort_after_failures: Option<u32>,
+    /// Stop the loop with [`StopReason::RetryBudgetExhausted`] once this
+    /// many attempts have failed since it started, counting every retry and
+    /// whether or not the failures were consecutive, so a misconfigured
+    /// heartbeat cannot retry forever (default: none).
+    pub total_retry_budget: Option<u64>,
+    /// Action taken after consecutive transport failures (connection errors
+    /// and timeouts), counted separately from HTTP failures (default: none).
+    pub transport_failure_policy: Option<FailurePolicy>,
+    /// Action taken after consecutive responses not counted as success,
+    /// counted separately from transport failures (default: none).
+    pub http_failure_policy: Option<FailurePolicy>,
+    /// Seconds after spawning during which failures are logged and counted
+    /// in the totals but neither extend the consecutive-failure streak nor
+    /// fire `on_transition`, so they cannot trigger `abort_after_failures`
+    /// or the failure policies (default: 0).
+    pub startup_grace_secs: u64,
+    /// Log a one-time error if no heartbeat has succeeded this long after
+    /// the loop started (default: none).
+    pub initial_success_deadline: Option<Duration>,
+    /// Channel receiving the loop's [`LifecycleEvent`]s (default: none).
+    /// Events are dropped rather than awaited if the channel is full or
+    /// closed, so the consumer cannot stall or break the loop.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub events_tx: Option<tokio::sync::mpsc::Sender<LifecycleEvent>>,
+    /// Withhold heartbeats until [`HeartbeatHandle::mark_ready`] has been
+    /// called this many times; the schedule then starts as if the loop had
+    /// just been spawned (default: 0, no gate).
+    pub ready_marks_required: u32,
+    /// Window in milliseconds over which rapid
+    /// [`HeartbeatHandle::update_config`] calls are coalesced into one
+    /// (default: 250).
+    pub config_debounce_ms: u64,
+    /// File whose modification time is set to now after every successful
+    /// heartbeat, for file-based liveness probes; created if missing.
+    /// Failures are logged and do not affect the heartbeat (default: none).
+    pub liveness_file: Option<std::path::PathBuf>,
+    /// Number of recent attempt latencies kept for
+    /// [`HeartbeatStatusHandle::latency_samples`]; the oldest sample is
+    /// evicted when the buffer is full (default: 64).
+    pub latency_samples: usize,
+    /// Number of recent failed heartbeats kept for
+    /// [`HeartbeatStatusHandle::recent_failures`]; the oldest record is
+    /// evicted when the buffer is full (default: 16).
+    pub failure_history: usize,
+    /// How long heartbeat outcomes are kept for
+    /// [`HeartbeatStatusHandle::availability_over`], in seconds; older ones
+    /// are evicted as new ones arrive, as is the oldest beyond 10 000
+    /// outcomes (default: 86400, one day).
+    pub availability_history_secs: u64,
+    /// Readiness reported by [`HeartbeatStatusHandle::is_ready`] before the
+    /// first heartbeat outcome is known (default: false, fail-closed).
+    ///
+    /// Fail-closed keeps an instance out of rotation until it has proven it
+    /// can reach the monitor; fail-open avoids holding back a healthy
+    /// instance just because its first heartbeat is not due yet, e.g. with
+    /// a long interval and [`FirstTickPolicy::Skip`].
+    pub readiness_default: bool,
+    /// URL pinged once by [`HeartbeatHandle::signal_startup_complete`], e.g.
+    /// a separate "startup complete" monitor (default: none).
+    pub startup_url: Option<String>,
+    /// What happens to the loop when its [`HeartbeatHandle`] is dropped
+    /// without [`HeartbeatHandle::close`] (default:
+    /// [`HandleDropPolicy::Detach`]).
+    pub on_handle_drop: HandleDropPolicy,
+    /// Send a failed job's exit code with the failure ping of [`report_job`],
+    /// as the path segment of `{url}/{exit_code}` instead of `/fail`
+    /// (default: false).
+    pub include_exit_code: bool,
+    /// Rebuild the loop's HTTP client, dropping its pooled connections,
+    /// once it is this old, to bound what a very long-running process
+    /// accumulates. Checked before each heartbeat (default: none, the
+    /// client is kept).
+    pub client_max_lifetime: Option<Duration>,
+    /// Time source of the heartbeat schedule, e.g.
+    /// `test_util::ManualClock` in tests (default: none, [`TokioClock`]).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub clock: Option<Arc<dyn Clock>>,
+    /// Skip scheduled heartbeats, with a warning, while the 1-minute load
+    /// average is above this value, so the loop does not add to an
+    /// overload (default: none, never skipped). The load is read from
+    /// `/proc/loadavg`, so this only has an effect on Linux unless
+    /// `load_source` provides it. Triggered heartbeats are always sent.
+    pub skip_above_loadavg: Option<f64>,
+    /// Source of the load average checked against `skip_above_loadavg`
+    /// (default: none, [`SystemLoad`]).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub load_source: Option<Arc<dyn LoadSource>>,
+    /// Delay the start of the schedule by an offset within the interval
+    /// derived from a hash of the host name (the `HOSTNAME` environment
+    /// variable, else `/etc/hostname`), so a fleet spreads its heartbeats
+    /// evenly and each host keeps the same offset across restarts. Only
+    /// the loop's start is delayed, not a schedule restarted on resume or
+    /// a config update. No delay if the host name is unknown (default:
+    /// false).
+    pub stagger_by_hostname: bool,
+    /// HTTP version used for requests (default: none, same as
+    /// [`HttpVersion::Auto`]).
+    pub http_version: Option<HttpVersion>,
+    /// IP family used to connect to the heartbeat host (default: none, same
+    /// as [`IpFamily::Auto`]).
+    pub ip_family: Option<IpFamily>,
+    /// Adjusts the HTTP client's builder after the settings above have been
+    /// applied, e.g. to add a proxy (default: none). Not used for a client
+    /// passed to [`spawn_with_client`] or by `spawn_blocking`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub client_customizer: Option<ClientCustomizer>,
+    /// JSON file the lifetime success/failure counts are loaded from when
+    /// the loop starts and written to after every heartbeat, so totals
+    /// survive restarts (default: none). Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub stats_file: Option<std::path::PathBuf>,
+}
+
+impl Default for HeartbeatConfig {
+    /// Default settings with an empty URL.
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            interval_secs: 60,
+            monitor_period_secs: None,
+            ping_fraction: 0.5,
+            jitter_secs: 0,
+            timeout_secs: 10,
+            pool_max_idle_per_host: None,
+            first_tick_policy: FirstTickPolicy::default(),
+            startup_delay_secs: 0,
+            include_timestamp: false,
+            path_suffix: None,
+            query_params: Vec::new(),
+            query_conflict_policy: QueryConflictPolicy::default(),
+            silent: false,
+            include_url_in_logs: true,
+            log_summary: false,
+            max_retries: 0,
+            retry_base_delay_ms: 500,
+            backoff: BackoffStrategy::default(),
+            app_name: None,
+            name: None,
+            drift_compensation: false,
+            overlap_policy: OverlapPolicy::default(),
+            realign_after_trigger: false,
+            ops_per_heartbeat: None,
+            keep_schedule_on_resume: false,
+            heartbeat_on_resume: true,
+            unhealthy_policy: UnhealthyPolicy::default(),
+            body_validator: None,
+            body_decode_policy: BodyDecodePolicy::default(),
+            expected_etag: None,
+            #[cfg(feature = "rustls-tls")]
+            warn_cert_expiry_days: None,
+            user_agent: None,
+            user_agents: Vec::new(),
+            accept: None,
+            method: reqwest::Method::GET,
+            headers: Vec::new(),
+            body_template: None,
+            success_statuses: Vec::new(),
+            treat_redirect_as_success: false,
+            on_transition: None,
+            abort_after_failures: None,
+            total_retry_budget: None,
+            transport_failure_policy: None,
+            http_failure_policy: None,
+            startup_grace_secs: 0,
+            initial_success_deadline: None,
+            events_tx: None,
+            ready_marks_required: 0,
+            config_debounce_ms: 250,
+            liveness_file: None,
+            latency_samples: 64,
+            failure_history: 16,
+            availability_history_secs: 86_400,
+            readiness_default: false,
+            startup_url: None,
+            on_handle_drop: HandleDropPolicy::default(),
+            include_exit_code: false,
+            client_max_lifetime: None,
+            clock: None,
+            skip_above_loadavg: None,
+            load_source: None,
+            stagger_by_hostname: false,
+            http_version: None,
+            ip_family: None,
+            client_customizer: None,
+            #[cfg(feature = "serde")]
+            stats_file: None,
+        }
+    }
+}
+
+/// When the heartbeat loop sends its first heartbeat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FirstTickPolicy {
+    /// Wait one full interval before the first heartbeat, then send every
+    /// interval (sends at `interval`, `2 * interval`, ...).
+    #[default]
+    Skip,
+    /// Send the first heartbeat as soon as the loop starts, then every
+    /// interval (sends at `0`, `interval`, `2 * interval`, ...).
+    FireImmediately,
+}
+
+/// How ticks missed while a heartbeat was in flight are handled.
+///
+/// A heartbeat that takes longer than the interval overlaps the next
+/// scheduled tick. Either way the schedule stays on its original grid of
+/// `interval` steps afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverlapPolicy {
+    /// At-most-once: drop the missed ticks and wait for the next scheduled
+    /// one.
+    Skip,
+    /// At-least-once: send one heartbeat right after the slow one finishes,
+    /// however many ticks were missed, then continue on schedule.
+    #[default]
+    QueueOne,
+}
+
+/// What a loop started with [`spawn_with_health_check`] does on a tick
+/// whose health check fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnhealthyPolicy {
+    /// Send nothing, so Better Uptime notices the missing heartbeat once
+    /// the monitor's period and grace have passed.
+    #[default]
+    Skip,
+    /// Ping the URL's `/fail` endpoint, so Better Uptime reports the
+    /// outage right away.
+    ReportFail,
+}
+
+/// HTTP version preference for heartbeat requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HttpVersion {
+    /// Only use HTTP/1.1, e.g. behind intermediaries that break on HTTP/2.
+    Http1,
+    /// Use HTTP/2 with prior knowledge, without negotiating an upgrade.
+    /// Fails against servers that only speak HTTP/1. Requires the `http2`
+    /// feature; without it the HTTP client cannot be built and no
+    /// heartbeats are sent.
+    Http2,
+    /// With the `http2` feature, negotiate HTTP/2 via ALPN on TLS
+    /// connections and fall back to HTTP/1.1 otherwise; without it, use
+    /// HTTP/1.1.
+    #[default]
+    Auto,
+}
+
+/// IP family preference for heartbeat connections.
+///
+/// Restricting the family binds outgoing connections to the unspecified
+/// local address of that family, which also limits the resolved addresses
+/// tried to the same family. DNS resolution itself is unchanged, so a host
+/// without an address of the chosen family fails to connect, as does a URL
+/// with an IP literal of the other family. Proxies are connected to under
+/// the same restriction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IpFamily {
+    /// Only connect over IPv4, e.g. when IPv6 egress is broken.
+    V4Only,
+    /// Only connect over IPv6.
+    V6Only,
+    /// Try all resolved addresses, preferring the family listed first.
+    #[default]
+    Auto,
 }
 
 impl HeartbeatConfig {
+    /// Start building a config for `url`, with the defaults (interval 60s,
+    /// timeout 10s, the same as [`from_env`](Self::from_env)) for every
+    /// setting not overridden.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config = HeartbeatConfig::builder("https://uptime.betterstack.com/api/v1/heartbeat/TOKEN")
+    ///     .interval_secs(30)
+    ///     .build()
+    ///     .expect("valid heartbeat config");
+    ///
+    /// assert_eq!((config.interval_secs, config.timeout_secs), (30, 10));
+    /// ```
+    #[must_use]
+    pub fn builder(url: impl Into<String>) -> HeartbeatConfigBuilder {
+        HeartbeatConfigBuilder::new().url(url)
+    }
+
+    /// A copy of this config sending to `url` instead, for related
+    /// heartbeats sharing headers, timeouts and the rest.
+    ///
+    /// Everything else is inherited, including `name` and the hooks, except
+    /// `stats_file`, which two loops cannot share; set a new `name` so the
+    /// logs of the loops can be told apart.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let base = HeartbeatConfig {
+    ///     url: "https://uptime.betterstack.com/api/v1/heartbeat/API".to_string(),
+    ///     interval_secs: 30,
+    ///     name: Some("api".to_string()),
+    ///     ..HeartbeatConfig::default()
+    /// };
+    /// let worker = HeartbeatConfig {
+    ///     name: Some("worker".to_string()),
+    ///     ..base.child("https://uptime.betterstack.com/api/v1/heartbeat/WORKER")
+    /// };
+    /// assert_eq!(worker.interval_secs, 30);
+    /// ```
+    #[must_use]
+    pub fn child(&self, url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            #[cfg(feature = "serde")]
+            stats_file: None,
+            ..self.clone()
+        }
+    }
+
     /// Create config from environment variables.
     ///
-    /// Returns `None` if `HEARTBEAT_URL` is not set or empty.
+    /// Returns `None` if `HEARTBEAT_URL` is not set or empty, or, with a
+    /// warning, if the resulting config fails [`validate`](Self::validate),
+    /// e.g. because of a typo in the URL.
     ///
     /// # Environment variables
     ///
     /// - `HEARTBEAT_URL` (required): Better Uptime heartbeat URL
-    /// - `HEARTBEAT_INTERVAL_SECS` (optional): interval in seconds, default 60
+    /// - `HEARTBEAT_INTERVAL_SECS` (optional): interval in seconds, default
+    ///   60; `0` is raised to 1 with a warning
     /// - `HEARTBEAT_TIMEOUT_SECS` (optional): timeout in seconds, default 10
+    /// - `HEARTBEAT_JITTER_SECS` (optional): maximum random delay before
+    ///   each heartbeat in seconds, default 0
+    /// - `HEARTBEAT_STARTUP_DELAY_SECS` (optional): delay before the
+    ///   schedule begins in seconds, default 0
+    /// - `HEARTBEAT_PING_ON_START` (optional): `true` (or `1`) sends the
+    ///   first heartbeat as soon as the schedule begins, as
+    ///   [`FirstTickPolicy::FireImmediately`], instead of one interval
+    ///   later; default `false`
     ///
     /// # Example
     ///
@@ -80,24 +695,300 @@ impl HeartbeatConfig {
     /// ```
     #[must_use]
     pub fn from_env() -> Option<Self> {
-        let url = std::env::var("HEARTBEAT_URL").ok()?;
+        Self::env_defaults().checked_env_config()
+    }
+
+    /// Create the config of the loop called `name` from environment
+    /// variables suffixed with the upper-cased name, for processes running
+    /// several loops.
+    ///
+    /// Reads the variables of [`from_env`](Self::from_env) with `_{NAME}`
+    /// appended, where characters other than ASCII letters and digits become
+    /// `_`: `HEARTBEAT_URL_INGEST`, `HEARTBEAT_INTERVAL_SECS_INGEST`, ... for
+    /// `"ingest"`. Sets [`name`](Self::name) to `name`. Returns `None` under
+    /// the same conditions as [`from_env`](Self::from_env).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::{HeartbeatConfig, spawn};
+    ///
+    /// # async fn run() {
+    /// let mut heartbeats = Vec::new();
+    /// for name in ["ingest", "scheduler", "api"] {
+    ///     if let Some(config) = HeartbeatConfig::from_env_named(name) {
+    ///         heartbeats.extend(spawn(config));
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn from_env_named(name: &str) -> Option<Self> {
+        let suffix: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        Self { name: Some(name.to_string()), ..Self::env_vars(&format!("_{suffix}")) }
+            .checked_env_config()
+    }
 
-        if url.trim().is_empty() {
+    /// `self` with its interval [resolved](Self::resolve_interval) if it
+    /// has a URL and is valid, else `None`, with a warning if it is invalid.
+    fn checked_env_config(self) -> Option<Self> {
+        if self.url.trim().is_empty() {
+            return None;
+        }
+        let config = self.resolve_interval();
+        if let Err(e) = config.validate() {
+            tracing::warn!("Invalid heartbeat config from environment, heartbeat disabled: {}", e);
             return None;
         }
+        Some(config)
+    }
+
+    /// The config described by the environment variables, with an empty URL
+    /// if `HEARTBEAT_URL` is not set.
+    fn env_defaults() -> Self {
+        Self::env_vars("")
+    }
+
+    /// The config described by the environment variables ending in
+    /// `suffix`, with defaults for unset or unparsable ones.
+    fn env_vars(suffix: &str) -> Self {
+        let var = |key: &str| std::env::var(format!("HEARTBEAT_{key}{suffix}")).ok();
+        let secs =
+            |key: &str, default: u64| var(key).and_then(|s| s.parse().ok()).unwrap_or(default);
+
+        Self {
+            url: var("URL").unwrap_or_default(),
+            interval_secs: secs("INTERVAL_SECS", 60),
+            jitter_secs: secs("JITTER_SECS", 0),
+            timeout_secs: secs("TIMEOUT_SECS", 10),
+            startup_delay_secs: secs("STARTUP_DELAY_SECS", 0),
+            first_tick_policy: match var("PING_ON_START").as_deref() {
+                Some("true" | "1") => FirstTickPolicy::FireImmediately,
+                _ => FirstTickPolicy::Skip,
+            },
+            ..Self::default()
+        }
+    }
+
+    /// The heartbeat URL with everything after the host replaced by `***`.
+    ///
+    /// Better Uptime heartbeat URLs embed a secret token in the path, so
+    /// only this form should be logged. Returns `***` if the URL cannot be
+    /// parsed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config = HeartbeatConfig {
+    ///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+    ///     ..HeartbeatConfig::default()
+    /// };
+    /// assert_eq!(config.redacted_url(), "https://uptime.betterstack.com/***");
+    /// ```
+    #[must_use]
+    pub fn redacted_url(&self) -> String {
+        let Ok(url) = reqwest::Url::parse(&self.url) else {
+            return "***".to_string();
+        };
+
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port().map(|port| format!(":{port}")).unwrap_or_default();
+        format!("{}://{host}{port}/***", url.scheme())
+    }
+
+    /// The URL as it may appear in log events: redacted, or `***` without
+    /// [`include_url_in_logs`](Self::include_url_in_logs).
+    fn logged_url(&self) -> String {
+        if self.include_url_in_logs { self.redacted_url() } else { "***".to_string() }
+    }
+
+    /// The settings read by [`from_env`](Self::from_env) as `HEARTBEAT_*=value`
+    /// lines, e.g. to log the effective config when reproducing a problem.
+    ///
+    /// The URL is given as its [redacted form](Self::redacted_url), so the
+    /// secret token never appears; put the real one back to recreate the
+    /// config. Settings without an environment variable are not included.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config = HeartbeatConfig {
+    ///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+    ///     interval_secs: 30,
+    ///     ..HeartbeatConfig::default()
+    /// };
+    /// let lines = config.to_env_lines();
+    /// assert_eq!(lines[0], "HEARTBEAT_URL=https://uptime.betterstack.com/***");
+    /// assert_eq!(lines[1], "HEARTBEAT_INTERVAL_SECS=30");
+    /// ```
+    #[must_use]
+    pub fn to_env_lines(&self) -> Vec<String> {
+        vec![
+            format!("HEARTBEAT_URL={}", self.redacted_url()),
+            format!("HEARTBEAT_INTERVAL_SECS={}", self.interval_secs),
+            format!("HEARTBEAT_TIMEOUT_SECS={}", self.timeout_secs),
+            format!("HEARTBEAT_JITTER_SECS={}", self.jitter_secs),
+            format!("HEARTBEAT_STARTUP_DELAY_SECS={}", self.startup_delay_secs),
+            format!(
+                "HEARTBEAT_PING_ON_START={}",
+                self.first_tick_policy == FirstTickPolicy::FireImmediately
+            ),
+        ]
+    }
+
+    /// Derive `interval_secs` from `monitor_period_secs` and `ping_fraction`.
+    ///
+    /// Keeps `interval_secs` if `monitor_period_secs` is not set. The interval
+    /// is rounded to whole seconds and is at least one second; a zero
+    /// `interval_secs` is raised to one second too. A warning is
+    /// logged (unless `silent`) if it exceeds the monitor period, as the
+    /// monitor would then alert between heartbeats, or if `ping_fraction` is
+    /// not a usable number, in which case `interval_secs` is left unchanged.
+    /// The spawn functions and [`run_report`] call this automatically.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config = HeartbeatConfig { monitor_period_secs: Some(300), ..HeartbeatConfig::default() };
+    /// assert_eq!(config.resolve_interval().interval_secs, 150);
+    /// ```
+    #[must_use]
+    pub fn resolve_interval(mut self) -> Self {
+        if let Some(period_secs) = self.monitor_period_secs {
+            self.derive_interval(period_secs);
+        }
+        // A zero interval would send heartbeats back to back.
+        if self.interval_secs == 0 {
+            if !self.silent {
+                tracing::warn!("Heartbeat interval of 0s is not allowed, using 1s");
+            }
+            self.interval_secs = 1;
+        }
+        self
+    }
 
-        let interval_secs = std::env::var("HEARTBEAT_INTERVAL_SECS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(60);
+    /// Set `interval_secs` to `ping_fraction` of a monitor period of
+    /// `period_secs`, see [`resolve_interval`](Self::resolve_interval).
+    fn derive_interval(&mut self, period_secs: u64) {
+        let period = Duration::from_secs(period_secs);
+        match Duration::try_from_secs_f64(period.as_secs_f64() * self.ping_fraction) {
+            Ok(interval) => {
+                let rounded = interval.as_secs() + u64::from(interval.subsec_millis() >= 500);
+                self.interval_secs = rounded.max(1);
+                if self.interval_secs > period_secs && !self.silent {
+                    tracing::warn!(
+                        "Heartbeat interval {}s exceeds the monitor period {}s",
+                        self.interval_secs,
+                        period_secs
+                    );
+                }
+            }
+            Err(_) if !self.silent => {
+                tracing::warn!(
+                    "Invalid heartbeat ping fraction {}, keeping interval {}s",
+                    self.ping_fraction,
+                    self.interval_secs
+                );
+            }
+            Err(_) => {}
+        }
+    }
 
-        let timeout_secs =
-            std::env::var("HEARTBEAT_TIMEOUT_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(10);
+    /// Check that the config can drive a heartbeat loop.
+    ///
+    /// The URL must be a non-empty `http` or `https` URL with a host, the
+    /// interval and timeout must be non-zero, `startup_url` must be such a
+    /// URL too if set, and `headers` must be valid HTTP headers.
+    /// Rejected URLs are reported redacted (as `***` without
+    /// [`include_url_in_logs`](Self::include_url_in_logs)), rejected headers
+    /// by name only.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found as a [`ConfigError`].
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.url.trim().is_empty() {
+            return Err(ConfigError::MissingUrl);
+        }
+        if !is_http_url(&self.url) {
+            return Err(invalid("url", self.logged_url()));
+        }
+        if self.interval_secs == 0 {
+            return Err(invalid("interval_secs", "0"));
+        }
+        if self.timeout_secs == 0 {
+            return Err(invalid("timeout_secs", "0"));
+        }
+        if self.ops_per_heartbeat == Some(0) {
+            return Err(invalid("ops_per_heartbeat", "0"));
+        }
+        if let Some(url) = &self.startup_url {
+            if !is_http_url(url) {
+                return Err(invalid("startup_url", "***"));
+            }
+        }
+        for (name, value) in &self.headers {
+            let name_ok = reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_ok();
+            if !name_ok || reqwest::header::HeaderValue::from_str(value).is_err() {
+                return Err(invalid("headers", name.clone()));
+            }
+        }
+        Ok(())
+    }
 
-        Some(Self { url, interval_secs, timeout_secs })
+    /// Advice if the interval, after [`resolve_interval`](Self::resolve_interval),
+    /// is shorter than the 30 seconds recommended for heartbeat monitors.
+    ///
+    /// Only advisory: such a config is still valid and used as is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config = HeartbeatConfig { interval_secs: 5, ..HeartbeatConfig::default() };
+    /// if let Some(warning) = config.interval_warning() {
+    ///     eprintln!("{warning}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn interval_warning(&self) -> Option<String> {
+        let interval = self.clone().resolve_interval().interval_secs;
+        (interval < RECOMMENDED_MIN_INTERVAL_SECS).then(|| {
+            format!(
+                "heartbeat interval of {interval}s is below the recommended minimum of \
+                 {RECOMMENDED_MIN_INTERVAL_SECS}s; Better Uptime monitors gain nothing from \
+                 more frequent pings"
+            )
+        })
     }
 }
 
+/// Shortest interval worth using for a Better Uptime heartbeat monitor, in
+/// seconds: monitors check in at coarser periods, so faster pings only add
+/// requests.
+const RECOMMENDED_MIN_INTERVAL_SECS: u64 = 30;
+
+/// Whether `url` parses as an `http` or `https` URL with a non-empty host.
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https") && url.host_str().is_some_and(|h| !h.is_empty())
+    })
+}
+
+fn invalid(key: &str, value: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidValue { key: key.to_string(), value: value.into() }
+}
+
 /// Spawn heartbeat background task if configured.
 ///
 /// Returns `true` if task was spawned, `false` if disabled.
@@ -121,22 +1012,153 @@ impl HeartbeatConfig {
 /// ```
 #[must_use]
 pub fn spawn_from_env() -> bool {
-    HeartbeatConfig::from_env().map_or_else(
-        || {
-            tracing::info!("HEARTBEAT_URL not configured, heartbeat disabled");
-            false
-        },
-        |config| {
-            spawn(config);
-            true
-        },
-    )
+    try_spawn_from_env().is_some()
+}
+
+/// Spawn heartbeat background task if configured, returning its handle.
+///
+/// Like [`spawn_from_env()`], but returns the [`HeartbeatHandle`] of the
+/// loop, or `None` if `HEARTBEAT_URL` is not configured or the config was
+/// refused (see [`spawn`]). Services that restart their subsystems can use
+/// the handle to tear the heartbeat down before starting a new one.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// #[tokio::main]
+/// async fn main() {
+///     let heartbeat = betteruptime_heartbeat::try_spawn_from_env();
+///
+///     // When restarting the subsystem:
+///     if let Some(heartbeat) = heartbeat {
+///         heartbeat.abort();
+///     }
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task; use `spawn_from_env` if it is not needed"]
+pub fn try_spawn_from_env() -> Option<HeartbeatHandle> {
+    let Some(config) = HeartbeatConfig::from_env() else {
+        tracing::info!("HEARTBEAT_URL not configured, heartbeat disabled");
+        return None;
+    };
+    spawn(config)
+}
+
+/// Spawn a task that waits for `HEARTBEAT_URL` to appear, then runs the heartbeat.
+///
+/// Unlike [`spawn_from_env()`], which checks the environment once, this
+/// polls [`HeartbeatConfig::from_env()`] every `check_interval` until it
+/// returns a config or `max_wait` has elapsed. The environment is checked
+/// immediately, so an already configured URL starts the heartbeat without
+/// delay. Once a config is found it goes through the checks of [`spawn`]
+/// and the task becomes the heartbeat loop; if `max_wait` elapses first, an
+/// info message is logged and the task ends.
+///
+/// Returns immediately with the task's [`JoinHandle`], which can be used to
+/// abort the task while it is still waiting or after the loop has started.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     // Wait up to a minute for HEARTBEAT_URL, checking every second:
+///     let _task = betteruptime_heartbeat::spawn_from_env_retry(
+///         Duration::from_secs(1),
+///         Duration::from_secs(60),
+///     );
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_from_env_retry(check_interval: Duration, max_wait: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let deadline = Instant::now() + max_wait;
+
+        let config = loop {
+            if let Some(config) = HeartbeatConfig::from_env() {
+                break config;
+            }
+            if Instant::now() + check_interval > deadline {
+                tracing::info!(
+                    "HEARTBEAT_URL not configured after {:?}, heartbeat disabled",
+                    max_wait
+                );
+                return;
+            }
+            tokio::time::sleep(check_interval).await;
+        };
+
+        let span = loop_span(&config);
+        let config = match checked(config) {
+            Ok(config) => config,
+            Err(e) => {
+                span.in_scope(|| tracing::error!("Heartbeat not started: {}", e));
+                return;
+            }
+        };
+        tracing::info!(
+            "Heartbeat task started from env: interval={}s, timeout={}s",
+            config.interval_secs,
+            config.timeout_secs
+        );
+        let (task, _status) = start_loop(config, LoopControl::detached());
+        tracing::Instrument::instrument(task, span).await;
+    })
+}
+
+/// Spawn heartbeat background task from env, falling back to `fallback`.
+///
+/// Uses [`HeartbeatConfig::from_env()`] if `HEARTBEAT_URL` is configured and
+/// `fallback` otherwise, and spawns it as [`spawn_with_handle`] does.
+/// Returns the loop's [`HeartbeatHandle`]. Logs which source
+/// was used together with the redacted URL, unless
+/// [`include_url_in_logs`](HeartbeatConfig::include_url_in_logs) is off.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::HeartbeatConfig;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let fallback = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/DEFAULT_TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let _heartbeat = betteruptime_heartbeat::spawn_from_env_or(fallback);
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the loop, or stops it with `HandleDropPolicy::Abort`"]
+pub fn spawn_from_env_or(fallback: HeartbeatConfig) -> HeartbeatHandle {
+    let (config, source) = HeartbeatConfig::from_env()
+        .map_or((fallback, "fallback config"), |config| (config, "environment"));
+
+    if !config.silent {
+        if config.include_url_in_logs {
+            tracing::info!("Using heartbeat config from {}: url={}", source, config.redacted_url());
+        } else {
+            tracing::info!("Using heartbeat config from {}", source);
+        }
+    }
+
+    spawn_with_handle(config)
 }
 
 /// Spawn heartbeat background task with explicit config.
 ///
 /// This function creates an HTTP client and spawns a background tokio task
-/// that sends periodic heartbeat pings to the configured URL.
+/// that sends periodic heartbeat pings to the configured URL. The config is
+/// first passed through the [config interceptor](set_config_interceptor)
+/// and validated; if either fails, an error is logged and nothing is
+/// spawned. Use [`try_spawn`] to handle that case.
+///
+/// Returns the [`HeartbeatHandle`] of the loop, or `None` if the config was
+/// refused. Dropping the handle detaches the loop, unless
+/// [`HeartbeatConfig::on_handle_drop`] says otherwise, so keep it for as
+/// long as the loop should run; call [`HeartbeatHandle::abort`] to stop it.
 ///
 /// # Example
 ///
@@ -149,74 +1171,583 @@ pub fn spawn_from_env() -> bool {
 ///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
 ///         interval_secs: 60,
 ///         timeout_secs: 10,
+///         ..HeartbeatConfig::default()
 ///     };
 ///
-///     spawn(config);
+///     let _heartbeat = spawn(config);
 /// }
 /// ```
-pub fn spawn(config: HeartbeatConfig) {
-    tracing::info!(
-        "Heartbeat task spawned: interval={}s, timeout={}s",
-        config.interval_secs,
-        config.timeout_secs
-    );
+#[must_use = "dropping the handle detaches the loop, or stops it with `HandleDropPolicy::Abort`"]
+pub fn spawn(config: HeartbeatConfig) -> Option<HeartbeatHandle> {
+    let span = loop_span(&config);
+    try_spawn(config)
+        .inspect_err(|e| span.in_scope(|| tracing::error!("Heartbeat not started: {}", e)))
+        .ok()
+}
 
-    tokio::spawn(async move {
-        heartbeat_loop(config).await;
-    });
+/// Spawn heartbeat background task, returning an error if the config is refused.
+///
+/// Runs the [config interceptor](set_config_interceptor), if one is
+/// installed, then [validates](HeartbeatConfig::validate) the config it
+/// returns, and only then spawns the loop.
+///
+/// # Errors
+///
+/// Returns [`HeartbeatError::Rejected`] (or any other error) from the
+/// interceptor, or [`HeartbeatError::Config`] if validation fails.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, try_spawn};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     match try_spawn(config) {
+///         Ok(heartbeat) => println!("heartbeat started: {:?}", heartbeat.stats()),
+///         Err(e) => eprintln!("heartbeat not started: {e}"),
+///     }
+/// }
+/// ```
+pub fn try_spawn(config: HeartbeatConfig) -> Result<HeartbeatHandle, HeartbeatError> {
+    try_spawn_handle(config, |_| {})
 }
 
-/// Internal heartbeat loop that runs indefinitely.
+/// Spawn heartbeat background task and return its task and status handles.
 ///
-/// Sends GET requests to the configured URL at regular intervals.
-/// Never panics - all errors are logged and the loop continues.
-async fn heartbeat_loop(config: HeartbeatConfig) {
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(config.timeout_secs))
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!("Failed to create HTTP client for heartbeat: {}", e);
-            return;
-        }
+/// The [`JoinHandle`] controls the task: call [`JoinHandle::abort`] to stop
+/// the loop. The [`HeartbeatStatusHandle`] only observes it and can be cloned
+/// and shared freely. The two are independent: dropping the status handle
+/// (or the join handle) does not stop the loop, and the status handle keeps
+/// returning the final statistics after the task has been aborted.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_observed};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let (task, status) = spawn_observed(config);
+///
+///     // Later:
+///     println!("successful heartbeats: {}", status.stats().successes);
+///     task.abort();
+/// }
+/// ```
+#[must_use = "dropping the handles detaches the task; use `spawn` if they are not needed"]
+pub fn spawn_observed(config: HeartbeatConfig) -> (JoinHandle<()>, HeartbeatStatusHandle) {
+    let span = loop_span(&config);
+    let (task, status, _controller) = match checked(config) {
+        Ok(config) => spawn_loop(config, |_| {}),
+        Err(e) => refused_loop(&span, &e),
     };
-
-    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
-
-    // First tick completes immediately, skip it to align with intended interval
-    interval.tick().await;
-
-    loop {
-        interval.tick().await;
-
-        match client.get(&config.url).send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    tracing::debug!("Heartbeat sent successfully");
-                } else {
-                    tracing::warn!(
-                        "Heartbeat request returned non-2xx status: {}",
-                        response.status()
-                    );
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Heartbeat request failed: {}", e);
-            }
-        }
-    }
+    (task, status)
 }
 
-#[cfg(test)]
-#[allow(unsafe_code)] // Tests need to manipulate environment variables
-mod tests {
-    use super::*;
-    use serial_test::serial;
-
-    #[test]
-    #[serial]
-    fn test_config_from_env_returns_none_when_url_not_set() {
+/// Spawn heartbeat background task and return a [`HeartbeatHandle`] to
+/// observe and stop it.
+///
+/// Unlike [`JoinHandle::abort`], [`HeartbeatHandle::close`] lets a
+/// heartbeat in flight complete and waits for the loop to exit. The config
+/// goes through the same checks as in [`spawn`]; if it is refused, an error
+/// is logged and the loop of the returned handle has already finished.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_with_handle};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = spawn_with_handle(config);
+///
+///     // On shutdown:
+///     let stats = heartbeat.close().await;
+///     println!("{} heartbeats sent", stats.successes + stats.failures);
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_handle(config: HeartbeatConfig) -> HeartbeatHandle {
+    spawn_handle(config, |_| {})
+}
+
+/// Spawn heartbeat background task that stops when `token` is cancelled.
+///
+/// Once the token fires, the loop sends one final heartbeat if
+/// `final_heartbeat` is set, then exits and the task completes: the
+/// returned handle's [`is_finished`](HeartbeatHandle::is_finished) turns
+/// `true` shortly afterwards. A heartbeat in flight when the token fires is
+/// completed first. The handle can still be used to observe, pause or
+/// close the loop before that.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_with_shutdown};
+/// use tokio_util::sync::CancellationToken;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///     let shutdown = CancellationToken::new();
+///
+///     let heartbeat = spawn_with_shutdown(config, shutdown.clone(), false);
+///
+///     // On shutdown:
+///     shutdown.cancel();
+/// }
+/// ```
+#[cfg(feature = "cancellation")]
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_shutdown(
+    config: HeartbeatConfig,
+    token: tokio_util::sync::CancellationToken,
+    final_heartbeat: bool,
+) -> HeartbeatHandle {
+    let shutdown = async move {
+        token.cancelled().await;
+        final_heartbeat
+    };
+    spawn_handle(config, |control| control.shutdown = Some(Box::pin(shutdown)))
+}
+
+/// Spawn heartbeat background task that reports every outcome to
+/// `observer`.
+///
+/// Like [`spawn_with_handle`], with [`HeartbeatObserver::on_success`] or
+/// [`HeartbeatObserver::on_failure`] called after each heartbeat, e.g. to
+/// increment a Prometheus counter without parsing logs.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+///
+/// use betteruptime_heartbeat::{
+///     HeartbeatConfig, HeartbeatError, HeartbeatObserver, spawn_with_observer,
+/// };
+///
+/// struct Alerting;
+///
+/// impl HeartbeatObserver for Alerting {
+///     fn on_failure(&self, err: &HeartbeatError) {
+///         eprintln!("heartbeat failed: {err}");
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = spawn_with_observer(config, Arc::new(Alerting));
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_observer(
+    config: HeartbeatConfig,
+    observer: Arc<dyn HeartbeatObserver>,
+) -> HeartbeatHandle {
+    spawn_handle(config, |control| control.observer = observer)
+}
+
+/// Spawn heartbeat background task that only pings while `check` passes.
+///
+/// Like [`spawn_with_handle`], but before each heartbeat the loop awaits
+/// `check()`, e.g. a database ping or a queue depth check. If it returns
+/// `true` the heartbeat is sent as usual; if it returns `false` a warning
+/// is logged and, following [`HeartbeatConfig::unhealthy_policy`], the
+/// heartbeat is skipped or replaced by a ping to the URL's `/fail`
+/// endpoint. A skipped heartbeat is counted in the skip counts as
+/// [`SkipReason::Unhealthy`]; the `/fail` ping goes through the loop's
+/// client or transport and is counted in the statistics like a heartbeat,
+/// so one that is not delivered shows up as a failure. A check that hangs
+/// delays the heartbeat, so give it its own timeout.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, UnhealthyPolicy, spawn_with_health_check};
+///
+/// async fn database_reachable() -> bool {
+///     // e.g. `SELECT 1` with a timeout
+///     true
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         unhealthy_policy: UnhealthyPolicy::ReportFail,
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = spawn_with_health_check(config, || Box::pin(database_reachable()));
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_health_check<F>(config: HeartbeatConfig, check: F) -> HeartbeatHandle
+where
+    F: Fn() -> BoxFuture<'static, bool> + Send + Sync + 'static,
+{
+    spawn_handle(config, |control| control.health_check = Some(Arc::new(check)))
+}
+
+/// Spawn heartbeat background task that sends its requests with `client`.
+///
+/// Like [`spawn_with_handle`], but reuses an existing client, with its
+/// connection pool, proxy and TLS settings, instead of building one. The
+/// loop never rebuilds it, so the config settings that shape the client
+/// itself do not apply: `pool_max_idle_per_host`, `http_version`,
+/// `ip_family`, `client_max_lifetime`, `client_customizer` and the
+/// `X-Heartbeat-Client` header
+/// of `app_name`. Everything set per request still does, including
+/// `timeout_secs`, which bounds every attempt whatever the client's own
+/// timeout.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_with_client};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = reqwest::Client::new(); // shared with the rest of the app
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = spawn_with_client(config, client.clone());
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_client(config: HeartbeatConfig, client: reqwest::Client) -> HeartbeatHandle {
+    spawn_handle(config, |control| control.client = Some(client))
+}
+
+/// Spawn heartbeat background task that pings through `transport` instead
+/// of an HTTP client, e.g. a fake recording the calls in tests.
+///
+/// Like [`spawn_with_handle`], with the schedule, timeout, retries,
+/// [`success_statuses`](HeartbeatConfig::success_statuses), statistics and
+/// hooks applied to the transport's results. The transport is handed the
+/// request URL and decides everything else about the request, so `method`,
+/// `headers`, `body_template` and the other request settings, as well as
+/// the body and `ETag` checks, do not apply.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use betteruptime_heartbeat::{
+///     BoxFuture, FirstTickPolicy, HeartbeatConfig, HeartbeatError, HeartbeatTransport,
+///     spawn_with_transport,
+/// };
+/// use reqwest::StatusCode;
+///
+/// #[derive(Debug, Default)]
+/// struct Counter(AtomicUsize);
+///
+/// impl HeartbeatTransport for Counter {
+///     fn ping<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<StatusCode, HeartbeatError>> {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///         Box::pin(async { Ok(StatusCode::OK) })
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let pings = Arc::new(Counter::default());
+///     let heartbeat = spawn_with_transport(
+///         HeartbeatConfig {
+///             url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///             first_tick_policy: FirstTickPolicy::FireImmediately,
+///             ..HeartbeatConfig::default()
+///         },
+///         pings.clone(),
+///     );
+///
+///     tokio::time::sleep(Duration::from_secs(150)).await;
+///     assert_eq!(pings.0.load(Ordering::Relaxed), 3);
+///     heartbeat.close().await;
+/// }
+/// ```
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_transport(
+    config: HeartbeatConfig,
+    transport: Arc<dyn HeartbeatTransport>,
+) -> HeartbeatHandle {
+    spawn_handle(config, |control| control.transport = Some(transport))
+}
+
+/// Run the heartbeat loop as a future instead of spawning a task for it.
+///
+/// Does what [`spawn`] does, but leaves driving the loop to the caller:
+/// pass the future to `tokio::spawn` under your own supervision, add it to
+/// a `JoinSet`, run it on a `LocalSet` or race it against other futures
+/// with `tokio::select!`. The config goes through the
+/// [config interceptor](set_config_interceptor) and validation first; if
+/// it is refused, an error is logged and the future completes at once.
+/// Otherwise it runs until [`HeartbeatConfig::abort_after_failures`] or a
+/// [`FailurePolicy`] stops the loop, or until it is dropped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, run};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = tokio::spawn(run(config));
+///
+///     // On shutdown:
+///     heartbeat.abort();
+/// }
+/// ```
+pub async fn run(config: HeartbeatConfig) {
+    let span = loop_span(&config);
+    let run = async move {
+        let config = match checked(config) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("Heartbeat not started: {}", e);
+                return;
+            }
+        };
+        if !config.silent {
+            tracing::info!(
+                "Heartbeat loop started: interval={}s, timeout={}s",
+                config.interval_secs,
+                config.timeout_secs
+            );
+        }
+
+        let (task, _status) = start_loop(config, LoopControl::detached());
+        task.await;
+    };
+    tracing::Instrument::instrument(run, span).await;
+}
+
+/// Spawn a loop sending gRPC health checks instead of HTTP heartbeats.
+///
+/// Each heartbeat is one `Check` call of the standard gRPC health checking
+/// protocol for `service` (empty for the server as a whole) on the gRPC
+/// server at `config.url`, e.g. `http://10.0.0.5:50051`:
+///
+/// ```proto
+/// syntax = "proto3";
+/// package grpc.health.v1;
+///
+/// message HealthCheckRequest {
+///   string service = 1;
+/// }
+///
+/// message HealthCheckResponse {
+///   enum ServingStatus {
+///     UNKNOWN = 0;
+///     SERVING = 1;
+///     NOT_SERVING = 2;
+///     SERVICE_UNKNOWN = 3;
+///   }
+///   ServingStatus status = 1;
+/// }
+///
+/// service Health {
+///   rpc Check(HealthCheckRequest) returns (HealthCheckResponse);
+///   rpc Watch(HealthCheckRequest) returns (stream HealthCheckResponse);
+/// }
+/// ```
+///
+/// `SERVING` counts as a successful heartbeat with status 200, the HTTP
+/// status of every gRPC response; any other serving status or an error
+/// status counts as a failure. Scheduling, retries, `timeout_secs`, stats,
+/// hooks and the handle work as for HTTP heartbeats; settings of the HTTP
+/// request, such as headers, the body and `success_statuses`, are ignored.
+/// `https` URLs are verified against the webpki root certificates.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn_with_grpc_health};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "http://127.0.0.1:50051".to_string(),
+///         interval_secs: 30,
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let heartbeat = spawn_with_grpc_health(config, "billing.Ledger");
+///     // ...
+///     heartbeat.close().await;
+/// }
+/// ```
+#[cfg(feature = "grpc")]
+#[must_use = "dropping the handle detaches the task"]
+pub fn spawn_with_grpc_health(
+    config: HeartbeatConfig,
+    service: impl Into<String>,
+) -> HeartbeatHandle {
+    let service = service.into();
+    spawn_handle(config, |control| control.grpc_service = Some(service))
+}
+
+/// Spawn the loop for `config` with [`spawn_loop`], behind a
+/// [`HeartbeatHandle`]. A refused config is logged and leaves the handle of
+/// a loop that has already finished.
+fn spawn_handle(config: HeartbeatConfig, setup: impl FnOnce(&mut LoopControl)) -> HeartbeatHandle {
+    let span = loop_span(&config);
+    try_spawn_handle(config, setup).unwrap_or_else(|e| {
+        let (task, status, controller) = refused_loop(&span, &e);
+        HeartbeatHandle::new(task, status, controller, None, HandleDropPolicy::default(), None)
+    })
+}
+
+/// [`spawn_handle`], returning the error if the config is refused.
+fn try_spawn_handle(
+    config: HeartbeatConfig,
+    setup: impl FnOnce(&mut LoopControl),
+) -> Result<HeartbeatHandle, HeartbeatError> {
+    let config = checked(config)?;
+    let startup = config.startup_url.clone().map(|url| HeartbeatConfig {
+        url,
+        liveness_file: None,
+        ..config.clone()
+    });
+    let on_drop = config.on_handle_drop;
+    let ops_per_heartbeat = config.ops_per_heartbeat;
+    let (task, status, controller) = spawn_loop(config, setup);
+    Ok(HeartbeatHandle::new(task, status, controller, startup, on_drop, ops_per_heartbeat))
+}
+
+/// `config` as every loop starts with it, passed through
+/// [`intercept::apply`] in the loop's span.
+fn checked(config: HeartbeatConfig) -> Result<HeartbeatConfig, HeartbeatError> {
+    let span = loop_span(&config);
+    span.in_scope(|| intercept::apply(config))
+}
+
+/// Log in `span` that a loop was refused with `e`, and return the handles
+/// of a loop that has already finished.
+fn refused_loop(
+    span: &tracing::Span,
+    e: &HeartbeatError,
+) -> (JoinHandle<()>, HeartbeatStatusHandle, handle::Controller) {
+    span.in_scope(|| tracing::error!("Heartbeat not started: {}", e));
+    let (controller, _control) = handle::loop_control();
+    let status = HeartbeatStatusHandle::new(Arc::new(Status::default()));
+    (tokio::spawn(async {}), status, controller)
+}
+
+/// Spawn the loop for the [checked](checked) `config` with its controls
+/// adjusted by `setup`, e.g. to add a shutdown signal; dropping the
+/// returned controller detaches it.
+fn spawn_loop(
+    config: HeartbeatConfig,
+    setup: impl FnOnce(&mut LoopControl),
+) -> (JoinHandle<()>, HeartbeatStatusHandle, handle::Controller) {
+    let span = loop_span(&config);
+    let (task, status, controller) = span.in_scope(|| {
+        if !config.silent {
+            tracing::info!(
+                "Heartbeat task spawned: interval={}s, timeout={}s",
+                config.interval_secs,
+                config.timeout_secs
+            );
+        }
+
+        let (controller, mut control) = handle::loop_control();
+        setup(&mut control);
+        let (task, status) = start_loop(config, control);
+        (task, status, controller)
+    });
+    let task = tracing::Instrument::instrument(task, span);
+    // Heartbeats carry the trace context of the span the loop was spawned in.
+    #[cfg(feature = "otel")]
+    let task = tracing::Instrument::in_current_span(task);
+    let task = tokio::spawn(task);
+
+    (task, HeartbeatStatusHandle::new(status), controller)
+}
+
+/// Span around everything logged for a loop with `config`, carrying its
+/// [`name`](HeartbeatConfig::name). Disabled for unnamed loops.
+fn loop_span(config: &HeartbeatConfig) -> tracing::Span {
+    // At error level so the name is attached whenever any of the loop's
+    // events is enabled.
+    config.name.as_ref().map_or_else(
+        tracing::Span::none,
+        |name| tracing::error_span!("heartbeat", heartbeat.name = %name),
+    )
+}
+
+/// The loop for the resolved `config`, driven by `control`, and its status.
+fn start_loop(
+    config: HeartbeatConfig,
+    control: LoopControl,
+) -> (impl Future<Output = ()> + Send + 'static, Arc<Status>) {
+    let status = Arc::new(Status::new(&config));
+    events::emit(&config, LifecycleEvent::Spawned);
+    (heartbeat_loop(config, Arc::clone(&status), control), status)
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)] // Tests need to manipulate environment variables
+mod tests {
+    use super::*;
+    use crate::test_util::{MockResponse, MockServer};
+    use serial_test::serial;
+
+    /// Poll `handle` until `done` returns true or `timeout` elapses.
+    async fn wait_for_stats(
+        handle: &HeartbeatStatusHandle,
+        timeout: Duration,
+        done: impl Fn(&HeartbeatStats) -> bool,
+    ) -> HeartbeatStats {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let stats = handle.stats();
+            if done(&stats) || Instant::now() >= deadline {
+                return stats;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_from_env_returns_none_when_url_not_set() {
         // Clear environment
         // SAFETY: Tests run sequentially and we clean up after ourselves
         unsafe {
@@ -263,6 +1794,27 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn test_config_from_env_rejects_invalid_url() {
+        let (logs, _guard) = crate::test_support::capture_logs();
+        for url in ["not a url", "ftp://example.com/heartbeat", "https//example.com/heartbeat"] {
+            // SAFETY: Tests run sequentially and we clean up after ourselves
+            unsafe {
+                std::env::set_var("HEARTBEAT_URL", url);
+            }
+            assert!(HeartbeatConfig::from_env().is_none(), "{url}");
+        }
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+        let warnings =
+            logs.events().into_iter().filter(|e| e.level == tracing::Level::WARN).count();
+        assert_eq!(warnings, 3);
+    }
+
     #[test]
     #[serial]
     fn test_config_from_env_uses_defaults() {
@@ -311,76 +1863,1493 @@ mod tests {
 
     #[test]
     #[serial]
-    fn test_config_from_env_ignores_invalid_interval() {
+    fn test_config_from_env_parses_jitter() {
         // SAFETY: Tests run sequentially and we clean up after ourselves
         unsafe {
-            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
-            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
             std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
-            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "invalid");
+            std::env::set_var("HEARTBEAT_JITTER_SECS", "5");
         }
-
         let config = HeartbeatConfig::from_env().expect("config should be Some");
+        assert_eq!(config.jitter_secs, 5);
 
-        // Should fallback to default
-        assert_eq!(config.interval_secs, 60);
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe { std::env::set_var("HEARTBEAT_JITTER_SECS", "soon") };
+        let config = HeartbeatConfig::from_env().expect("config should be Some");
+        assert_eq!(config.jitter_secs, 0);
 
         // SAFETY: Cleanup
         unsafe {
             std::env::remove_var("HEARTBEAT_URL");
-            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+            std::env::remove_var("HEARTBEAT_JITTER_SECS");
         }
     }
 
     #[test]
     #[serial]
-    fn test_config_from_env_ignores_invalid_timeout() {
+    fn test_config_from_env_parses_startup_delay() {
         // SAFETY: Tests run sequentially and we clean up after ourselves
         unsafe {
-            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
-            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
             std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
-            std::env::set_var("HEARTBEAT_TIMEOUT_SECS", "not-a-number");
+            std::env::set_var("HEARTBEAT_STARTUP_DELAY_SECS", "15");
         }
-
         let config = HeartbeatConfig::from_env().expect("config should be Some");
+        assert_eq!(config.startup_delay_secs, 15);
 
-        // Should fallback to default
-        assert_eq!(config.timeout_secs, 10);
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe { std::env::set_var("HEARTBEAT_STARTUP_DELAY_SECS", "-1") };
+        let config = HeartbeatConfig::from_env().expect("config should be Some");
+        assert_eq!(config.startup_delay_secs, 0);
 
         // SAFETY: Cleanup
         unsafe {
             std::env::remove_var("HEARTBEAT_URL");
-            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+            std::env::remove_var("HEARTBEAT_STARTUP_DELAY_SECS");
         }
     }
 
     #[test]
     #[serial]
-    fn test_spawn_from_env_returns_false_when_not_configured() {
+    fn test_config_from_env_named_reads_suffixed_variables() {
         // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/default");
+            std::env::set_var("HEARTBEAT_URL_JOB_RUNNER", "https://example.com/jobs");
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS_JOB_RUNNER", "90");
+            std::env::remove_var("HEARTBEAT_URL_API");
+        }
+
+        let config = HeartbeatConfig::from_env_named("job-runner").expect("config should be Some");
+        let missing = HeartbeatConfig::from_env_named("api");
+
+        // SAFETY: Cleanup
         unsafe {
             std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_URL_JOB_RUNNER");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS_JOB_RUNNER");
         }
 
-        let spawned = spawn_from_env();
-        assert!(!spawned);
+        assert_eq!(config.url, "https://example.com/jobs");
+        assert_eq!(config.name.as_deref(), Some("job-runner"));
+        assert_eq!((config.interval_secs, config.timeout_secs), (90, 10));
+        assert!(missing.is_none());
     }
 
-    #[tokio::test]
+    #[test]
+    fn test_child_inherits_all_but_the_url() {
+        let parent = HeartbeatConfig {
+            url: "https://example.com/heartbeat/parent".to_string(),
+            interval_secs: 30,
+            timeout_secs: 4,
+            max_retries: 2,
+            headers: vec![("X-Team".to_string(), "billing".to_string())],
+            name: Some("billing".to_string()),
+            ..HeartbeatConfig::default()
+        };
+        let child = parent.child("https://example.com/heartbeat/child");
+
+        assert_eq!(child.url, "https://example.com/heartbeat/child");
+        assert_eq!(parent.url, "https://example.com/heartbeat/parent");
+        assert_eq!((child.interval_secs, child.timeout_secs, child.max_retries), (30, 4, 2));
+        assert_eq!(child.headers, parent.headers);
+        assert_eq!(child.name.as_deref(), Some("billing"));
+    }
+
+    #[test]
     #[serial]
-    async fn test_spawn_from_env_returns_true_when_configured() {
+    fn test_env_lines_round_trip_without_the_token() {
+        let config = HeartbeatConfig {
+            url: "https://uptime.betterstack.com/api/v1/heartbeat/SECRET".to_string(),
+            interval_secs: 45,
+            timeout_secs: 7,
+            jitter_secs: 3,
+            startup_delay_secs: 20,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+        let lines = config.to_env_lines();
+        assert!(lines.iter().all(|line| !line.contains("SECRET")), "{lines:?}");
+        assert!(lines.contains(&"HEARTBEAT_URL=https://uptime.betterstack.com/***".to_string()));
+
+        let vars: Vec<(&str, &str)> =
+            lines.iter().map(|line| line.split_once('=').unwrap()).collect();
         // SAFETY: Tests run sequentially and we clean up after ourselves
         unsafe {
-            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+            for (key, value) in &vars {
+                std::env::set_var(key, value);
+            }
+            std::env::set_var("HEARTBEAT_URL", &config.url);
         }
 
-        let spawned = spawn_from_env();
-        assert!(spawned);
+        let restored = HeartbeatConfig::from_env().expect("config should be Some");
 
         // SAFETY: Cleanup
         unsafe {
-            std::env::remove_var("HEARTBEAT_URL");
+            for (key, _) in &vars {
+                std::env::remove_var(key);
+            }
         }
+
+        assert_eq!(restored.to_env_lines(), lines);
+        assert_eq!(
+            (restored.interval_secs, restored.timeout_secs, restored.jitter_secs),
+            (45, 7, 3)
+        );
+        assert_eq!(restored.startup_delay_secs, 20);
+        assert_eq!(restored.first_tick_policy, FirstTickPolicy::FireImmediately);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_config_deserializes_with_defaults() {
+        let config: HeartbeatConfig = serde_json::from_value(serde_json::json!({
+            "url": "https://example.com/heartbeat",
+            "jitter_secs": 5,
+            "method": "post",
+            "first_tick_policy": "FireImmediately",
+            "headers": [["X-Team", "billing"]],
+            "http_failure_policy": { "threshold": 3, "action": "Stop" },
+        }))
+        .unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!((config.interval_secs, config.timeout_secs, config.jitter_secs), (60, 10, 5));
+        assert_eq!(config.method, reqwest::Method::POST);
+        assert_eq!(config.first_tick_policy, FirstTickPolicy::FireImmediately);
+        assert_eq!(config.headers, vec![("X-Team".to_string(), "billing".to_string())]);
+        assert!(matches!(
+            config.http_failure_policy,
+            Some(FailurePolicy { threshold: 3, action: FailureAction::Stop })
+        ));
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["method"], "POST");
+        let again: HeartbeatConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(again.to_env_lines(), config.to_env_lines());
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_from_env_ignores_invalid_interval() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "invalid");
+        }
+
+        let config = HeartbeatConfig::from_env().expect("config should be Some");
+
+        // Should fallback to default
+        assert_eq!(config.interval_secs, 60);
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_from_env_ignores_invalid_timeout() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+            std::env::set_var("HEARTBEAT_TIMEOUT_SECS", "not-a-number");
+        }
+
+        let config = HeartbeatConfig::from_env().expect("config should be Some");
+
+        // Should fallback to default
+        assert_eq!(config.timeout_secs, 10);
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_spawn_from_env_returns_false_when_not_configured() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+
+        let spawned = spawn_from_env();
+        assert!(!spawned);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_from_env_returns_true_when_configured() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+        }
+
+        let spawned = spawn_from_env();
+        assert!(spawned);
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_try_spawn_from_env_returns_handle() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+        assert!(try_spawn_from_env().is_none());
+
+        let server = MockServer::start().await;
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", server.url());
+        }
+        let heartbeat = try_spawn_from_env();
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+
+        let heartbeat = heartbeat.unwrap();
+        assert!(!heartbeat.is_finished());
+        heartbeat.abort();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !heartbeat.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[cfg(feature = "cancellation")]
+    async fn cancelled_run(final_heartbeat: bool) -> usize {
+        let server = MockServer::start().await;
+        let token = tokio_util::sync::CancellationToken::new();
+        let heartbeat = spawn_with_shutdown(
+            HeartbeatConfig {
+                url: server.url(),
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            },
+            token.clone(),
+            final_heartbeat,
+        );
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        assert!(!heartbeat.is_finished());
+
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !heartbeat.is_finished() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("loop should exit after cancellation");
+        server.request_count()
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "cancellation")]
+    async fn test_cancellation_stops_loop() {
+        assert_eq!(cancelled_run(false).await, 1);
+        assert_eq!(cancelled_run(true).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_suppressed_failures_are_not_counted() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            abort_after_failures: Some(1),
+            ..HeartbeatConfig::default()
+        });
+        heartbeat.suppress_failures_until(Instant::now() + Duration::from_millis(1500));
+
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(heartbeat.stats().failures, 0);
+        assert!(!heartbeat.is_finished());
+
+        server.wait_for_requests(3, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(heartbeat.stats().failures, 1);
+        assert_eq!(heartbeat.status().stop_reason(), Some(StopReason::TooManyFailures));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_returns_handle_unless_refused() {
+        assert!(spawn(HeartbeatConfig::default()).is_none());
+
+        let server = MockServer::start().await;
+        let heartbeat = spawn(HeartbeatConfig {
+            url: server.url(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        heartbeat.abort();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !heartbeat.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_observer_sees_every_outcome() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<Result<Duration, HeartbeatError>>>);
+
+        impl HeartbeatObserver for Recorder {
+            fn on_success(&self, _status: reqwest::StatusCode, latency: Duration) {
+                self.0.lock().unwrap().push(Ok(latency));
+            }
+
+            fn on_failure(&self, err: &HeartbeatError) {
+                self.0.lock().unwrap().push(Err(err.clone()));
+            }
+        }
+
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(503));
+        server.push_response(MockResponse::status(200).delay(Duration::from_millis(150)));
+        let recorder = Arc::new(Recorder::default());
+        let heartbeat = spawn_with_observer(
+            HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            },
+            Arc::clone(&recorder) as Arc<dyn HeartbeatObserver>,
+        );
+
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        heartbeat.close().await;
+
+        let outcomes = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            outcomes[0],
+            Err(HeartbeatError::Http(reqwest::StatusCode::SERVICE_UNAVAILABLE))
+        );
+        assert!(matches!(outcomes[1], Ok(latency) if latency >= Duration::from_millis(150)));
+    }
+
+    #[tokio::test]
+    async fn test_named_loops_log_under_their_names() {
+        let (logs, _guard) = crate::test_support::capture_logs();
+        let server = MockServer::start().await;
+        let loops: Vec<_> = ["ingest", "api"]
+            .into_iter()
+            .map(|name| {
+                spawn_with_handle(HeartbeatConfig {
+                    url: server.url(),
+                    name: Some(name.to_string()),
+                    first_tick_policy: FirstTickPolicy::FireImmediately,
+                    ..HeartbeatConfig::default()
+                })
+            })
+            .collect();
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        for heartbeat in loops {
+            heartbeat.close().await;
+        }
+
+        let events = logs.events();
+        for name in ["ingest", "api"] {
+            let field = format!(" heartbeat.name={name}");
+            let texts: Vec<&str> = events
+                .iter()
+                .filter(|event| event.spans == field)
+                .map(|event| event.text.as_str())
+                .collect();
+            assert!(texts.iter().any(|text| text.starts_with("Heartbeat task spawned")), "{name}");
+            assert!(
+                texts.iter().any(|text| text.starts_with("Heartbeat sent successfully in ")),
+                "{name}: {texts:?}"
+            );
+        }
+        assert!(events.iter().all(|event| !event.spans.is_empty()), "{events:?}");
+    }
+
+    #[tokio::test]
+    async fn test_recent_failures_are_classified() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(503));
+        server.push_response(MockResponse::status(200).delay(Duration::from_secs(3)));
+        server.push_response(MockResponse::status(404));
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 2,
+            timeout_secs: 1,
+            max_retries: 0,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        });
+
+        server.wait_for_requests(4, Duration::from_secs(10)).await;
+        let records = heartbeat.recent_failures();
+        heartbeat.close().await;
+
+        assert_eq!(
+            records.iter().map(|record| record.kind).collect::<Vec<_>>(),
+            vec![FailureKind::Status(503), FailureKind::Timeout, FailureKind::Status(404)]
+        );
+        assert_eq!(records[0].message, "heartbeat failed with status 503 Service Unavailable");
+        assert!(records.windows(2).all(|pair| pair[0].at <= pair[1].at));
+        assert!(records.iter().all(|record| !record.message.contains(&server.url())));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_gates_heartbeats() {
+        for (unhealthy_policy, expected) in [
+            (UnhealthyPolicy::Skip, vec!["/heartbeat"]),
+            (UnhealthyPolicy::ReportFail, vec!["/heartbeat/fail", "/heartbeat"]),
+        ] {
+            let server = MockServer::start().await;
+            let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let check = {
+                let healthy = Arc::clone(&healthy);
+                move || -> BoxFuture<'static, bool> {
+                    let healthy = healthy.load(std::sync::atomic::Ordering::Relaxed);
+                    Box::pin(async move { healthy })
+                }
+            };
+            let heartbeat = spawn_with_health_check(
+                HeartbeatConfig {
+                    url: server.url(),
+                    interval_secs: 1,
+                    first_tick_policy: FirstTickPolicy::FireImmediately,
+                    unhealthy_policy,
+                    ..HeartbeatConfig::default()
+                },
+                check,
+            );
+
+            // The first tick fails its check, the second passes.
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            healthy.store(true, std::sync::atomic::Ordering::Relaxed);
+            server.wait_for_requests(expected.len(), Duration::from_secs(5)).await;
+            heartbeat.abort();
+
+            let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+            assert_eq!(paths, expected, "{unhealthy_policy:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unhealthy_fail_ping_uses_transport_and_is_counted() {
+        #[derive(Debug, Default)]
+        struct Fake {
+            urls: std::sync::Mutex<Vec<String>>,
+        }
+
+        impl HeartbeatTransport for Fake {
+            fn ping<'a>(
+                &'a self,
+                url: &'a str,
+            ) -> BoxFuture<'a, Result<reqwest::StatusCode, HeartbeatError>> {
+                self.urls.lock().unwrap().push(url.to_string());
+                Box::pin(async { Ok(reqwest::StatusCode::SERVICE_UNAVAILABLE) })
+            }
+        }
+
+        let fake = Arc::new(Fake::default());
+        let checks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let check = {
+            let checks = Arc::clone(&checks);
+            move || -> BoxFuture<'static, bool> {
+                let first = checks.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0;
+                Box::pin(async move { !first })
+            }
+        };
+        let transport = Arc::clone(&fake) as Arc<dyn HeartbeatTransport>;
+        let heartbeat = spawn_handle(
+            HeartbeatConfig {
+                url: "https://example.com/heartbeat".to_string(),
+                path_suffix: Some("eu-west".to_string()),
+                unhealthy_policy: UnhealthyPolicy::ReportFail,
+                ..HeartbeatConfig::default()
+            },
+            |control| {
+                control.health_check = Some(Arc::new(check));
+                control.transport = Some(transport);
+            },
+        );
+        tokio::time::sleep(Duration::from_secs(130)).await;
+        let stats = heartbeat.close().await;
+
+        let urls = fake.urls.lock().unwrap().clone();
+        assert_eq!(
+            urls,
+            ["https://example.com/heartbeat/eu-west/fail", "https://example.com/heartbeat/eu-west"]
+        );
+        assert_eq!((stats.successes, stats.failures), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_with_client_uses_given_client_and_timeout() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200).delay(Duration::from_secs(3)));
+        let client = reqwest::Client::builder()
+            .default_headers(reqwest::header::HeaderMap::from_iter([(
+                reqwest::header::HeaderName::from_static("x-shared-client"),
+                reqwest::header::HeaderValue::from_static("yes"),
+            )]))
+            .build()
+            .unwrap();
+
+        let heartbeat = spawn_with_client(
+            HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 2,
+                timeout_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            },
+            client,
+        );
+        let requests = server.wait_for_requests(2, Duration::from_secs(5)).await;
+        heartbeat.abort();
+
+        assert_eq!(requests[0].header("x-shared-client"), Some("yes"));
+        assert_eq!(requests[0].header("x-heartbeat-client"), None);
+        // The client has no timeout; the config's still ended the slow attempt.
+        assert_eq!(heartbeat.stats().failures, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawn_with_transport_pings_through_the_fake() {
+        #[derive(Debug, Default)]
+        struct Fake {
+            pings: std::sync::Mutex<Vec<(Instant, String)>>,
+        }
+
+        impl HeartbeatTransport for Fake {
+            fn ping<'a>(
+                &'a self,
+                url: &'a str,
+            ) -> BoxFuture<'a, Result<reqwest::StatusCode, HeartbeatError>> {
+                let count = {
+                    let mut pings = self.pings.lock().unwrap();
+                    pings.push((Instant::now(), url.to_string()));
+                    pings.len()
+                };
+                let status = if count == 1 {
+                    reqwest::StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    reqwest::StatusCode::OK
+                };
+                Box::pin(async move { Ok(status) })
+            }
+        }
+
+        let fake = Arc::new(Fake::default());
+        let started = Instant::now();
+        let heartbeat = spawn_with_transport(
+            HeartbeatConfig {
+                url: "https://example.com/heartbeat".to_string(),
+                query_params: vec![("host".to_string(), "a".to_string())],
+                ..HeartbeatConfig::default()
+            },
+            Arc::clone(&fake) as Arc<dyn HeartbeatTransport>,
+        );
+        tokio::time::sleep(Duration::from_secs(190)).await;
+        let stats = heartbeat.close().await;
+
+        let pings = fake.pings.lock().unwrap().clone();
+        let offsets: Vec<u64> = pings.iter().map(|(at, _)| (*at - started).as_secs()).collect();
+        assert_eq!(offsets, [60, 120, 180]);
+        assert_eq!(pings[0].1, "https://example.com/heartbeat?host=a");
+        assert_eq!((stats.successes, stats.failures), (2, 1));
+        assert_eq!(stats.last_status_code, Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_startup_delay_holds_back_the_first_heartbeat() {
+        let server = MockServer::start().await;
+        let started = Instant::now();
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            startup_delay_secs: 2,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        });
+
+        let requests = server.wait_for_requests(1, Duration::from_secs(5)).await;
+        assert!(requests[0].received_at - started >= Duration::from_secs(2));
+
+        // Closing during the delay does not wait for it.
+        let waiting = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            startup_delay_secs: 60,
+            ..HeartbeatConfig::default()
+        });
+        tokio::time::timeout(Duration::from_secs(1), waiting.close()).await.unwrap();
+        heartbeat.close().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn test_zero_interval_is_clamped_and_zero_timeout_refused() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        #[derive(Debug, Default)]
+        struct Slow {
+            pings: AtomicU64,
+        }
+
+        impl HeartbeatTransport for Slow {
+            fn ping<'a>(
+                &'a self,
+                _url: &'a str,
+            ) -> BoxFuture<'a, Result<reqwest::StatusCode, HeartbeatError>> {
+                self.pings.fetch_add(1, Ordering::Relaxed);
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    Ok(reqwest::StatusCode::OK)
+                })
+            }
+        }
+
+        let (logs, _guard) = crate::test_support::capture_logs();
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", "https://example.com/heartbeat");
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "0");
+        }
+        let clamped = HeartbeatConfig::from_env();
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe { std::env::set_var("HEARTBEAT_TIMEOUT_SECS", "0") };
+        let refused = HeartbeatConfig::from_env();
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+            std::env::remove_var("HEARTBEAT_TIMEOUT_SECS");
+        }
+        assert_eq!(clamped.map(|config| config.interval_secs), Some(1));
+        assert!(refused.is_none());
+
+        // Spawned loops raise the interval the same way: a heartbeat every
+        // second. A zero timeout refuses the loop.
+        let slow = Arc::new(Slow::default());
+        let spawn = |timeout_secs| {
+            spawn_with_transport(
+                HeartbeatConfig {
+                    url: "https://example.com/heartbeat".to_string(),
+                    interval_secs: 0,
+                    timeout_secs,
+                    first_tick_policy: FirstTickPolicy::FireImmediately,
+                    ..HeartbeatConfig::default()
+                },
+                Arc::clone(&slow) as Arc<dyn HeartbeatTransport>,
+            )
+        };
+        let heartbeat = spawn(1);
+        let refused = spawn(0);
+        tokio::time::sleep(Duration::from_millis(2_700)).await;
+        let stats = heartbeat.close().await;
+
+        assert!(refused.is_finished());
+        assert_eq!(slow.pings.load(Ordering::Relaxed), 3);
+        assert_eq!((stats.successes, stats.failures), (3, 0));
+        let warnings: Vec<String> = logs
+            .events()
+            .into_iter()
+            .filter(|event| event.level == tracing::Level::WARN)
+            .map(|event| event.text)
+            .collect();
+        let count = |expected: &str| warnings.iter().filter(|text| *text == expected).count();
+        // From both `from_env` calls and both spawns.
+        assert_eq!(count("Heartbeat interval of 0s is not allowed, using 1s"), 4, "{warnings:?}");
+    }
+
+    #[tokio::test]
+    async fn test_url_left_out_of_logs_when_disabled() {
+        let (logs, _guard) = crate::test_support::capture_logs();
+        let rejected = |include_url_in_logs| {
+            spawn(HeartbeatConfig {
+                url: "ftp://127.0.0.1/heartbeat/SECRET".to_string(),
+                include_url_in_logs,
+                ..HeartbeatConfig::default()
+            })
+        };
+        assert!(rejected(true).is_none());
+        assert!(logs.events().iter().any(|e| e.text.contains("`ftp://127.0.0.1/***`")));
+
+        let (logs, _guard) = crate::test_support::capture_logs();
+        assert!(rejected(false).is_none());
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: "http://127.0.0.1:1/heartbeat/SECRET".to_string(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            max_retries: 1,
+            retry_base_delay_ms: 10,
+            include_url_in_logs: false,
+            name: Some("billing".to_string()),
+            ..HeartbeatConfig::default()
+        });
+        while heartbeat.stats().failures == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        heartbeat.close().await;
+
+        let events: Vec<_> = logs
+            .events()
+            .into_iter()
+            .filter(|e| e.target.starts_with(env!("CARGO_CRATE_NAME")))
+            .collect();
+        assert!(events.iter().any(|e| e.text.starts_with("Heartbeat not started")));
+        assert!(events.iter().any(|e| e.text.starts_with("Heartbeat attempt failed")));
+        assert!(events.iter().any(|e| e.text.starts_with("Heartbeat request failed")));
+        for event in events {
+            assert!(!event.text.contains("127.0.0.1"), "{}", event.text);
+            assert!(!event.text.contains("SECRET"), "{}", event.text);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unfollowed_redirect_counts_per_config() {
+        for treat_redirect_as_success in [false, true] {
+            let server = MockServer::start().await;
+            server.set_default_response(
+                MockResponse::status(302).header("location", "https://example.com/"),
+            );
+            let client = reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap();
+
+            let heartbeat = spawn_with_client(
+                HeartbeatConfig {
+                    url: server.url(),
+                    treat_redirect_as_success,
+                    first_tick_policy: FirstTickPolicy::FireImmediately,
+                    ..HeartbeatConfig::default()
+                },
+                client,
+            );
+            server.wait_for_requests(1, Duration::from_secs(5)).await;
+            let stats = heartbeat.close().await;
+
+            assert_eq!(stats.successes, u64::from(treat_redirect_as_success));
+            assert_eq!(stats.failures, u64::from(!treat_redirect_as_success));
+            assert_eq!(stats.last_status_code, Some(302));
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_run_is_driven_by_the_caller() {
+        tokio::time::timeout(Duration::from_secs(1), run(HeartbeatConfig::default()))
+            .await
+            .expect("a refused config should end the loop at once");
+
+        let server = MockServer::start().await;
+        let heartbeat = run(HeartbeatConfig {
+            url: server.url(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        });
+        tokio::select! {
+            () = heartbeat => panic!("the loop should keep running"),
+            _ = server.wait_for_requests(1, Duration::from_secs(5)) => {}
+        }
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_observed_reports_stats_and_aborts() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            ..HeartbeatConfig::default()
+        };
+
+        let (task, handle) = spawn_observed(config);
+
+        let stats = wait_for_stats(&handle, Duration::from_secs(5), |s| s.successes >= 1).await;
+        assert_eq!(stats.successes, 1);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(stats.last_status_code, Some(200));
+        assert!(stats.last_latency.is_some());
+
+        task.abort();
+        assert!(task.await.unwrap_err().is_cancelled());
+
+        let requests = server.request_count();
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert_eq!(server.request_count(), requests, "aborted loop kept sending");
+        assert_eq!(handle.stats().successes, 1, "status handle keeps final stats");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_handle_aborts_only_tied_loops() {
+        for (on_handle_drop, keeps_running) in
+            [(HandleDropPolicy::Detach, true), (HandleDropPolicy::Abort, false)]
+        {
+            let server = MockServer::start().await;
+            let heartbeat = spawn_with_handle(HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                on_handle_drop,
+                ..HeartbeatConfig::default()
+            });
+            server.wait_for_requests(1, Duration::from_secs(5)).await;
+
+            drop(heartbeat);
+            tokio::time::sleep(Duration::from_millis(2_500)).await;
+
+            assert_eq!(server.request_count() > 1, keeps_running, "{on_handle_drop:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_in_flight_heartbeat() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(200).delay(Duration::from_millis(300)));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 60,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+
+        let heartbeat = spawn_with_handle(config);
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        assert_eq!(heartbeat.stats().successes, 0);
+
+        let stats = tokio::time::timeout(Duration::from_secs(5), heartbeat.close())
+            .await
+            .expect("close should not wait for the next interval");
+
+        assert_eq!(stats.successes, 1);
+        assert_eq!(stats.last_status_code, Some(200));
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events_across_pause_resume_and_close() {
+        let server = MockServer::start().await;
+        let (events_tx, mut events) = tokio::sync::mpsc::channel(16);
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            events_tx: Some(events_tx),
+            ..HeartbeatConfig::default()
+        };
+        let next_event = async |events: &mut tokio::sync::mpsc::Receiver<_>| {
+            tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap()
+        };
+
+        let heartbeat = spawn_with_handle(config);
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::Spawned);
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::FirstActive);
+
+        heartbeat.pause();
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::Paused);
+        let requests = server.request_count();
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert_eq!(server.request_count(), requests, "paused loop kept sending");
+
+        heartbeat.resume();
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::Resumed);
+        server.wait_for_requests(requests + 1, Duration::from_secs(5)).await;
+
+        heartbeat.close().await;
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::Stopping);
+        assert_eq!(next_event(&mut events).await, LifecycleEvent::Stopped);
+        assert!(events.recv().await.is_none(), "loop dropped its sender");
+    }
+
+    #[tokio::test]
+    async fn test_trigger_mid_interval_follows_realign_policy() {
+        for (realign_after_trigger, expected_gap) in [(false, 1_000), (true, 2_000)] {
+            let server = MockServer::start().await;
+            let heartbeat = spawn_with_handle(HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 2,
+                realign_after_trigger,
+                ..HeartbeatConfig::default()
+            });
+
+            // Halfway to the first scheduled heartbeat at 2s.
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            heartbeat.trigger();
+            let requests = server.wait_for_requests(2, Duration::from_secs(5)).await;
+            heartbeat.abort();
+
+            let gap = requests[1].received_at - requests[0].received_at;
+            let expected = Duration::from_millis(expected_gap);
+            assert!(
+                gap.abs_diff(expected) < Duration::from_millis(400),
+                "realign_after_trigger={realign_after_trigger}: next heartbeat after {gap:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_nth_operation_sends_a_heartbeat() {
+        let server = MockServer::start().await;
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 3600,
+            ops_per_heartbeat: Some(5),
+            ..HeartbeatConfig::default()
+        });
+
+        for _ in 0..9 {
+            heartbeat.record_operation();
+        }
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        heartbeat.close().await;
+
+        assert_eq!(server.request_count(), 1);
+
+        // A zero count refuses the loop; operations on its handle do nothing.
+        let refused = spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 3600,
+            ops_per_heartbeat: Some(0),
+            ..HeartbeatConfig::default()
+        });
+        refused.record_operation();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        refused.close().await;
+        assert_eq!(server.request_count(), 1);
+
+        let never = HeartbeatConfig {
+            url: "https://example.com/heartbeat".to_string(),
+            ops_per_heartbeat: Some(0),
+            ..HeartbeatConfig::default()
+        };
+        assert_eq!(never.validate(), Err(invalid("ops_per_heartbeat", "0")));
+    }
+
+    #[tokio::test]
+    async fn test_resume_follows_schedule_policy() {
+        for (keep_schedule_on_resume, expected_gap) in [(false, 3_000), (true, 4_000)] {
+            let server = MockServer::start().await;
+            let heartbeat = spawn_with_handle(HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 2,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                keep_schedule_on_resume,
+                ..HeartbeatConfig::default()
+            });
+
+            // Paused over the heartbeat due at 2s, resumed at 3s.
+            server.wait_for_requests(1, Duration::from_secs(5)).await;
+            heartbeat.pause();
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            heartbeat.resume();
+            let requests = server.wait_for_requests(2, Duration::from_secs(5)).await;
+            heartbeat.abort();
+
+            let gap = requests[1].received_at - requests[0].received_at;
+            let expected = Duration::from_millis(expected_gap);
+            assert!(
+                gap.abs_diff(expected) < Duration::from_millis(400),
+                "keep_schedule_on_resume={keep_schedule_on_resume}: resumed after {gap:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_config_updates_are_coalesced() {
+        let (logs, _guard) = crate::test_support::capture_logs();
+        let stale = MockServer::start().await;
+        let server = MockServer::start().await;
+        let heartbeat = spawn_with_handle(HeartbeatConfig {
+            url: stale.url(),
+            config_debounce_ms: 200,
+            ..HeartbeatConfig::default()
+        });
+
+        for url in [stale.url(), stale.url(), server.url()] {
+            heartbeat.update_config(HeartbeatConfig {
+                url,
+                interval_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            });
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        heartbeat.close().await;
+
+        assert_eq!(stale.request_count(), 0);
+        assert_eq!(server.request_count(), 1);
+        let events = logs.events();
+        let rebuilds = events.iter().filter(|e| e.text.contains("rebuilt HTTP client")).count();
+        assert_eq!(rebuilds, 1);
+        assert!(events.iter().any(|e| e.text.contains("Coalesced 2 heartbeat config updates")));
+    }
+
+    #[tokio::test]
+    async fn test_startup_ping_is_sent_once_on_signal() {
+        let (server, startup) = (MockServer::start().await, MockServer::start().await);
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 30,
+            startup_url: Some(startup.url()),
+            ..HeartbeatConfig::default()
+        };
+
+        let idle = spawn_with_handle(config.clone());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(startup.request_count(), 0, "no ping without a signal");
+        idle.close().await;
+
+        let heartbeat = spawn_with_handle(config);
+        heartbeat.signal_startup_complete();
+        heartbeat.signal_startup_complete();
+        startup.wait_for_requests(1, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(startup.request_count(), 1);
+        assert_eq!(server.request_count(), 0);
+        assert_eq!(heartbeat.close().await, HeartbeatStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_next_send_at_is_one_interval_ahead() {
+        let server = MockServer::start().await;
+        let config =
+            HeartbeatConfig { url: server.url(), interval_secs: 30, ..HeartbeatConfig::default() };
+
+        let heartbeat = spawn_with_handle(config);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let expected = std::time::SystemTime::now() + Duration::from_secs(30);
+        let next = heartbeat.next_send_at().expect("a heartbeat is scheduled");
+        let offset = expected.duration_since(next).unwrap_or_else(|e| e.duration());
+        assert!(offset < Duration::from_secs(2), "next send {offset:?} away from now + interval");
+
+        heartbeat.pause();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(heartbeat.next_send_at(), None);
+        heartbeat.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_start_after_required_ready_marks() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ready_marks_required: 2,
+            ..HeartbeatConfig::default()
+        };
+
+        let heartbeat = spawn_with_handle(config);
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert_eq!(server.request_count(), 0);
+
+        heartbeat.mark_ready();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(server.request_count(), 0, "gate opened after one of two marks");
+
+        heartbeat.mark_ready();
+        let requests = server.wait_for_requests(1, Duration::from_secs(1)).await;
+        assert_eq!(requests.len(), 1);
+        heartbeat.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_dropped_events_receiver_does_not_break_loop() {
+        let server = MockServer::start().await;
+        let (events_tx, events) = tokio::sync::mpsc::channel(1);
+        drop(events);
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            events_tx: Some(events_tx),
+            ..HeartbeatConfig::default()
+        };
+
+        let heartbeat = spawn_with_handle(config);
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        assert_eq!(heartbeat.close().await.successes, 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_from_env_retry_starts_once_url_appears() {
+        let server = MockServer::start().await;
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "1");
+        }
+
+        let task = spawn_from_env_retry(Duration::from_millis(50), Duration::from_secs(10));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!task.is_finished());
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", server.url());
+        }
+
+        let requests = server.wait_for_requests(1, Duration::from_secs(5)).await;
+        assert_eq!(requests.len(), 1);
+        task.abort();
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_from_env_retry_gives_up_after_max_wait() {
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+
+        let task = spawn_from_env_retry(Duration::from_millis(20), Duration::from_millis(100));
+
+        tokio::time::timeout(Duration::from_secs(2), task)
+            .await
+            .expect("task should finish after max_wait")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fire_immediately_sends_at_start_and_every_interval() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+
+        let start = Instant::now();
+        let (task, _status) = spawn_observed(config);
+        let requests = server.wait_for_requests(3, Duration::from_secs(5)).await;
+        task.abort();
+
+        let tolerance = Duration::from_millis(250);
+        assert_eq!(requests.len(), 3);
+        for (n, request) in requests.iter().enumerate() {
+            let expected = Duration::from_secs(n as u64);
+            let actual = request.received_at - start;
+            assert!(
+                actual.abs_diff(expected) <= tolerance,
+                "heartbeat #{n} sent at {actual:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_include_timestamp_sends_current_unix_millis() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: format!("{}?region=eu", server.url()),
+            interval_secs: 1,
+            timeout_secs: 5,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            include_timestamp: true,
+            ..HeartbeatConfig::default()
+        };
+
+        let (task, _status) = spawn_observed(config);
+        let requests = server.wait_for_requests(1, Duration::from_secs(5)).await;
+        task.abort();
+
+        let now_millis =
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
+        let ts: u128 = requests[0].query_param("ts").expect("ts param").parse().unwrap();
+
+        assert_eq!(requests[0].query_param("region").as_deref(), Some("eu"));
+        assert!(now_millis.abs_diff(ts) < 5_000, "ts={ts} too far from now={now_millis}");
+    }
+
+    #[test]
+    fn test_interval_derived_from_monitor_period() {
+        let config =
+            HeartbeatConfig { monitor_period_secs: Some(120), ..HeartbeatConfig::default() };
+        assert_eq!(config.clone().resolve_interval().interval_secs, 60);
+        assert_eq!(
+            HeartbeatConfig { ping_fraction: 0.25, ..config.clone() }
+                .resolve_interval()
+                .interval_secs,
+            30
+        );
+        assert_eq!(
+            HeartbeatConfig { ping_fraction: -1.0, interval_secs: 42, ..config }
+                .resolve_interval()
+                .interval_secs,
+            42
+        );
+        assert_eq!(HeartbeatConfig::default().resolve_interval().interval_secs, 60);
+    }
+
+    #[test]
+    fn test_interval_longer_than_monitor_period_warns() {
+        let (logs, _guard) = crate::test_support::capture_logs();
+        let config = HeartbeatConfig {
+            monitor_period_secs: Some(60),
+            ping_fraction: 1.5,
+            ..HeartbeatConfig::default()
+        };
+
+        assert_eq!(config.resolve_interval().interval_secs, 90);
+        assert!(logs.events().iter().any(|e| e.level == tracing::Level::WARN
+            && e.text.contains("exceeds the monitor period 60s")));
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = HeartbeatConfig {
+            url: "https://example.com/heartbeat/TOKEN".to_string(),
+            ..HeartbeatConfig::default()
+        };
+        assert_eq!(valid.validate(), Ok(()));
+
+        assert_eq!(HeartbeatConfig::default().validate(), Err(ConfigError::MissingUrl));
+        assert_eq!(
+            HeartbeatConfig { url: "not a url".to_string(), ..valid.clone() }.validate(),
+            Err(invalid("url", "***"))
+        );
+        assert_eq!(
+            HeartbeatConfig { url: "ftp://example.com/TOKEN".to_string(), ..valid.clone() }
+                .validate(),
+            Err(invalid("url", "ftp://example.com/***"))
+        );
+        assert_eq!(
+            HeartbeatConfig { url: "file:///heartbeat".to_string(), ..valid.clone() }.validate(),
+            Err(invalid("url", "file:///***"))
+        );
+        assert_eq!(
+            HeartbeatConfig { interval_secs: 0, ..valid.clone() }.validate(),
+            Err(invalid("interval_secs", "0"))
+        );
+        let headers = vec![("Authorization".to_string(), "Bearer\nsecret".to_string())];
+        assert_eq!(
+            HeartbeatConfig { headers, ..valid.clone() }.validate(),
+            Err(invalid("headers", "Authorization"))
+        );
+        let headers = vec![("X Tenant".to_string(), "acme".to_string())];
+        assert_eq!(
+            HeartbeatConfig { headers, ..valid }.validate(),
+            Err(invalid("headers", "X Tenant"))
+        );
+    }
+
+    #[test]
+    fn test_interval_warning_below_recommended_minimum() {
+        let warning = |interval_secs| {
+            HeartbeatConfig { interval_secs, ..HeartbeatConfig::default() }.interval_warning()
+        };
+
+        assert_eq!(
+            warning(1).as_deref(),
+            Some(
+                "heartbeat interval of 1s is below the recommended minimum of 30s; Better \
+                 Uptime monitors gain nothing from more frequent pings"
+            )
+        );
+        assert_eq!(warning(60), None);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_config_interceptor_clamps_interval() {
+        let server = MockServer::start().await;
+        set_config_interceptor(|mut config| {
+            config.interval_secs = config.interval_secs.max(2);
+            Ok(config)
+        });
+
+        let spawned = try_spawn(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        });
+        let refused = try_spawn(HeartbeatConfig::default());
+        clear_config_interceptor();
+
+        assert_eq!(refused.unwrap_err(), HeartbeatError::Config(ConfigError::MissingUrl));
+        let heartbeat = spawned.unwrap();
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        heartbeat.close().await;
+        server.check_interval_timing(Duration::from_secs(2), Duration::from_millis(300)).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn test_config_interceptor_applies_to_every_spawn_function() {
+        #[derive(Debug, Default)]
+        struct Fake(std::sync::Mutex<Vec<Instant>>);
+
+        impl HeartbeatTransport for Fake {
+            fn ping<'a>(
+                &'a self,
+                _url: &'a str,
+            ) -> BoxFuture<'a, Result<reqwest::StatusCode, HeartbeatError>> {
+                self.0.lock().unwrap().push(Instant::now());
+                Box::pin(async { Ok(reqwest::StatusCode::OK) })
+            }
+        }
+
+        set_config_interceptor(|mut config| {
+            if config.url.contains("blocked") {
+                return Err(HeartbeatError::Rejected("blocked monitor".to_string()));
+            }
+            config.interval_secs = config.interval_secs.max(30);
+            Ok(config)
+        });
+        let fake = Arc::new(Fake::default());
+        let started = Instant::now();
+        let clamped = spawn_with_transport(
+            HeartbeatConfig {
+                url: "https://example.com/heartbeat".to_string(),
+                interval_secs: 10,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            },
+            Arc::clone(&fake) as Arc<dyn HeartbeatTransport>,
+        );
+        let refused = spawn_with_transport(
+            HeartbeatConfig {
+                url: "https://example.com/blocked".to_string(),
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            },
+            Arc::clone(&fake) as Arc<dyn HeartbeatTransport>,
+        );
+        clear_config_interceptor();
+
+        tokio::time::sleep(Duration::from_secs(65)).await;
+        assert!(refused.is_finished());
+        assert_eq!(refused.close().await, HeartbeatStats::default());
+        clamped.close().await;
+
+        let offsets: Vec<u64> =
+            fake.0.lock().unwrap().iter().map(|at| (*at - started).as_secs()).collect();
+        assert_eq!(offsets, [0, 30, 60]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_config_interceptor_can_reject() {
+        set_config_interceptor(|config| {
+            if config.url.starts_with("http://") {
+                Err(HeartbeatError::Rejected("plain HTTP is not allowed".to_string()))
+            } else {
+                Ok(config)
+            }
+        });
+
+        let result = try_spawn(HeartbeatConfig {
+            url: "http://example.com/heartbeat".to_string(),
+            ..HeartbeatConfig::default()
+        });
+        clear_config_interceptor();
+
+        assert_eq!(
+            result.unwrap_err(),
+            HeartbeatError::Rejected("plain HTTP is not allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_redacted_url_hides_path_and_query() {
+        let redact = |url: &str| {
+            HeartbeatConfig { url: url.to_string(), ..HeartbeatConfig::default() }.redacted_url()
+        };
+
+        assert_eq!(
+            redact("https://uptime.betterstack.com/api/v1/heartbeat/SECRET?x=1"),
+            "https://uptime.betterstack.com/***"
+        );
+        assert_eq!(redact("http://127.0.0.1:8080/heartbeat"), "http://127.0.0.1:8080/***");
+        assert_eq!(redact("not a url"), "***");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_from_env_or_prefers_env() {
+        let env_server = MockServer::start().await;
+        let fallback_server = MockServer::start().await;
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::set_var("HEARTBEAT_URL", env_server.url());
+            std::env::set_var("HEARTBEAT_INTERVAL_SECS", "1");
+        }
+
+        let heartbeat = spawn_from_env_or(HeartbeatConfig {
+            url: fallback_server.url(),
+            interval_secs: 1,
+            ..HeartbeatConfig::default()
+        });
+        env_server.wait_for_requests(1, Duration::from_secs(5)).await;
+        heartbeat.abort();
+
+        assert_eq!(env_server.request_count(), 1);
+        assert_eq!(fallback_server.request_count(), 0);
+
+        // SAFETY: Cleanup
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+            std::env::remove_var("HEARTBEAT_INTERVAL_SECS");
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_spawn_from_env_or_uses_fallback_without_env() {
+        let fallback_server = MockServer::start().await;
+        // SAFETY: Tests run sequentially and we clean up after ourselves
+        unsafe {
+            std::env::remove_var("HEARTBEAT_URL");
+        }
+
+        let heartbeat = spawn_from_env_or(HeartbeatConfig {
+            url: fallback_server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        });
+        let requests = fallback_server.wait_for_requests(1, Duration::from_secs(5)).await;
+        heartbeat.abort();
+
+        assert_eq!(requests.len(), 1);
     }
 }
@@ -0,0 +1,26 @@
+//! Source of the system load checked against `skip_above_loadavg`.
+
+use std::fmt;
+
+/// Source of the 1-minute system load average, checked before every
+/// scheduled heartbeat when [`HeartbeatConfig::skip_above_loadavg`](crate::HeartbeatConfig::skip_above_loadavg)
+/// is set.
+///
+/// Production code uses [`SystemLoad`]; a test can plug in a source
+/// returning whatever load it needs.
+pub trait LoadSource: fmt::Debug + Send + Sync {
+    /// The current 1-minute load average, `None` if it is unknown.
+    fn load_average(&self) -> Option<f64>;
+}
+
+/// The default [`LoadSource`]: the first field of `/proc/loadavg` on Linux.
+/// On other platforms the load is unknown, so no heartbeat is skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemLoad;
+
+impl LoadSource for SystemLoad {
+    fn load_average(&self) -> Option<f64> {
+        let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+        loadavg.split_whitespace().next()?.parse().ok()
+    }
+}
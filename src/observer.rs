@@ -0,0 +1,66 @@
+//! Callbacks on the outcome of every heartbeat.
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::HeartbeatError;
+
+/// Receives the outcome of every heartbeat of a loop started with
+/// [`spawn_with_observer`](crate::spawn_with_observer), e.g. to count them
+/// in your own metrics.
+///
+/// Each heartbeat is reported once, after its retries: successes with the
+/// response status and the latency of the successful attempt, failures
+/// with the [`HeartbeatError`] telling a timeout, a connection error and a
+/// rejecting status apart. Failures suppressed with
+/// [`HeartbeatHandle::suppress_failures_until`](crate::HeartbeatHandle::suppress_failures_until)
+/// are not reported. The methods run on the loop's task and should return
+/// quickly. Both do nothing by default.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::time::Duration;
+///
+/// use betteruptime_heartbeat::{HeartbeatError, HeartbeatObserver};
+/// use reqwest::StatusCode;
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     successes: AtomicU64,
+///     failures: AtomicU64,
+/// }
+///
+/// impl HeartbeatObserver for Counters {
+///     fn on_success(&self, _status: StatusCode, latency: Duration) {
+///         self.successes.fetch_add(1, Ordering::Relaxed);
+///         if latency > Duration::from_secs(5) {
+///             eprintln!("slow heartbeat: {latency:?}");
+///         }
+///     }
+///
+///     fn on_failure(&self, _err: &HeartbeatError) {
+///         self.failures.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait HeartbeatObserver: Send + Sync {
+    /// Called after a successful heartbeat with its response status and the
+    /// time until the response headers arrived.
+    fn on_success(&self, status: StatusCode, latency: Duration) {
+        let _ = (status, latency);
+    }
+
+    /// Called after a failed heartbeat.
+    fn on_failure(&self, err: &HeartbeatError) {
+        let _ = err;
+    }
+}
+
+/// Observer that ignores every outcome, used by loops spawned without one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl HeartbeatObserver for NoopObserver {}
@@ -0,0 +1,112 @@
+//! Lifetime counters persisted to [`HeartbeatConfig::stats_file`](crate::HeartbeatConfig::stats_file).
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::HeartbeatStats;
+use crate::status::Status;
+
+/// Contents of the stats file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct PersistedStats {
+    successes: u64,
+    failures: u64,
+}
+
+/// Add the counters stored in `path` to `status`.
+///
+/// A missing file is treated as a first start. An unreadable or corrupt
+/// file is logged and ignored; it is overwritten by the next flush.
+pub fn load(path: &Path, status: &Status) {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::debug!(
+                "Heartbeat stats file {} not found, starting from zero",
+                path.display()
+            );
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to read heartbeat stats file {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    match serde_json::from_slice::<PersistedStats>(&contents) {
+        Ok(persisted) => status.restore(persisted.successes, persisted.failures),
+        Err(e) => {
+            tracing::warn!("Ignoring corrupt heartbeat stats file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Write the lifetime counters of `stats` to `path`.
+///
+/// The file is replaced atomically so a crash mid-write cannot corrupt it.
+/// Errors are logged and otherwise ignored.
+pub fn flush(path: &Path, stats: &HeartbeatStats) {
+    let persisted = PersistedStats { successes: stats.successes, failures: stats.failures };
+    let result = serde_json::to_vec(&persisted).map_err(std::io::Error::from).and_then(|json| {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    });
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to write heartbeat stats file {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::FailureClass;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("betteruptime-heartbeat-{}-{name}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_counters_carry_over_restart() {
+        let path = temp_path("carry-over");
+
+        let first = Status::default();
+        load(&path, &first);
+        first.record_success(200, Duration::from_millis(1));
+        first.record_failure(FailureClass::Http, Some(503), Duration::from_millis(1));
+        first.record_success(200, Duration::from_millis(1));
+        flush(&path, &first.snapshot());
+
+        let second = Status::default();
+        load(&path, &second);
+        second.record_failure(FailureClass::Transport, None, Duration::from_millis(1));
+        let stats = second.snapshot();
+
+        assert_eq!((stats.successes, stats.failures), (2, 2));
+        assert_eq!(stats.consecutive_failures, 1, "streaks are not persisted");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_file_is_ignored() {
+        let path = temp_path("corrupt");
+        std::fs::write(&path, "{not json").unwrap();
+
+        let status = Status::default();
+        load(&path, &status);
+        assert_eq!(status.snapshot(), HeartbeatStats::default());
+
+        flush(&path, &HeartbeatStats { successes: 1, ..HeartbeatStats::default() });
+        load(&path, &status);
+        assert_eq!(status.snapshot().successes, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Process-wide coordination of all heartbeat loops.
+
+use std::num::NonZeroU32;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Token bucket shared by every heartbeat request, kept as the time at
+/// which the bucket would be full again.
+#[derive(Debug)]
+struct RateLimit {
+    /// Time to earn one request.
+    interval: Duration,
+    /// How far ahead of `now` reservations may go without waiting: the
+    /// burst beyond the first request.
+    tolerance: Duration,
+    /// When the next request would be allowed if no burst were left.
+    next: Option<Instant>,
+}
+
+impl RateLimit {
+    /// Reserve one request at `now`, returning how long to wait for it.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let next = self.next.map_or(now, |next| next.max(now));
+        self.next = Some(next + self.interval);
+        next.saturating_duration_since(now + self.tolerance)
+    }
+}
+
+static LIMIT: Mutex<Option<RateLimit>> = Mutex::new(None);
+
+/// Cap the heartbeat requests of the whole process at `max_requests` per
+/// `per`, e.g. to stay under Better Uptime's rate limits with many loops.
+///
+/// Every request of every loop, including retries, [`run_report`](crate::run_report)
+/// and [`HeartbeatService`](crate::HeartbeatService) calls, takes a token
+/// from a shared bucket holding up to `max_requests`, refilled evenly over
+/// `per`. When the bucket is empty the request is delayed, not dropped, and
+/// requests are served in the order they arrived. A zero `per` never
+/// delays. Replaces any previously set limit.
+///
+/// # Example
+///
+/// ```rust
+/// use std::num::NonZeroU32;
+/// use std::time::Duration;
+///
+/// use betteruptime_heartbeat::set_global_rate_limit;
+///
+/// // At most 10 heartbeat requests per second across all loops.
+/// set_global_rate_limit(NonZeroU32::new(10).unwrap(), Duration::from_secs(1));
+/// ```
+pub fn set_global_rate_limit(max_requests: NonZeroU32, per: Duration) {
+    let interval = per / max_requests.get();
+    let limit = RateLimit { interval, tolerance: interval * (max_requests.get() - 1), next: None };
+    *LIMIT.lock().unwrap_or_else(PoisonError::into_inner) = Some(limit);
+}
+
+/// Remove the limit set with [`set_global_rate_limit`]. Requests already
+/// waiting for a token still wait.
+pub fn clear_global_rate_limit() {
+    *LIMIT.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Wait until the global rate limit, if any, allows another request.
+pub async fn acquire() {
+    let reserved = LIMIT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+        .map(|limit| limit.reserve(Instant::now()));
+    let Some(wait) = reserved else {
+        return;
+    };
+    if !wait.is_zero() {
+        tracing::debug!("Heartbeat request delayed {:?} by the global rate limit", wait);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serial_test::serial;
+
+    use super::*;
+    use crate::{
+        BoxFuture, FirstTickPolicy, HeartbeatConfig, HeartbeatError, HeartbeatTransport,
+        spawn_with_transport,
+    };
+
+    /// Clears the limit when a test ends, even if it fails.
+    struct ClearOnDrop;
+
+    impl Drop for ClearOnDrop {
+        fn drop(&mut self) {
+            clear_global_rate_limit();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn test_global_rate_limit_allows_burst_then_spaces_requests() {
+        let _clear = ClearOnDrop;
+        set_global_rate_limit(NonZeroU32::new(2).unwrap(), Duration::from_secs(1));
+        let start = Instant::now();
+
+        let mut offsets = Vec::new();
+        for _ in 0..5 {
+            acquire().await;
+            offsets.push(start.elapsed().as_millis());
+        }
+        assert_eq!(offsets, vec![0, 0, 500, 1_000, 1_500]);
+
+        clear_global_rate_limit();
+        acquire().await;
+        assert_eq!(start.elapsed().as_millis(), 1_500);
+    }
+
+    #[tokio::test(start_paused = true)]
+    #[serial]
+    async fn test_global_rate_limit_bounds_all_loops() {
+        #[derive(Debug, Default)]
+        struct Fake(std::sync::Mutex<Vec<Instant>>);
+
+        impl HeartbeatTransport for Fake {
+            fn ping<'a>(
+                &'a self,
+                _url: &'a str,
+            ) -> BoxFuture<'a, Result<reqwest::StatusCode, HeartbeatError>> {
+                self.0.lock().unwrap().push(Instant::now());
+                Box::pin(async { Ok(reqwest::StatusCode::OK) })
+            }
+        }
+
+        let _clear = ClearOnDrop;
+        set_global_rate_limit(NonZeroU32::new(2).unwrap(), Duration::from_secs(1));
+        let fake = Arc::new(Fake::default());
+        let started = Instant::now();
+        let loops: Vec<_> = (0..6)
+            .map(|_| {
+                spawn_with_transport(
+                    HeartbeatConfig {
+                        url: "https://example.com/heartbeat".to_string(),
+                        interval_secs: 1,
+                        first_tick_policy: FirstTickPolicy::FireImmediately,
+                        ..HeartbeatConfig::default()
+                    },
+                    Arc::clone(&fake) as Arc<dyn HeartbeatTransport>,
+                )
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(3200)).await;
+        for heartbeat in &loops {
+            heartbeat.abort();
+        }
+
+        // Unlimited, the six loops would send six requests every second.
+        // The limit allows a burst of 2, then one every 500ms.
+        let offsets: Vec<u128> =
+            fake.0.lock().unwrap().iter().map(|at| (*at - started).as_millis()).collect();
+        assert_eq!(offsets, [0, 0, 500, 1_000, 1_500, 2_000, 2_500, 3_000]);
+    }
+}
@@ -0,0 +1,420 @@
+//! Per-request construction of heartbeat requests.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+
+use crate::HeartbeatConfig;
+
+/// Query parameter carrying the send time in Unix milliseconds.
+pub const TIMESTAMP_PARAM: &str = "ts";
+
+/// W3C trace context header.
+#[cfg(feature = "otel")]
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Path segment of the Better Uptime endpoint recording a failed run.
+pub const FAIL_SEGMENT: &str = "fail";
+
+/// Path segment of the Better Uptime endpoint recording a started run.
+pub const START_SEGMENT: &str = "start";
+
+/// Header identifying the application sending the heartbeat.
+pub const CLIENT_HEADER: &str = "x-heartbeat-client";
+
+/// Name identifying the application in requests.
+///
+/// Uses [`HeartbeatConfig::app_name`] if set, otherwise the file name of the
+/// running executable (without extension). Falls back to this crate's name
+/// if the executable path cannot be determined.
+pub fn app_name(config: &HeartbeatConfig) -> String {
+    config
+        .app_name
+        .clone()
+        .or_else(executable_name)
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string())
+}
+
+/// Default headers of the HTTP client: [`CLIENT_HEADER`] with the
+/// [`app_name`], unless that is not a valid header value.
+pub fn client_headers(config: &HeartbeatConfig) -> HeaderMap {
+    let app_name = app_name(config);
+    let Ok(value) = HeaderValue::from_str(&app_name) else {
+        tracing::debug!("App name {:?} is not a valid header value, not sent", app_name);
+        return HeaderMap::new();
+    };
+    HeaderMap::from_iter([(HeaderName::from_static(CLIENT_HEADER), value)])
+}
+
+/// Headers of the heartbeat with the given sequence number, in the order
+/// they are added: user-agent, `Accept`, `traceparent` (`otel` feature),
+/// [`HeartbeatConfig::headers`] and, for a body without a configured
+/// `Content-Type`, `Content-Type: application/json`. Shared by the async and
+/// blocking senders so
+/// that both send the same request; invalid names or values make the send
+/// fail with reqwest's error.
+pub fn headers(config: &HeartbeatConfig, sequence: usize) -> Vec<(String, String)> {
+    let mut headers = Vec::with_capacity(config.headers.len() + 3);
+    if let Some(user_agent) = user_agent(config, sequence) {
+        headers.push((USER_AGENT.to_string(), user_agent.to_string()));
+    }
+    if let Some(accept) = &config.accept {
+        headers.push((ACCEPT.to_string(), accept.clone()));
+    }
+    #[cfg(feature = "otel")]
+    if let Some(traceparent) = traceparent() {
+        headers.push((TRACEPARENT_HEADER.to_string(), traceparent));
+    }
+    headers.extend(config.headers.iter().cloned());
+    let has_content_type =
+        headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"));
+    if config.body_template.is_some() && !has_content_type {
+        headers.push((CONTENT_TYPE.to_string(), "application/json".to_string()));
+    }
+    headers
+}
+
+fn executable_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    exe.file_stem()?.to_str().map(str::to_string)
+}
+
+/// User-agent for the heartbeat with the given sequence number.
+///
+/// Rotates round-robin through [`HeartbeatConfig::user_agents`] if it is
+/// non-empty, otherwise uses [`HeartbeatConfig::user_agent`]. `None` keeps
+/// reqwest's default.
+pub fn user_agent(config: &HeartbeatConfig, sequence: usize) -> Option<&str> {
+    if config.user_agents.is_empty() {
+        return config.user_agent.as_deref();
+    }
+    Some(&config.user_agents[sequence % config.user_agents.len()])
+}
+
+/// W3C `traceparent` value for the current span's OpenTelemetry context,
+/// `None` if there is no valid one.
+#[cfg(feature = "otel")]
+pub fn traceparent() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| {
+        format!(
+            "00-{}-{}-{:02x}",
+            span_context.trace_id(),
+            span_context.span_id(),
+            span_context.trace_flags().to_u8()
+        )
+    })
+}
+
+/// Body of the heartbeat with the given sequence number.
+///
+/// Renders [`HeartbeatConfig::body_template`], replacing `{{app_name}}`,
+/// `{{timestamp}}` and `{{sequence}}`. Other text, including unknown
+/// placeholders, is sent as is.
+pub fn body(config: &HeartbeatConfig, sequence: usize) -> Option<String> {
+    let template = config.body_template.as_deref()?;
+    let mut body = template.replace("{{sequence}}", &sequence.to_string());
+    if body.contains("{{timestamp}}") {
+        body = body.replace("{{timestamp}}", &unix_millis().to_string());
+    }
+    if body.contains("{{app_name}}") {
+        body = body.replace("{{app_name}}", &app_name(config));
+    }
+    Some(body)
+}
+
+/// Whether `status` counts as a successful heartbeat: one of
+/// [`HeartbeatConfig::success_statuses`] if set, otherwise any 2xx status,
+/// or 3xx status with [`HeartbeatConfig::treat_redirect_as_success`].
+pub fn is_success(config: &HeartbeatConfig, status: reqwest::StatusCode) -> bool {
+    if config.success_statuses.is_empty() {
+        status.is_success() || (config.treat_redirect_as_success && status.is_redirection())
+    } else {
+        config.success_statuses.contains(&status.as_u16())
+    }
+}
+
+/// How a query parameter added by the crate is handled when `config.url`
+/// already has a parameter with the same key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QueryConflictPolicy {
+    /// Replace the existing value (the first occurrence keeps its position,
+    /// later ones are removed).
+    Overwrite,
+    /// Keep the value from `config.url` and drop the added one.
+    #[default]
+    Skip,
+    /// Keep both, adding the new pair after the existing ones.
+    Append,
+}
+
+/// Resolve the URL for a single heartbeat request.
+///
+/// Appends [`HeartbeatConfig::path_suffix`] to the path of `config.url`,
+/// then [`HeartbeatConfig::query_params`] and the per-request query
+/// parameters enabled in `config` to any parameters already present in
+/// `config.url`, resolving clashes with the latter according to
+/// [`HeartbeatConfig::query_conflict_policy`]. If the URL cannot be parsed
+/// it is returned unchanged so the send fails with reqwest's error.
+pub fn request_url(config: &HeartbeatConfig) -> String {
+    let base = suffixed_url(config);
+    let mut added = config.query_params.clone();
+    if config.include_timestamp {
+        added.push((TIMESTAMP_PARAM.to_string(), unix_millis().to_string()));
+    }
+    if added.is_empty() {
+        return base;
+    }
+
+    let Ok(mut url) = reqwest::Url::parse(&base) else {
+        return base;
+    };
+
+    let mut base: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let mut extra = Vec::new();
+    for (key, value) in added {
+        if !base.iter().any(|(k, _)| *k == key) {
+            extra.push((key, value));
+            continue;
+        }
+        match config.query_conflict_policy {
+            QueryConflictPolicy::Overwrite => {
+                let mut value = Some(value);
+                base.retain_mut(|(k, v)| *k != key || value.take().map(|new| *v = new).is_some());
+            }
+            QueryConflictPolicy::Skip => {}
+            QueryConflictPolicy::Append => extra.push((key, value)),
+        }
+    }
+
+    url.query_pairs_mut().clear().extend_pairs(base.iter().chain(&extra));
+    url.into()
+}
+
+/// `config` for pinging the Better Uptime endpoint `segment` of its
+/// monitor, such as [`FAIL_SEGMENT`]. The segment follows the
+/// [`path_suffix`](HeartbeatConfig::path_suffix), which is folded into the
+/// URL.
+pub fn endpoint_config(config: &HeartbeatConfig, segment: &str) -> HeartbeatConfig {
+    HeartbeatConfig {
+        url: endpoint_url(&suffixed_url(config), segment),
+        path_suffix: None,
+        ..config.clone()
+    }
+}
+
+/// `config.url` with the [`path_suffix`](HeartbeatConfig::path_suffix)
+/// appended.
+fn suffixed_url(config: &HeartbeatConfig) -> String {
+    config
+        .path_suffix
+        .as_deref()
+        .map_or_else(|| config.url.clone(), |suffix| endpoint_url(&config.url, suffix))
+}
+
+/// `url` with the path segments of `path` appended, keeping its query.
+///
+/// A trailing slash of `url` is replaced rather than producing an empty
+/// segment, and empty segments of `path` (from leading, trailing or double
+/// slashes) are dropped. Unparseable URLs are returned unchanged.
+pub fn endpoint_url(url: &str, path: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().extend(path.split('/').filter(|segment| !segment.is_empty()));
+    }
+    url.into()
+}
+
+fn unix_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_url_appends_segment() {
+        assert_eq!(
+            endpoint_url("https://example.com/api/v1/heartbeat/TOKEN", FAIL_SEGMENT),
+            "https://example.com/api/v1/heartbeat/TOKEN/fail"
+        );
+        assert_eq!(
+            endpoint_url("https://example.com/TOKEN/?a=1", FAIL_SEGMENT),
+            "https://example.com/TOKEN/fail?a=1"
+        );
+        assert_eq!(
+            endpoint_url("https://example.com/TOKEN/", START_SEGMENT),
+            "https://example.com/TOKEN/start"
+        );
+    }
+
+    #[test]
+    fn test_app_name_prefers_configured_name() {
+        let config = HeartbeatConfig {
+            app_name: Some("billing-api".to_string()),
+            ..HeartbeatConfig::default()
+        };
+
+        assert_eq!(app_name(&config), "billing-api");
+    }
+
+    #[test]
+    fn test_app_name_derives_from_executable() {
+        let exe = std::env::current_exe().unwrap();
+        let expected = exe.file_stem().unwrap().to_str().unwrap();
+
+        assert_eq!(app_name(&HeartbeatConfig::default()), expected);
+    }
+
+    #[test]
+    fn test_user_agent_list_overrides_single_value() {
+        let mut config = HeartbeatConfig {
+            user_agent: Some("single".to_string()),
+            ..HeartbeatConfig::default()
+        };
+        assert_eq!(user_agent(&config, 0), Some("single"));
+        assert_eq!(user_agent(&config, 7), Some("single"));
+
+        config.user_agents = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(user_agent(&config, 0), Some("a"));
+        assert_eq!(user_agent(&config, 1), Some("b"));
+        assert_eq!(user_agent(&config, 2), Some("a"));
+    }
+
+    #[test]
+    fn test_body_template_placeholders() {
+        let config = HeartbeatConfig {
+            app_name: Some("billing-api".to_string()),
+            body_template: Some(
+                r#"{"app":"{{app_name}}","seq":{{sequence}},"at":{{timestamp}},"x":"{{other}}"}"#
+                    .to_string(),
+            ),
+            ..HeartbeatConfig::default()
+        };
+
+        let body = body(&config, 3).unwrap();
+        assert!(body.starts_with(r#"{"app":"billing-api","seq":3,"at":"#), "{body}");
+        assert!(body.ends_with(r#","x":"{{other}}"}"#), "{body}");
+        assert!(!body.contains("{{timestamp}}"), "{body}");
+        assert_eq!(super::body(&HeartbeatConfig::default(), 0), None);
+    }
+
+    #[test]
+    fn test_body_is_sent_as_json_unless_content_type_is_configured() {
+        let content_types = |config: &HeartbeatConfig| -> Vec<String> {
+            headers(config, 0)
+                .into_iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                .map(|(_, value)| value)
+                .collect()
+        };
+        let with_body =
+            HeartbeatConfig { body_template: Some("{}".to_string()), ..HeartbeatConfig::default() };
+        assert_eq!(content_types(&with_body), ["application/json"]);
+
+        let own = HeartbeatConfig {
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            ..with_body
+        };
+        assert_eq!(content_types(&own), ["text/plain"]);
+        assert!(content_types(&HeartbeatConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_success_statuses_override_2xx() {
+        let mut config = HeartbeatConfig::default();
+        assert!(is_success(&config, reqwest::StatusCode::OK));
+        assert!(!is_success(&config, reqwest::StatusCode::FOUND));
+
+        config.success_statuses = vec![202, 302];
+        assert!(!is_success(&config, reqwest::StatusCode::OK));
+        assert!(is_success(&config, reqwest::StatusCode::ACCEPTED));
+        assert!(is_success(&config, reqwest::StatusCode::FOUND));
+    }
+
+    #[test]
+    fn test_path_suffix_is_joined_with_one_slash() {
+        let url = |url: &str, suffix: &str| {
+            request_url(&HeartbeatConfig {
+                url: url.to_string(),
+                path_suffix: Some(suffix.to_string()),
+                ..HeartbeatConfig::default()
+            })
+        };
+
+        assert_eq!(url("https://example.com/TOKEN", "ok"), "https://example.com/TOKEN/ok");
+        assert_eq!(url("https://example.com/TOKEN/", "/ok"), "https://example.com/TOKEN/ok");
+        assert_eq!(
+            url("https://example.com/TOKEN/?a=1", "eu-west/ok/"),
+            "https://example.com/TOKEN/eu-west/ok?a=1"
+        );
+    }
+
+    #[test]
+    fn test_endpoint_follows_path_suffix() {
+        let config = HeartbeatConfig {
+            url: "https://example.com/TOKEN?a=1".to_string(),
+            path_suffix: Some("eu-west".to_string()),
+            ..HeartbeatConfig::default()
+        };
+
+        let fail = endpoint_config(&config, FAIL_SEGMENT);
+        assert_eq!(request_url(&fail), "https://example.com/TOKEN/eu-west/fail?a=1");
+        assert_eq!(fail.path_suffix, None);
+    }
+
+    #[test]
+    fn test_request_url_unchanged_by_default() {
+        let config = HeartbeatConfig {
+            url: "https://example.com/heartbeat?a=1".to_string(),
+            ..HeartbeatConfig::default()
+        };
+
+        assert_eq!(request_url(&config), "https://example.com/heartbeat?a=1");
+    }
+
+    #[test]
+    fn test_query_conflict_policy_decides_final_query() {
+        let config = |policy| HeartbeatConfig {
+            url: "https://example.com/heartbeat?host=x&a=1&host=y".to_string(),
+            query_params: vec![
+                ("host".to_string(), "web-1".to_string()),
+                ("region".to_string(), "eu".to_string()),
+            ],
+            query_conflict_policy: policy,
+            ..HeartbeatConfig::default()
+        };
+        let query = |policy| {
+            reqwest::Url::parse(&request_url(&config(policy))).unwrap().query().unwrap().to_string()
+        };
+
+        assert_eq!(query(QueryConflictPolicy::Skip), "host=x&a=1&host=y&region=eu");
+        assert_eq!(query(QueryConflictPolicy::Overwrite), "host=web-1&a=1&region=eu");
+        assert_eq!(query(QueryConflictPolicy::Append), "host=x&a=1&host=y&host=web-1&region=eu");
+    }
+
+    #[test]
+    fn test_request_url_appends_timestamp_to_existing_query() {
+        let config = HeartbeatConfig {
+            url: "https://example.com/heartbeat?a=1".to_string(),
+            include_timestamp: true,
+            ..HeartbeatConfig::default()
+        };
+
+        let url = reqwest::Url::parse(&request_url(&config)).unwrap();
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0], ("a".to_string(), "1".to_string()));
+        assert_eq!(pairs[1].0, TIMESTAMP_PARAM);
+    }
+}
@@ -0,0 +1,1980 @@
+//! The heartbeat loop and its building blocks.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, HeaderMap};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::backoff::Backoff;
+use crate::events::{self, LifecycleEvent};
+use crate::failure::FailureStreaks;
+use crate::handle::{
+    ConfigUpdate, Control, HealthCheck, LoopControl, config_updated, control_changed,
+    shutdown_requested, triggered, wait_while_paused,
+};
+use crate::schedule::{self, Schedule};
+use crate::status::{SkipReason, Status, StopReason};
+use crate::{
+    BodyDecodePolicy, ErrorDetail, FailureClass, HeartbeatConfig, HeartbeatError,
+    HeartbeatObserver, HeartbeatTransport, HttpVersion, IpFamily, LoadSource, SystemLoad,
+    UnhealthyPolicy, request,
+};
+
+/// Summary of a bounded heartbeat run, returned by [`run_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeartbeatReport {
+    /// Number of successful heartbeats.
+    pub successes: u64,
+    /// Number of failed heartbeats.
+    pub failures: u64,
+    /// Sum of the round-trip times of all heartbeats (final attempts only).
+    pub total_latency: Duration,
+    /// Status code of each heartbeat in order, `None` if no response was received.
+    pub statuses: Vec<Option<u16>>,
+}
+
+/// Result of a single heartbeat attempt.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub success: bool,
+    /// Class of the failure, `None` on success or for a failure during a
+    /// suppression window.
+    pub failure: Option<FailureClass>,
+    pub status_code: Option<u16>,
+    pub latency: Duration,
+    /// Why the heartbeat failed, `None` on success.
+    pub error: Option<HeartbeatError>,
+}
+
+impl Outcome {
+    /// The error of a failed heartbeat, for callers reporting one request.
+    pub fn into_result(self) -> Result<(), HeartbeatError> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+/// Function adjusting the [`reqwest::ClientBuilder`] of a loop's HTTP
+/// client, for options without a config field of their own, such as a
+/// proxy or extra root certificates.
+///
+/// It runs after the crate has applied its own settings (timeout,
+/// `X-Heartbeat-Client` header, pool size, HTTP version and IP family), so
+/// it may override them. Cloning is cheap; clones share the same function.
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::{ClientCustomizer, HeartbeatConfig};
+///
+/// let config = HeartbeatConfig {
+///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///     client_customizer: Some(ClientCustomizer::new(|builder| builder.tcp_nodelay(true))),
+///     ..HeartbeatConfig::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct ClientCustomizer(Arc<CustomizeFn>);
+
+type CustomizeFn = dyn Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync;
+
+impl ClientCustomizer {
+    /// Wrap a function receiving the builder and returning it adjusted.
+    pub fn new<F>(customize: F) -> Self
+    where
+        F: Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync + 'static,
+    {
+        Self(Arc::new(customize))
+    }
+}
+
+impl std::fmt::Debug for ClientCustomizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ClientCustomizer(..)")
+    }
+}
+
+/// Why [`build_client`] fails for `HttpVersion::Http2` in builds without
+/// the `http2` feature.
+#[cfg(not(feature = "http2"))]
+pub const HTTP2_DISABLED: &str = "HttpVersion::Http2 requires the `http2` feature";
+
+/// Build the HTTP client used for heartbeat requests, or describe why it
+/// cannot be built.
+pub fn build_client(config: &HeartbeatConfig) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(config.timeout_secs));
+
+    builder = builder.default_headers(request::client_headers(config));
+
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+
+    match config.http_version.unwrap_or_default() {
+        HttpVersion::Http1 => builder = builder.http1_only(),
+        #[cfg(feature = "http2")]
+        HttpVersion::Http2 => builder = builder.http2_prior_knowledge(),
+        #[cfg(not(feature = "http2"))]
+        HttpVersion::Http2 => return Err(HTTP2_DISABLED.to_string()),
+        HttpVersion::Auto => {}
+    }
+
+    if let Some(address) = local_address(config.ip_family.unwrap_or_default()) {
+        builder = builder.local_address(address);
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    if config.warn_cert_expiry_days.is_some() {
+        builder = builder.tls_info(true);
+    }
+
+    if let Some(customizer) = &config.client_customizer {
+        builder = (customizer.0)(builder);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Local address restricting connections to `family`, `None` for any.
+pub const fn local_address(family: IpFamily) -> Option<IpAddr> {
+    match family {
+        IpFamily::V4Only => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        IpFamily::V6Only => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        IpFamily::Auto => None,
+    }
+}
+
+/// Build the client, logging an error if that fails.
+pub fn build_client_or_log(config: &HeartbeatConfig) -> Option<reqwest::Client> {
+    match build_client(config) {
+        Ok(c) => Some(c),
+        Err(e) => {
+            tracing::error!("Failed to create HTTP client for heartbeat: {}", e);
+            None
+        }
+    }
+}
+
+/// Why a single attempt failed.
+#[derive(Debug)]
+pub enum AttemptError {
+    /// A response was received with a status not counted as success.
+    Status(reqwest::StatusCode),
+    /// No response was received, or its body could not be read. The error
+    /// never contains the URL.
+    Request(Arc<reqwest::Error>),
+    /// The body of a successful response is not valid UTF-8.
+    BodyDecode(std::str::Utf8Error),
+    /// The body validator rejected a successful response.
+    BodyRejected(reqwest::StatusCode),
+    /// The attempt as a whole took longer than the configured timeout.
+    DeadlineExceeded(Duration),
+    /// A successful response's `ETag`, if any, differs from the expected one.
+    EtagMismatch(Option<String>),
+    /// The gRPC health check call failed. Boxed because `tonic::Status` is
+    /// much larger than the other variants.
+    #[cfg(feature = "grpc")]
+    Grpc(Box<tonic::Status>),
+    /// The gRPC health check returned a status other than `SERVING`.
+    #[cfg(feature = "grpc")]
+    NotServing(&'static str),
+    /// A [`HeartbeatTransport`] got no response.
+    Transport(HeartbeatError),
+}
+
+impl AttemptError {
+    pub const fn status_code(&self) -> Option<u16> {
+        match self {
+            Self::Status(status) | Self::BodyRejected(status) => Some(status.as_u16()),
+            Self::Request(_)
+            | Self::BodyDecode(_)
+            | Self::DeadlineExceeded(_)
+            | Self::EtagMismatch(_) => None,
+            #[cfg(feature = "grpc")]
+            Self::Grpc(_) | Self::NotServing(_) => None,
+            Self::Transport(e) => e.status_code(),
+        }
+    }
+
+    pub const fn class(&self) -> FailureClass {
+        match self {
+            Self::Request(_) | Self::DeadlineExceeded(_) => FailureClass::Transport,
+            Self::Status(_)
+            | Self::BodyDecode(_)
+            | Self::BodyRejected(_)
+            | Self::EtagMismatch(_) => FailureClass::Http,
+            #[cfg(feature = "grpc")]
+            Self::Grpc(_) => FailureClass::Transport,
+            #[cfg(feature = "grpc")]
+            Self::NotServing(_) => FailureClass::Http,
+            Self::Transport(HeartbeatError::Http(_)) => FailureClass::Http,
+            Self::Transport(_) => FailureClass::Transport,
+        }
+    }
+
+    /// The public error describing this failure.
+    pub fn to_error(&self) -> HeartbeatError {
+        match self {
+            Self::Status(status) => HeartbeatError::Http(*status),
+            Self::Request(e) if e.is_timeout() => HeartbeatError::Timeout,
+            Self::Request(e) if e.is_connect() => HeartbeatError::Connect(detail(e)),
+            Self::Request(e) => HeartbeatError::Other(detail(e)),
+            Self::DeadlineExceeded(_) => HeartbeatError::Timeout,
+            Self::BodyDecode(_) | Self::BodyRejected(_) | Self::EtagMismatch(_) => {
+                HeartbeatError::Other(self.to_string().into())
+            }
+            #[cfg(feature = "grpc")]
+            Self::Grpc(status) => match status.code() {
+                tonic::Code::Unavailable => HeartbeatError::Connect(status.message().into()),
+                tonic::Code::DeadlineExceeded => HeartbeatError::Timeout,
+                _ => HeartbeatError::Other(self.to_string().into()),
+            },
+            #[cfg(feature = "grpc")]
+            Self::NotServing(_) => HeartbeatError::Other(self.to_string().into()),
+            Self::Transport(e) => e.clone(),
+        }
+    }
+
+    pub fn log_final(&self) {
+        match self {
+            Self::Status(status) => {
+                tracing::warn!("Heartbeat request returned unexpected status: {}", status);
+            }
+            Self::Request(e) => tracing::warn!("Heartbeat request failed: {}", describe(e)),
+            Self::Transport(e) => tracing::warn!("Heartbeat request failed: {}", e),
+            Self::BodyDecode(e) => {
+                tracing::warn!("Heartbeat response body could not be decoded: {}", e);
+            }
+            Self::BodyRejected(_) => tracing::warn!("Heartbeat response body failed validation"),
+            Self::DeadlineExceeded(deadline) => {
+                tracing::warn!("Heartbeat attempt deadline exceeded after {:?}", deadline);
+            }
+            Self::EtagMismatch(etag) => {
+                let etag = etag.as_deref().unwrap_or("none");
+                tracing::warn!("Heartbeat response ETag changed: got {}", etag);
+            }
+            #[cfg(feature = "grpc")]
+            Self::Grpc(status) => tracing::warn!("Heartbeat health check failed: {}", status),
+            #[cfg(feature = "grpc")]
+            Self::NotServing(status) => {
+                tracing::warn!("Heartbeat health check returned {}", status);
+            }
+        }
+    }
+}
+
+/// The public detail of `e`, keeping it as the cause.
+fn detail(e: &Arc<reqwest::Error>) -> ErrorDetail {
+    ErrorDetail::with_cause(describe(e), Arc::clone(e))
+}
+
+impl std::fmt::Display for AttemptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Status(status) => write!(f, "unexpected status {status}"),
+            Self::Request(e) => f.write_str(&describe(e)),
+            Self::BodyDecode(e) => write!(f, "undecodable body: {e}"),
+            Self::BodyRejected(_) => write!(f, "body failed validation"),
+            Self::DeadlineExceeded(deadline) => write!(f, "deadline of {deadline:?} exceeded"),
+            Self::EtagMismatch(Some(etag)) => write!(f, "unexpected ETag {etag}"),
+            Self::EtagMismatch(None) => write!(f, "missing ETag"),
+            #[cfg(feature = "grpc")]
+            Self::Grpc(status) => write!(f, "gRPC status {}: {}", status.code(), status.message()),
+            #[cfg(feature = "grpc")]
+            Self::NotServing(status) => write!(f, "service is {status}"),
+            Self::Transport(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AttemptError {
+    /// Wrap `e` without the secret heartbeat URL.
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(Arc::new(e.without_url()))
+    }
+}
+
+/// Describe `e` with its causes.
+fn describe(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+    message
+}
+
+/// Send a single request and classify the response.
+///
+/// The whole attempt, including reading and validating the body, is bounded
+/// by the configured timeout. The returned latency is the time until the
+/// response headers arrived, or the full attempt time if none did.
+async fn attempt(
+    client: &reqwest::Client,
+    config: &HeartbeatConfig,
+    sequence: usize,
+) -> (Result<u16, AttemptError>, Duration) {
+    #[cfg(feature = "registry")]
+    crate::registry::acquire().await;
+    let started = Instant::now();
+    let deadline = Duration::from_secs(config.timeout_secs);
+
+    let attempt = async {
+        let mut request = client.request(config.method.clone(), request::request_url(config));
+        for (name, value) in request::headers(config, sequence) {
+            request = request.header(name, value);
+        }
+        if let Some(body) = request::body(config, sequence) {
+            request = request.body(body);
+        }
+        let result = request.send().await;
+        let latency = started.elapsed();
+
+        let result = match result {
+            Ok(response) if request::is_success(config, response.status()) => {
+                #[cfg(feature = "rustls-tls")]
+                if let Some(days) = config.warn_cert_expiry_days {
+                    crate::cert::check(response.extensions().get(), days);
+                }
+                match check_etag(config, response.headers()) {
+                    Ok(()) => check_body(config, response).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Ok(response) => Err(AttemptError::Status(response.status())),
+            Err(e) => Err(e.into()),
+        };
+
+        (result, latency)
+    };
+
+    tokio::time::timeout(deadline, attempt)
+        .await
+        .unwrap_or_else(|_| (Err(AttemptError::DeadlineExceeded(deadline)), started.elapsed()))
+}
+
+/// Ping through `transport` and classify the status, bounded by the
+/// configured timeout.
+async fn ping(
+    transport: &dyn HeartbeatTransport,
+    config: &HeartbeatConfig,
+) -> (Result<u16, AttemptError>, Duration) {
+    #[cfg(feature = "registry")]
+    crate::registry::acquire().await;
+    let started = Instant::now();
+    let deadline = Duration::from_secs(config.timeout_secs);
+    let url = request::request_url(config);
+
+    let result = match tokio::time::timeout(deadline, transport.ping(url.as_str())).await {
+        Ok(Ok(status)) if request::is_success(config, status) => Ok(status.as_u16()),
+        Ok(Ok(status)) => Err(AttemptError::Status(status)),
+        Ok(Err(e)) => Err(AttemptError::Transport(e)),
+        Err(_) => Err(AttemptError::DeadlineExceeded(deadline)),
+    };
+    (result, started.elapsed())
+}
+
+/// Fail a successful response whose `ETag` differs from
+/// [`HeartbeatConfig::expected_etag`], if set.
+pub fn check_etag(config: &HeartbeatConfig, headers: &HeaderMap) -> Result<(), AttemptError> {
+    let Some(expected) = &config.expected_etag else {
+        return Ok(());
+    };
+    let etag =
+        headers.get(ETAG).map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+    if etag.as_deref() == Some(expected.as_str()) {
+        Ok(())
+    } else {
+        Err(AttemptError::EtagMismatch(etag))
+    }
+}
+
+/// Run the configured body validator on a successful response.
+async fn check_body(
+    config: &HeartbeatConfig,
+    response: reqwest::Response,
+) -> Result<u16, AttemptError> {
+    let status = response.status();
+    let Some(validator) = &config.body_validator else {
+        return Ok(status.as_u16());
+    };
+
+    let bytes = response.bytes().await.map_err(AttemptError::from)?;
+    let body = match std::str::from_utf8(&bytes) {
+        Ok(body) => body.to_string(),
+        Err(e) => {
+            tracing::warn!("Heartbeat response body is not valid UTF-8: {}", e);
+            return match config.body_decode_policy {
+                BodyDecodePolicy::Fail => Err(AttemptError::BodyDecode(e)),
+                BodyDecodePolicy::IgnoreBody => Ok(status.as_u16()),
+            };
+        }
+    };
+
+    if validator.validate(body).await {
+        Ok(status.as_u16())
+    } else {
+        Err(AttemptError::BodyRejected(status))
+    }
+}
+
+/// Send one heartbeat, log the result and record it in `status`.
+///
+/// `sequence` numbers the heartbeats of a loop from zero; it selects the
+/// rotated user-agent and fills `{{sequence}}` in the body template.
+///
+/// Failed attempts are retried up to [`HeartbeatConfig::max_retries`] times
+/// using the configured backoff, as long as the next retry still starts
+/// within the heartbeat interval. Only the final result is recorded.
+pub async fn send_heartbeat(
+    client: &reqwest::Client,
+    config: &HeartbeatConfig,
+    status: &Status,
+    sequence: usize,
+) -> Outcome {
+    send_with(config, status, || attempt(client, config, sequence)).await
+}
+
+/// Send a heartbeat as [`send_heartbeat`] does, making each attempt with
+/// `attempt`.
+async fn send_with<F, A>(config: &HeartbeatConfig, status: &Status, mut attempt: F) -> Outcome
+where
+    F: FnMut() -> A,
+    A: Future<Output = (Result<u16, AttemptError>, Duration)>,
+{
+    let started = Instant::now();
+    let interval = Duration::from_secs(config.interval_secs);
+    let mut backoff = Backoff::new(
+        config.backoff,
+        Duration::from_millis(config.retry_base_delay_ms),
+        interval,
+        fastrand::Rng::new(),
+    );
+    let mut retries = 0;
+
+    loop {
+        let (result, latency) = attempt().await;
+
+        let err = match result {
+            Ok(code) => {
+                tracing::debug!("Heartbeat sent successfully in {}ms", latency.as_millis());
+                status.record_success(code, latency);
+                if let Some(path) = &config.liveness_file {
+                    touch(path);
+                }
+                return Outcome {
+                    success: true,
+                    failure: None,
+                    status_code: Some(code),
+                    latency,
+                    error: None,
+                };
+            }
+            Err(err) => err,
+        };
+
+        status.record_failed_attempt();
+        if retries < config.max_retries && !status.retry_budget_exhausted(config) {
+            let delay = backoff.next_delay();
+            if started.elapsed() + delay < interval {
+                retries += 1;
+                tracing::debug!(
+                    "Heartbeat attempt failed ({}), retry {}/{} in {:?}",
+                    err,
+                    retries,
+                    config.max_retries,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        }
+
+        if status.failures_suppressed() {
+            tracing::info!("Heartbeat failed during suppression window, as expected: {}", err);
+            return Outcome {
+                success: false,
+                failure: None,
+                status_code: err.status_code(),
+                latency,
+                error: Some(err.to_error()),
+            };
+        }
+
+        err.log_final();
+        let error = err.to_error();
+        status.record_failure(err.class(), err.status_code(), latency);
+        status.record_error(&error);
+        return Outcome {
+            success: false,
+            failure: Some(err.class()),
+            status_code: err.status_code(),
+            latency,
+            error: Some(error),
+        };
+    }
+}
+
+/// Send the one-time startup ping to `config.url`, outside any loop's
+/// statistics.
+pub async fn send_startup_ping(config: HeartbeatConfig) {
+    let ping = async {
+        let Some(client) = build_client_or_log(&config) else {
+            return;
+        };
+        if send_heartbeat(&client, &config, &Status::default(), 0).await.success {
+            tracing::info!("Startup complete ping sent");
+        }
+    };
+
+    if config.silent {
+        ping.with_subscriber(NoSubscriber::default()).await;
+    } else {
+        ping.await;
+    }
+}
+
+/// Set the modification time of `path` to now, creating it if needed.
+/// Errors are logged and otherwise ignored.
+fn touch(path: &Path) {
+    let result = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to touch liveness file {}: {}", path.display(), e);
+    }
+}
+
+/// Internal heartbeat loop that runs indefinitely.
+///
+/// Sends requests to the configured URL at regular intervals, following
+/// the state and config updates requested through `control`. Never panics -
+/// all errors are logged and the loop continues, unless
+/// [`HeartbeatConfig::abort_after_failures`] is reached or a
+/// [`FailurePolicy`](crate::FailurePolicy) stops it.
+/// With [`HeartbeatConfig::silent`] set, nothing is logged at all.
+pub async fn heartbeat_loop(config: HeartbeatConfig, status: Arc<Status>, control: LoopControl) {
+    // Dropped last, including when the task is aborted.
+    let _summary = (config.log_summary && !config.silent)
+        .then(|| ShutdownSummary { status: Arc::clone(&status), started: Instant::now() });
+    let config = if config.silent {
+        run_loop(config, status, control).with_subscriber(NoSubscriber::default()).await
+    } else {
+        run_loop(config, status, control).await
+    };
+    events::emit(&config, LifecycleEvent::Stopped);
+}
+
+/// Logs the summary of a loop with [`HeartbeatConfig::log_summary`] when
+/// dropped.
+struct ShutdownSummary {
+    status: Arc<Status>,
+    started: Instant,
+}
+
+impl Drop for ShutdownSummary {
+    fn drop(&mut self) {
+        let stats = self.status.snapshot();
+        let average = self
+            .status
+            .average_latency()
+            .map_or_else(|| "n/a".to_string(), |latency| format!("{latency:?}"));
+        tracing::info!(
+            "Heartbeat summary: {} sent, {} succeeded, {} failed, uptime {:?}, avg latency {}",
+            stats.successes + stats.failures,
+            stats.successes,
+            stats.failures,
+            self.started.elapsed(),
+            average
+        );
+    }
+}
+
+/// Run the loop until it stops, returning the config it ended with.
+async fn run_loop(
+    mut config: HeartbeatConfig,
+    status: Arc<Status>,
+    mut control: LoopControl,
+) -> HeartbeatConfig {
+    let Some(mut client) = LoopClient::start(&mut control, &config) else {
+        return config;
+    };
+
+    #[cfg(feature = "serde")]
+    if let Some(path) = &config.stats_file {
+        crate::persist::load(path, &status);
+    }
+
+    let initial_success_deadline = config.initial_success_deadline;
+    let delay = Duration::from_secs(config.startup_delay_secs) + schedule::stagger(&config);
+    let (delay_until, mut schedule) = (Instant::now() + delay, Schedule::delayed(&config, delay));
+
+    let beats = async {
+        let mut sequence = 0_usize;
+        let mut active = false;
+        let mut streaks = FailureStreaks::default();
+        let mut ready = config.ready_marks_required == 0;
+        let mut applied_updates = 0;
+        let mut pending_update: Option<Instant> = None;
+        let mut rng = fastrand::Rng::new();
+        loop {
+            status.set_next_send(ready.then(|| schedule.next_deadline()));
+            tokio::select! {
+                biased;
+                requested = control_changed(&mut control.state) => {
+                    if follow_request(requested, &mut control.state, &config, &status, &mut schedule)
+                        .await
+                    {
+                        return;
+                    }
+                    continue;
+                }
+                final_heartbeat = shutdown_requested(&mut control.shutdown) => {
+                    events::emit(&config, LifecycleEvent::Stopping);
+                    if final_heartbeat {
+                        client.send(&config, &status, sequence).await;
+                    }
+                    return;
+                }
+                () = config_updated(&mut control.updates), if pending_update.is_none() => {
+                    let window = Duration::from_millis(config.config_debounce_ms);
+                    pending_update = Some(Instant::now() + window);
+                    continue;
+                }
+                () = tokio::time::sleep_until(pending_update.unwrap_or_else(Instant::now)),
+                    if pending_update.is_some() =>
+                {
+                    pending_update = None;
+                    if let Some(update) = take_update(&mut control.updates, &mut applied_updates) {
+                        if apply_update(update, &mut config, &mut client) && ready {
+                            schedule = Schedule::new(&config);
+                        }
+                    }
+                    continue;
+                }
+                () = status.wait_ready(config.ready_marks_required), if !ready => {
+                    ready = true;
+                    // Saturates at zero once the delay is over.
+                    schedule = Schedule::delayed(&config, delay_until - Instant::now());
+                    continue;
+                }
+                () = triggered(&mut control.triggers), if ready => {
+                    tracing::debug!("Heartbeat triggered manually");
+                    if config.realign_after_trigger {
+                        schedule.realign();
+                    }
+                }
+                _ = schedule.tick(), if ready => {
+                    if !prepare_scheduled(&mut schedule, &config, &status, &mut rng).await {
+                        continue;
+                    }
+                }
+            }
+            if !client.health_gate(&config, &status, sequence).await {
+                continue;
+            }
+            client.renew_if_expired(&config);
+            let outcome = client.send(&config, &status, sequence).await;
+            sequence = sequence.wrapping_add(1);
+            notify(control.observer.as_ref(), &outcome);
+
+            if outcome.success && !active {
+                active = true;
+                events::emit(&config, LifecycleEvent::FirstActive);
+            }
+
+            #[cfg(feature = "serde")]
+            if let Some(path) = &config.stats_file {
+                crate::persist::flush(path, &status.snapshot());
+            }
+
+            if let Some(reason) = stop_reason(&config, &status, &mut streaks, &outcome) {
+                status.stop(reason);
+                events::emit(&config, LifecycleEvent::Stopping);
+                return;
+            }
+        }
+    };
+
+    tokio::select! {
+        () = beats => {}
+        () = watch_initial_success(initial_success_deadline, &status) => {}
+    }
+    status.set_next_send(None);
+    config
+}
+
+/// Report a heartbeat's `outcome` to `observer`, unless it is a failure
+/// during a suppression window.
+fn notify(observer: &dyn HeartbeatObserver, outcome: &Outcome) {
+    if outcome.success {
+        if let Some(status) = outcome.status_code.and_then(|code| StatusCode::from_u16(code).ok()) {
+            observer.on_success(status, outcome.latency);
+        }
+    } else if let Some(err) = outcome.error.as_ref().filter(|_| outcome.failure.is_some()) {
+        observer.on_failure(err);
+    }
+}
+
+/// Follow a state change requested through the loop's handle, returning
+/// whether the loop should stop. A paused loop waits here and restarts its
+/// schedule when resumed.
+async fn follow_request(
+    requested: Control,
+    state: &mut watch::Receiver<Control>,
+    config: &HeartbeatConfig,
+    status: &Status,
+    schedule: &mut Schedule,
+) -> bool {
+    if requested == Control::Pause {
+        status.set_next_send(None);
+        events::emit(config, LifecycleEvent::Paused);
+        if wait_while_paused(state).await == Control::Run {
+            events::emit(config, LifecycleEvent::Resumed);
+            status.record_skips(SkipReason::Paused, schedule.missed());
+            if config.keep_schedule_on_resume {
+                schedule.skip_missed();
+            } else {
+                *schedule = Schedule::new(config);
+            }
+        }
+    }
+    let stop = *state.borrow() == Control::Stop;
+    if stop {
+        events::emit(config, LifecycleEvent::Stopping);
+    }
+    stop
+}
+
+/// Get ready to send the heartbeat of the tick that just fired: count the
+/// ticks dropped before it, log a suspected suspend, check the load or wait
+/// for the jitter. Returns whether to send it.
+async fn prepare_scheduled(
+    schedule: &mut Schedule,
+    config: &HeartbeatConfig,
+    status: &Status,
+    rng: &mut fastrand::Rng,
+) -> bool {
+    status.record_skips(SkipReason::Overlap, schedule.take_skipped());
+    if let Some(gap) = schedule.take_suspend_gap() {
+        tracing::info!(
+            "Heartbeat woke up {:?} late, the system was probably suspended{}",
+            gap,
+            if config.heartbeat_on_resume { "" } else { "; skipping to the next heartbeat" }
+        );
+        if !config.heartbeat_on_resume {
+            status.record_skips(SkipReason::Suspended, 1);
+        }
+        return config.heartbeat_on_resume;
+    }
+    if let Some(load) = load_above_limit(config) {
+        tracing::warn!("Heartbeat skipped, load average {:.2} is above the limit", load);
+        status.record_skips(SkipReason::HighLoad, 1);
+        return false;
+    }
+    schedule.sleep(jitter(rng, config.jitter_secs)).await;
+    true
+}
+
+/// The current load average if it exceeds
+/// [`HeartbeatConfig::skip_above_loadavg`].
+pub fn load_above_limit(config: &HeartbeatConfig) -> Option<f64> {
+    let limit = config.skip_above_loadavg?;
+    let load = config
+        .load_source
+        .as_ref()
+        .map_or_else(|| SystemLoad.load_average(), |source| source.load_average())?;
+    (load > limit).then_some(load)
+}
+
+/// Random delay of up to `jitter_secs` before a heartbeat, in whole
+/// milliseconds.
+fn jitter(rng: &mut fastrand::Rng, jitter_secs: u64) -> Duration {
+    if jitter_secs == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rng.u64(..=jitter_secs.saturating_mul(1_000)))
+}
+
+/// Why the loop should stop after a heartbeat with `outcome`, if it should.
+fn stop_reason(
+    config: &HeartbeatConfig,
+    status: &Status,
+    streaks: &mut FailureStreaks,
+    outcome: &Outcome,
+) -> Option<StopReason> {
+    // Failures during the startup grace period or a suppression window
+    // (which have no class) leave the streaks and retry budget untouched.
+    let counted = outcome.failure.map_or(outcome.success, |_| !status.in_grace());
+    if !counted {
+        return None;
+    }
+    if let Some(class) = streaks.record(config, outcome.failure, outcome.status_code) {
+        return Some(StopReason::FailurePolicy(class));
+    }
+
+    if let Some(budget) =
+        config.total_retry_budget.filter(|_| status.retry_budget_exhausted(config))
+    {
+        tracing::error!("Heartbeat stopped after its retry budget of {} failed attempts", budget);
+        return Some(StopReason::RetryBudgetExhausted);
+    }
+
+    let limit = config.abort_after_failures?;
+    let failures = status.snapshot().consecutive_failures;
+    if failures >= u64::from(limit) {
+        tracing::error!("Heartbeat stopped after {} consecutive failures", failures);
+        return Some(StopReason::TooManyFailures);
+    }
+    None
+}
+
+/// HTTP client of a loop and when it was built.
+struct LoopClient {
+    client: reqwest::Client,
+    built_at: Instant,
+    /// Whether the client was passed in by the caller, who then manages it:
+    /// it is never rebuilt.
+    provided: bool,
+    /// Health check sent instead of HTTP requests, if any.
+    #[cfg(feature = "grpc")]
+    grpc: Option<crate::grpc::HealthProbe>,
+    /// Transport pinging instead of the HTTP client, if any.
+    transport: Option<Arc<dyn HeartbeatTransport>>,
+    /// Check that decides whether to send each heartbeat, if any.
+    health_check: Option<HealthCheck>,
+}
+
+impl LoopClient {
+    fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            built_at: Instant::now(),
+            provided: false,
+            #[cfg(feature = "grpc")]
+            grpc: None,
+            transport: None,
+            health_check: None,
+        }
+    }
+
+    /// The client provided in `control`, or else a new one for `config`,
+    /// `None` if that or the gRPC channel cannot be built. Takes the health
+    /// check of `control` along.
+    fn start(control: &mut LoopControl, config: &HeartbeatConfig) -> Option<Self> {
+        let mut client = control.client.take().map_or_else(
+            || build_client_or_log(config).map(Self::new),
+            |client| Some(Self { provided: true, ..Self::new(client) }),
+        )?;
+        client.health_check = control.health_check.take();
+
+        #[cfg(feature = "grpc")]
+        if let Some(service) = control.grpc_service.take() {
+            let probe = crate::grpc::HealthProbe::new(config, service)
+                .inspect_err(|e| {
+                    tracing::error!("Failed to create gRPC channel for heartbeat: {}", e);
+                })
+                .ok()?;
+            return Some(Self { grpc: Some(probe), ..client });
+        }
+        Some(Self { transport: control.transport.take(), ..client })
+    }
+
+    /// Send one heartbeat with the HTTP client, the gRPC health check or the
+    /// transport.
+    async fn send(&self, config: &HeartbeatConfig, status: &Status, sequence: usize) -> Outcome {
+        #[cfg(feature = "grpc")]
+        if let Some(probe) = &self.grpc {
+            return send_with(config, status, || probe.attempt(config)).await;
+        }
+        if let Some(transport) = &self.transport {
+            return send_with(config, status, || ping(transport.as_ref(), config)).await;
+        }
+        send_heartbeat(&self.client, config, status, sequence).await
+    }
+
+    /// Run the health check, if any, and return whether to send the
+    /// heartbeat. A failed check sends the `/fail` ping instead, like any
+    /// heartbeat, if [`HeartbeatConfig::unhealthy_policy`] asks for it.
+    async fn health_gate(
+        &self,
+        config: &HeartbeatConfig,
+        status: &Status,
+        sequence: usize,
+    ) -> bool {
+        let Some(check) = &self.health_check else {
+            return true;
+        };
+        if check().await {
+            return true;
+        }
+
+        match config.unhealthy_policy {
+            UnhealthyPolicy::Skip => {
+                tracing::warn!("Health check failed, heartbeat skipped");
+                status.record_skips(SkipReason::Unhealthy, 1);
+            }
+            UnhealthyPolicy::ReportFail => {
+                tracing::warn!("Health check failed, reporting the failure");
+                let fail = HeartbeatConfig {
+                    liveness_file: None,
+                    ..request::endpoint_config(config, request::FAIL_SEGMENT)
+                };
+                self.send(&fail, status, sequence).await;
+            }
+        }
+        false
+    }
+
+    /// Rebuild the client if it is older than
+    /// [`HeartbeatConfig::client_max_lifetime`]. If that fails, the old
+    /// client is kept for another lifetime. Returns whether it was rebuilt.
+    fn renew_if_expired(&mut self, config: &HeartbeatConfig) -> bool {
+        let Some(max_lifetime) = config.client_max_lifetime.filter(|_| !self.provided) else {
+            return false;
+        };
+        let age = self.built_at.elapsed();
+        if age < max_lifetime {
+            return false;
+        }
+
+        self.built_at = Instant::now();
+        let Some(client) = build_client_or_log(config) else {
+            return false;
+        };
+        tracing::info!("Rebuilt heartbeat HTTP client after {:?}", age);
+        self.client = client;
+        true
+    }
+}
+
+/// The latest config update, logging how many earlier ones it replaced.
+/// `applied` is the count of updates seen so far and is advanced.
+fn take_update(
+    updates: &mut watch::Receiver<ConfigUpdate>,
+    applied: &mut u64,
+) -> Option<HeartbeatConfig> {
+    let update = updates.borrow_and_update();
+    if update.count - *applied > 1 {
+        tracing::debug!("Coalesced {} heartbeat config updates", update.count - *applied - 1);
+    }
+    *applied = update.count;
+    update.config.clone()
+}
+
+/// Replace `config` and `client` with `update` if it is valid and a client
+/// can be built for it; a client passed in by the caller is kept. Returns
+/// whether the update was applied.
+fn apply_update(
+    update: HeartbeatConfig,
+    config: &mut HeartbeatConfig,
+    client: &mut LoopClient,
+) -> bool {
+    let update = HeartbeatConfig { silent: config.silent, ..update }.resolve_interval();
+    if let Err(e) = update.validate() {
+        tracing::warn!("Ignoring invalid heartbeat config update: {}", e);
+        return false;
+    }
+    #[cfg(feature = "grpc")]
+    let probe = match client.grpc.as_ref().map(|probe| probe.for_config(&update)).transpose() {
+        Ok(probe) => probe,
+        Err(e) => {
+            tracing::warn!("Ignoring heartbeat config update, no gRPC channel: {}", e);
+            return false;
+        }
+    };
+    if client.provided {
+        tracing::debug!("Heartbeat config updated");
+    } else {
+        let Some(new_client) = build_client_or_log(&update) else {
+            return false;
+        };
+        tracing::debug!("Heartbeat config updated, rebuilt HTTP client");
+        *client = LoopClient::new(new_client);
+    }
+    #[cfg(feature = "grpc")]
+    {
+        client.grpc = probe;
+    }
+    *config = update;
+    true
+}
+
+/// Log a single error if no heartbeat has succeeded `deadline` after the
+/// loop started. Never completes.
+async fn watch_initial_success(deadline: Option<Duration>, status: &Status) {
+    if let Some(deadline) = deadline {
+        tokio::time::sleep(deadline).await;
+        if status.snapshot().successes == 0 {
+            tracing::error!("No heartbeat succeeded within {:?} of start", deadline);
+        }
+    }
+    std::future::pending::<()>().await;
+}
+
+/// Send `count` heartbeats on the configured schedule and summarize them.
+///
+/// Heartbeats are scheduled exactly like the background loop, including
+/// [`HeartbeatConfig::first_tick_policy`], so with the default policy the
+/// run takes `count * interval`. Failures are logged as usual and counted in
+/// the report. The config goes through the same checks as in
+/// [`spawn`](crate::spawn); if it is refused, or the HTTP client cannot be
+/// created, an error is logged and an empty report is returned.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{FirstTickPolicy, HeartbeatConfig, run_report};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = HeartbeatConfig {
+///         url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///         first_tick_policy: FirstTickPolicy::FireImmediately,
+///         ..HeartbeatConfig::default()
+///     };
+///
+///     let report = run_report(config, 3).await;
+///     println!("{} of 3 heartbeats succeeded", report.successes);
+/// }
+/// ```
+pub async fn run_report(config: HeartbeatConfig, count: u64) -> HeartbeatReport {
+    if config.silent {
+        collect_report(config, count).with_subscriber(NoSubscriber::default()).await
+    } else {
+        collect_report(config, count).await
+    }
+}
+
+async fn collect_report(config: HeartbeatConfig, count: u64) -> HeartbeatReport {
+    let mut report = HeartbeatReport::default();
+    let config = match crate::checked(config) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Heartbeat not started: {}", e);
+            return report;
+        }
+    };
+    let Some(client) = build_client_or_log(&config) else {
+        return report;
+    };
+
+    let status = Status::new(&config);
+    let mut schedule = Schedule::delayed(&config, schedule::stagger(&config));
+
+    for sequence in 0..count {
+        schedule.tick().await;
+        let sequence = usize::try_from(sequence).unwrap_or(usize::MAX);
+        let outcome = send_heartbeat(&client, &config, &status, sequence).await;
+
+        if outcome.success {
+            report.successes += 1;
+        } else {
+            report.failures += 1;
+        }
+        report.total_latency += outcome.latency;
+        report.statuses.push(outcome.status_code);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::test_support::{CapturedEvent, capture_logs};
+    use crate::test_util::{ManualClock, MockResponse, MockServer};
+    use crate::{
+        BackoffStrategy, BodyValidator, FailureAction, FailureHook, FailurePolicy, FirstTickPolicy,
+        OverlapPolicy,
+    };
+    use tracing::Level;
+
+    #[test]
+    fn test_build_client_with_pool_max_idle_per_host() {
+        let config = HeartbeatConfig {
+            url: "https://example.com/heartbeat".to_string(),
+            pool_max_idle_per_host: Some(1),
+            ..HeartbeatConfig::default()
+        };
+
+        assert!(build_client(&config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ip_family_restricts_connections() {
+        assert_eq!(local_address(IpFamily::V4Only), Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+        assert_eq!(local_address(IpFamily::V6Only), Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)));
+        assert_eq!(local_address(IpFamily::Auto), None);
+
+        // The mock server only listens on 127.0.0.1.
+        let server = MockServer::start().await;
+        let config = |ip_family| HeartbeatConfig {
+            url: server.url(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ip_family: Some(ip_family),
+            ..HeartbeatConfig::default()
+        };
+
+        assert!(build_client(&config(IpFamily::V6Only)).is_ok());
+        assert_eq!(run_report(config(IpFamily::V4Only), 1).await.successes, 1);
+        assert_eq!(run_report(config(IpFamily::Auto), 1).await.successes, 1);
+        let report = run_report(config(IpFamily::V6Only), 1).await;
+        assert_eq!((report.failures, report.statuses), (1, vec![None]));
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_is_rebuilt_after_max_lifetime() {
+        let config = HeartbeatConfig {
+            client_max_lifetime: Some(Duration::from_secs(3600)),
+            ..HeartbeatConfig::default()
+        };
+        let (logs, _guard) = capture_logs();
+        let mut client = LoopClient::new(build_client(&config).unwrap());
+
+        tokio::time::advance(Duration::from_secs(3599)).await;
+        assert!(!client.renew_if_expired(&config));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(client.renew_if_expired(&config));
+        assert!(!client.renew_if_expired(&config), "lifetime restarts after a rebuild");
+
+        let never = HeartbeatConfig::default();
+        tokio::time::advance(Duration::from_secs(86_400)).await;
+        assert!(!client.renew_if_expired(&never));
+
+        let rebuilds = logs
+            .events()
+            .iter()
+            .filter(|e| e.text.contains("Rebuilt heartbeat HTTP client"))
+            .count();
+        assert_eq!(rebuilds, 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_version_preference() {
+        let server = MockServer::start().await;
+        let config = |http_version| HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            http_version: Some(http_version),
+            ..HeartbeatConfig::default()
+        };
+
+        assert!(build_client(&config(HttpVersion::Http1)).is_ok());
+        assert_eq!(run_report(config(HttpVersion::Http1), 1).await.successes, 1);
+        // The mock server only speaks HTTP/1.1.
+        #[cfg(feature = "http2")]
+        assert_eq!(run_report(config(HttpVersion::Http2), 1).await.failures, 1);
+        #[cfg(not(feature = "http2"))]
+        assert_eq!(build_client(&config(HttpVersion::Http2)).unwrap_err(), HTTP2_DISABLED);
+    }
+
+    #[tokio::test]
+    async fn test_run_report_summarizes_mixed_results() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200));
+        server.push_response(MockResponse::status(503));
+        server.push_response(MockResponse::status(204));
+
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = run_report(config, 3).await;
+
+        assert_eq!(report.successes, 2);
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.statuses, vec![Some(200), Some(503), Some(204)]);
+        assert!(report.total_latency > Duration::ZERO);
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_report_counts_request_errors() {
+        let server = MockServer::start().await;
+        let url = server.url();
+        drop(server);
+
+        let config = HeartbeatConfig {
+            url,
+            interval_secs: 1,
+            timeout_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = run_report(config, 1).await;
+
+        assert_eq!(report.successes, 0);
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.statuses, vec![None]);
+    }
+
+    async fn failing_run(silent: bool) -> Vec<CapturedEvent> {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(500));
+
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            silent,
+            ..HeartbeatConfig::default()
+        };
+
+        let (logs, _guard) = capture_logs();
+        let (task, handle) = crate::spawn_observed(config);
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        while handle.stats().failures == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        task.abort();
+
+        logs.events()
+    }
+
+    #[tokio::test]
+    async fn test_silent_loop_emits_no_events() {
+        let control = failing_run(false).await;
+        assert!(
+            control.iter().any(|e| e.level == Level::WARN && e.text.contains("unexpected status")),
+            "control run should log the failure: {control:?}"
+        );
+
+        let silent = failing_run(true).await;
+        assert!(silent.is_empty(), "silent run logged: {silent:?}");
+    }
+
+    #[tokio::test]
+    async fn test_failed_heartbeat_is_retried_within_interval() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(503));
+        server.push_response(MockResponse::status(502));
+
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            max_retries: 3,
+            retry_base_delay_ms: 10,
+            backoff: BackoffStrategy::Fixed,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = run_report(config, 1).await;
+
+        assert_eq!(report.successes, 1);
+        assert_eq!(report.statuses, vec![Some(200)]);
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_only_exhausted_retries_log_the_failure() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let (logs, _guard) = capture_logs();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            max_retries: 2,
+            retry_base_delay_ms: 10,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = run_report(config, 1).await;
+
+        assert_eq!((report.successes, report.failures), (0, 1));
+        assert_eq!(server.request_count(), 3);
+        let warnings: Vec<_> =
+            logs.events().into_iter().filter(|e| e.level == Level::WARN).collect();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].text.contains("unexpected status"));
+    }
+
+    #[tokio::test]
+    async fn test_client_header_carries_app_name() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+        let expected = request::app_name(&config);
+
+        run_report(config.clone(), 1).await;
+        run_report(HeartbeatConfig { app_name: Some("billing-api".to_string()), ..config }, 1)
+            .await;
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("x-heartbeat-client"), Some(expected.as_str()));
+        assert_eq!(requests[1].header("x-heartbeat-client"), Some("billing-api"));
+    }
+
+    #[tokio::test]
+    async fn test_loop_exits_after_consecutive_failures() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let (logs, _guard) = capture_logs();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            abort_after_failures: Some(2),
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::default());
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("loop should stop by itself");
+
+        assert_eq!(server.request_count(), 2);
+        assert_eq!(status.stop_reason(), Some(StopReason::TooManyFailures));
+        assert!(
+            logs.events().iter().any(|e| e.level == Level::ERROR
+                && e.text.contains("stopped after 2 consecutive failures"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loop_exits_when_retry_budget_is_exhausted() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            max_retries: 5,
+            retry_base_delay_ms: 10,
+            backoff: BackoffStrategy::Fixed,
+            total_retry_budget: Some(3),
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::default());
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("loop should stop by itself");
+
+        // The budget cut the first heartbeat's retries short.
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(status.stop_reason(), Some(StopReason::RetryBudgetExhausted));
+    }
+
+    #[tokio::test]
+    async fn test_suppressed_failures_leave_retry_budget_untouched() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            total_retry_budget: Some(1),
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::default());
+        status.suppress_failures_until(Instant::now() + Duration::from_millis(1_500));
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("loop should stop by itself");
+
+        // The two heartbeats inside the window did not use up the budget.
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(status.stop_reason(), Some(StopReason::RetryBudgetExhausted));
+    }
+
+    /// Offsets of three heartbeats from the first when the first response
+    /// takes 2.5 intervals.
+    async fn overlapping_run(overlap_policy: OverlapPolicy) -> Vec<Duration> {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200).delay(Duration::from_millis(2_500)));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            overlap_policy,
+            ..HeartbeatConfig::default()
+        };
+
+        let report = run_report(config, 3).await;
+        assert_eq!(report.successes, 3);
+        let requests = server.requests();
+        requests.iter().map(|r| r.received_at - requests[0].received_at).collect()
+    }
+
+    fn assert_near(actual: Duration, expected_ms: u64) {
+        let expected = Duration::from_millis(expected_ms);
+        assert!(actual.abs_diff(expected) < Duration::from_millis(250), "{actual:?}");
+    }
+
+    #[tokio::test]
+    async fn test_overlap_skip_drops_missed_ticks() {
+        let offsets = overlapping_run(OverlapPolicy::Skip).await;
+
+        // Ticks at 1s and 2s passed during the slow send; the next is at 3s.
+        assert_near(offsets[1], 3_000);
+        assert_near(offsets[2], 4_000);
+    }
+
+    #[tokio::test]
+    async fn test_overlap_queue_one_sends_a_single_catch_up() {
+        let offsets = overlapping_run(OverlapPolicy::QueueOne).await;
+
+        // One catch-up send for the two missed ticks, then back on schedule.
+        assert_near(offsets[1], 2_500);
+        assert_near(offsets[2], 3_000);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bound() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        assert_eq!(jitter(&mut rng, 0), Duration::ZERO);
+
+        let delays: Vec<Duration> = (0..100).map(|_| jitter(&mut rng, 2)).collect();
+        assert!(delays.iter().all(|delay| *delay <= Duration::from_secs(2)));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[tokio::test]
+    async fn test_jitter_delays_heartbeat() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            jitter_secs: 1,
+            ..HeartbeatConfig::default()
+        };
+        let started = Instant::now();
+        let task = tokio::spawn(heartbeat_loop(
+            config,
+            Arc::new(Status::default()),
+            LoopControl::detached(),
+        ));
+
+        let requests = server.wait_for_requests(1, Duration::from_secs(5)).await;
+        task.abort();
+        assert!(requests[0].received_at - started <= Duration::from_millis(1_250));
+    }
+
+    #[tokio::test]
+    async fn test_jitter_waits_on_the_configured_clock() {
+        let server = MockServer::start().await;
+        let clock = ManualClock::new();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 7_200,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            jitter_secs: 3_600,
+            clock: Some(Arc::new(clock.clone())),
+            ..HeartbeatConfig::default()
+        };
+        let drive = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            // Without advancing the clock the jitter has not elapsed.
+            assert_eq!(server.request_count(), 0);
+            clock.advance(Duration::from_secs(3_600));
+            server.wait_for_requests(1, Duration::from_secs(5)).await;
+        };
+
+        tokio::select! {
+            () = heartbeat_loop(config, Arc::new(Status::default()), LoopControl::detached()) => {
+                panic!("loop stopped");
+            }
+            () = drive => {}
+        }
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_suspend_gap_is_logged_with_optional_resume_heartbeat() {
+        for heartbeat_on_resume in [true, false] {
+            let server = MockServer::start().await;
+            let clock = ManualClock::new();
+            let (logs, _guard) = capture_logs();
+            let config = HeartbeatConfig {
+                url: server.url(),
+                interval_secs: 10,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                clock: Some(Arc::new(clock.clone())),
+                heartbeat_on_resume,
+                ..HeartbeatConfig::default()
+            };
+            let suspend = async {
+                server.wait_for_requests(1, Duration::from_secs(5)).await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                // Wake up 90s after the next heartbeat was due.
+                clock.advance(Duration::from_secs(100));
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            };
+
+            tokio::select! {
+                () = heartbeat_loop(config, Arc::new(Status::default()), LoopControl::detached()) => {
+                    panic!("loop stopped");
+                }
+                () = suspend => {}
+            }
+
+            assert_eq!(server.request_count(), if heartbeat_on_resume { 2 } else { 1 });
+            assert!(
+                logs.events().iter().any(|e| e.level == Level::INFO
+                    && e.text.contains("woke up 90s late, the system was probably suspended")),
+                "heartbeat_on_resume={heartbeat_on_resume}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summary_is_logged_on_exit() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200));
+        server.push_response(MockResponse::status(200));
+        server.set_default_response(MockResponse::status(503));
+        let (logs, _guard) = capture_logs();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            abort_after_failures: Some(2),
+            log_summary: true,
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::default());
+
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("loop should stop by itself");
+
+        let summaries: Vec<_> = logs
+            .events()
+            .into_iter()
+            .filter(|e| e.level == Level::INFO && e.text.starts_with("Heartbeat summary"))
+            .collect();
+        assert_eq!(summaries.len(), 1);
+        assert!(
+            summaries[0].text.contains("4 sent, 2 succeeded, 2 failed, uptime "),
+            "{}",
+            summaries[0].text
+        );
+        assert!(!summaries[0].text.contains("avg latency n/a"));
+    }
+
+    #[tokio::test]
+    async fn test_failure_policies_fire_per_class() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(404));
+        server.push_response(MockResponse::status(404));
+        server.push_response(MockResponse::default().delay(Duration::from_secs(2)));
+        server.push_response(MockResponse::default().delay(Duration::from_secs(2)));
+        let fired = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hook = {
+            let fired = Arc::clone(&fired);
+            FailureHook::new(move |class, streak| fired.lock().unwrap().push((class, streak)))
+        };
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            http_failure_policy: Some(FailurePolicy::new(2, FailureAction::Call(hook))),
+            transport_failure_policy: Some(FailurePolicy::new(2, FailureAction::Stop)),
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::default());
+
+        tokio::time::timeout(
+            Duration::from_secs(8),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("transport policy should stop the loop");
+
+        assert_eq!(server.request_count(), 4);
+        assert_eq!(*fired.lock().unwrap(), vec![(FailureClass::Http, 2)]);
+        assert_eq!(status.stop_reason(), Some(StopReason::FailurePolicy(FailureClass::Transport)));
+    }
+
+    #[tokio::test]
+    async fn test_failures_during_startup_grace_do_not_count_towards_abort() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            abort_after_failures: Some(2),
+            startup_grace_secs: 2,
+            ..HeartbeatConfig::default()
+        };
+        let status = Arc::new(Status::new(&config));
+
+        tokio::time::timeout(
+            Duration::from_secs(6),
+            heartbeat_loop(config, Arc::clone(&status), LoopControl::detached()),
+        )
+        .await
+        .expect("loop should stop after the grace period");
+
+        // Two failures inside the grace window, then two more to reach the limit
+        assert_eq!(server.request_count(), 4);
+        let snapshot = status.snapshot();
+        assert_eq!((snapshot.failures, snapshot.consecutive_failures), (4, 2));
+        assert_eq!(status.stop_reason(), Some(StopReason::TooManyFailures));
+    }
+
+    #[tokio::test]
+    async fn test_initial_success_deadline_logs_once() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(503));
+        let (logs, _guard) = capture_logs();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            initial_success_deadline: Some(Duration::from_millis(1_500)),
+            ..HeartbeatConfig::default()
+        };
+        let deadline_errors = || {
+            logs.events()
+                .iter()
+                .filter(|e| e.level == Level::ERROR && e.text.contains("No heartbeat succeeded"))
+                .count()
+        };
+
+        let run = tokio::time::timeout(
+            Duration::from_millis(3_500),
+            heartbeat_loop(config, Arc::new(Status::default()), LoopControl::detached()),
+        );
+        let before_deadline = async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            deadline_errors()
+        };
+        let (_, before_deadline) = tokio::join!(run, before_deadline);
+
+        assert_eq!(before_deadline, 0);
+        assert_eq!(deadline_errors(), 1);
+        assert!(server.request_count() >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_user_agents_rotate_per_heartbeat() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            user_agent: Some("ignored".to_string()),
+            user_agents: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            ..HeartbeatConfig::default()
+        };
+
+        run_report(config, 4).await;
+
+        let agents: Vec<_> =
+            server.requests().iter().map(|r| r.header("user-agent").map(str::to_string)).collect();
+        let expected = ["a", "b", "c", "a"].map(|ua| Some(ua.to_string()));
+        assert_eq!(agents, expected);
+    }
+
+    #[tokio::test]
+    async fn test_success_touches_liveness_file() {
+        let server = MockServer::start().await;
+        let path = std::env::temp_dir()
+            .join(format!("betteruptime-heartbeat-{}-liveness", std::process::id()));
+        let stale = SystemTime::now() - Duration::from_secs(3_600);
+        std::fs::File::create(&path).unwrap().set_modified(stale).unwrap();
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            liveness_file: Some(path.clone()),
+            ..HeartbeatConfig::default()
+        };
+
+        server.push_response(MockResponse::status(503));
+        run_report(config.clone(), 1).await;
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), stale);
+
+        run_report(config, 1).await;
+        assert!(std::fs::metadata(&path).unwrap().modified().unwrap() > stale);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_accept_header_is_sent() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            accept: Some("application/json".to_string()),
+            user_agent: Some("probe".to_string()),
+            ..HeartbeatConfig::default()
+        };
+
+        run_report(config, 1).await;
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("accept"), Some("application/json"));
+        assert_eq!(request.header("user-agent"), Some("probe"));
+        assert!(request.header("x-heartbeat-client").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_are_sent() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            headers: vec![
+                ("Authorization".to_string(), "Bearer secret".to_string()),
+                ("X-Tenant-Id".to_string(), "acme".to_string()),
+            ],
+            ..HeartbeatConfig::default()
+        };
+
+        run_report(config, 1).await;
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+        assert_eq!(request.header("x-tenant-id"), Some("acme"));
+    }
+
+    fn validated_config(server: &MockServer, policy: BodyDecodePolicy) -> HeartbeatConfig {
+        HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            body_validator: Some(BodyValidator::new(|body| async move { body == "ok" })),
+            body_decode_policy: policy,
+            ..HeartbeatConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_body_validator_classifies_body() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200).body("ok"));
+        server.push_response(MockResponse::status(200).body("degraded"));
+
+        let report = run_report(validated_config(&server, BodyDecodePolicy::Fail), 2).await;
+
+        assert_eq!(report.successes, 1);
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.statuses, vec![Some(200), Some(200)]);
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8_body_follows_decode_policy() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(200).body(vec![0xff, 0xfe, 0x6f]));
+        let (logs, _guard) = capture_logs();
+
+        let report = run_report(validated_config(&server, BodyDecodePolicy::Fail), 1).await;
+        assert_eq!((report.successes, report.failures), (0, 1));
+
+        let report = run_report(validated_config(&server, BodyDecodePolicy::IgnoreBody), 1).await;
+        assert_eq!((report.successes, report.failures), (1, 0));
+
+        let decode_logs =
+            logs.events().into_iter().filter(|e| e.text.contains("not valid UTF-8")).count();
+        assert_eq!(decode_logs, 2);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_skipped_while_load_is_high() {
+        #[derive(Debug)]
+        struct FixedLoad(std::sync::Mutex<f64>);
+
+        impl LoadSource for FixedLoad {
+            fn load_average(&self) -> Option<f64> {
+                Some(*self.0.lock().unwrap())
+            }
+        }
+
+        let server = MockServer::start().await;
+        let load = Arc::new(FixedLoad(std::sync::Mutex::new(8.5)));
+        let (logs, _guard) = capture_logs();
+        let heartbeat = crate::spawn_with_handle(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            skip_above_loadavg: Some(4.0),
+            load_source: Some(Arc::clone(&load) as Arc<dyn LoadSource>),
+            ..HeartbeatConfig::default()
+        });
+
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        assert_eq!(server.request_count(), 0, "sent under high load");
+        *load.0.lock().unwrap() = 0.5;
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        heartbeat.close().await;
+
+        let skips = logs.events().into_iter().filter(|e| e.text.starts_with("Heartbeat skipped"));
+        assert_eq!(
+            skips.map(|e| e.text).collect::<Vec<_>>(),
+            ["Heartbeat skipped, load average 8.50 is above the limit"; 2]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_skipped_ticks_counted_by_reason() {
+        #[derive(Debug)]
+        struct Overloaded;
+
+        impl LoadSource for Overloaded {
+            fn load_average(&self) -> Option<f64> {
+                Some(8.5)
+            }
+        }
+
+        let heartbeat = crate::spawn_with_handle(HeartbeatConfig {
+            url: "http://127.0.0.1:1/heartbeat".to_string(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            skip_above_loadavg: Some(4.0),
+            load_source: Some(Arc::new(Overloaded)),
+            ..HeartbeatConfig::default()
+        });
+
+        // Ticks at 0s and 1s are skipped for the load, those at 2s, 3s and
+        // 4s while paused, and the restarted schedule's first at 4.5s again
+        // for the load.
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        heartbeat.pause();
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        heartbeat.resume();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            heartbeat.skip_counts(),
+            HashMap::from([(SkipReason::HighLoad, 3), (SkipReason::Paused, 3)])
+        );
+        heartbeat.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_expected_etag_classifies_response() {
+        let server = MockServer::start().await;
+        server.push_response(MockResponse::status(200).header("etag", "\"v1\""));
+        server.push_response(MockResponse::status(200).header("etag", "\"v2\""));
+        server.push_response(MockResponse::status(200));
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            expected_etag: Some("\"v1\"".to_string()),
+            ..HeartbeatConfig::default()
+        };
+        let (logs, _guard) = capture_logs();
+
+        let report = run_report(config, 3).await;
+
+        assert_eq!((report.successes, report.failures), (1, 2));
+        let changed: Vec<String> = logs
+            .events()
+            .into_iter()
+            .filter(|e| e.text.starts_with("Heartbeat response ETag changed"))
+            .map(|e| e.text)
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                "Heartbeat response ETag changed: got \"v2\"",
+                "Heartbeat response ETag changed: got none"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_validator_is_bounded_by_timeout() {
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 10,
+            timeout_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            body_validator: Some(BodyValidator::new(|_| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                true
+            })),
+            ..HeartbeatConfig::default()
+        };
+        let (logs, _guard) = capture_logs();
+
+        let started = Instant::now();
+        let report = run_report(config, 1).await;
+        let elapsed = started.elapsed();
+
+        assert_eq!(report.failures, 1);
+        assert!(elapsed < Duration::from_millis(1_500), "attempt took {elapsed:?}");
+        assert!(logs.events().iter().any(|e| e.text.contains("attempt deadline exceeded")));
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn test_traceparent_follows_active_span() {
+        use opentelemetry::trace::{TraceContextExt, TracerProvider};
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let server = MockServer::start().await;
+        let config = HeartbeatConfig {
+            url: server.url(),
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            ..HeartbeatConfig::default()
+        };
+
+        run_report(config.clone(), 1).await;
+        let span = tracing::info_span!("deploy");
+        let trace_id = span.context().span().span_context().trace_id();
+        run_report(config, 1).instrument(span).await;
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("traceparent"), None);
+        let traceparent = requests[1].header("traceparent").expect("traceparent header");
+        assert!(traceparent.starts_with(&format!("00-{trace_id}-")), "{traceparent}");
+        assert_eq!(traceparent.len(), 55, "{traceparent}");
+    }
+}
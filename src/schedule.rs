@@ -0,0 +1,412 @@
+//! Heartbeat scheduling.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::time::Instant;
+
+use crate::clock::{Clock, TokioClock};
+use crate::{FirstTickPolicy, HeartbeatConfig, OverlapPolicy};
+
+/// Produces the instants at which heartbeats are sent, timed by the
+/// config's [`Clock`].
+#[derive(Debug)]
+pub struct Schedule {
+    clock: Arc<dyn Clock>,
+    period: Duration,
+    kind: Kind,
+    /// How much longer than expected the wait for the last tick took, if
+    /// it took more than a period longer: the system was likely suspended.
+    suspend_gap: Option<Duration>,
+    /// Whether `clock` is the real time, so the wall clock can show a
+    /// suspend it missed.
+    real_time: bool,
+    /// Ticks dropped because a slow heartbeat ran past them, not yet taken.
+    skipped: u64,
+}
+
+#[derive(Debug)]
+enum Kind {
+    /// Each deadline is one period after the previous one; ticks missed
+    /// while a heartbeat was in flight are handled per `overlap`.
+    Chained {
+        /// Deadline of the next tick.
+        next: Instant,
+        overlap: OverlapPolicy,
+        /// Whether a tick has fired, so a missed deadline means overlap
+        /// rather than a late start.
+        ticked: bool,
+    },
+    /// Deadlines recomputed from a fixed reference start on every tick.
+    Anchored {
+        /// Reference instant that all deadlines are derived from.
+        start: Instant,
+        /// Index of the next tick; its deadline is `start + next * period`.
+        next: u32,
+    },
+}
+
+impl Schedule {
+    /// Create the schedule for `config`, starting now.
+    ///
+    /// With [`FirstTickPolicy::Skip`] the first tick is one period after the
+    /// start, with [`FirstTickPolicy::FireImmediately`] it is at the start.
+    pub fn new(config: &HeartbeatConfig) -> Self {
+        Self::delayed(config, Duration::ZERO)
+    }
+
+    /// Create the schedule for `config`, starting `delay` from now.
+    pub fn delayed(config: &HeartbeatConfig, delay: Duration) -> Self {
+        let real_time = config.clock.is_none();
+        let clock = config.clock.clone().unwrap_or_else(|| Arc::new(TokioClock));
+        let period = Duration::from_secs(config.interval_secs);
+        let first = u32::from(config.first_tick_policy == FirstTickPolicy::Skip);
+        let now = clock.now() + delay;
+
+        let kind = if config.drift_compensation {
+            Kind::Anchored { start: now, next: first }
+        } else {
+            Kind::Chained {
+                next: now + period * first,
+                overlap: config.overlap_policy,
+                ticked: false,
+            }
+        };
+        Self { clock, period, kind, suspend_gap: None, real_time, skipped: 0 }
+    }
+
+    /// Scheduled instant of the next tick. May be in the past if the loop is
+    /// behind; an anchored schedule then skips to a later slot.
+    pub fn next_deadline(&self) -> Instant {
+        match &self.kind {
+            Kind::Chained { next, .. } => *next,
+            Kind::Anchored { start, next } => *start + self.period.saturating_mul(*next),
+        }
+    }
+
+    /// Number of ticks whose time has passed without firing, e.g. while the
+    /// loop was paused.
+    pub fn missed(&self) -> u64 {
+        let (next, now) = (self.next_deadline(), self.clock.now());
+        if next > now { 0 } else { slots_between(next, now, self.period) + 1 }
+    }
+
+    /// Restart the schedule so the next tick is one period from now.
+    pub fn realign(&mut self) {
+        let now = self.clock.now();
+        match &mut self.kind {
+            Kind::Chained { next, ticked, .. } => {
+                *next = now + self.period;
+                *ticked = true;
+            }
+            Kind::Anchored { start, next } => {
+                *start = now;
+                *next = 1;
+            }
+        }
+    }
+
+    /// Drop the ticks whose time has passed, e.g. while the loop was paused,
+    /// so the next tick is the next slot of the original schedule.
+    pub fn skip_missed(&mut self) {
+        let now = self.clock.now();
+        // An anchored schedule skips passed slots on its next tick anyway.
+        if let Kind::Chained { next, .. } = &mut self.kind
+            && *next < now
+        {
+            *next = first_slot_after(*next, self.period, now);
+        }
+    }
+
+    /// Wait for the next tick and return its scheduled instant.
+    ///
+    /// Cancel safe: if the returned future is dropped before it completes,
+    /// the tick is not consumed.
+    pub async fn tick(&mut self) -> Instant {
+        let now = self.clock.now();
+        match &mut self.kind {
+            Kind::Anchored { start, next } => {
+                // Skip slots that have already passed instead of bursting to catch up
+                let deadline = *start + self.period.saturating_mul(*next);
+                if deadline < now && !self.period.is_zero() {
+                    let behind = now.duration_since(*start).as_nanos() / self.period.as_nanos();
+                    let first = u32::try_from(behind + 1).unwrap_or(u32::MAX);
+                    self.skipped += u64::from(first.saturating_sub(*next));
+                    *next = first;
+                }
+            }
+            Kind::Chained { next, overlap, ticked: true } if *next < now => {
+                // At most one missed tick is kept: the one at `next`.
+                if *overlap == OverlapPolicy::Skip {
+                    tracing::debug!("Skipping heartbeat ticks missed by a slow heartbeat");
+                    let first = first_slot_after(*next, self.period, now);
+                    self.skipped += slots_between(*next, first, self.period);
+                    *next = first;
+                }
+            }
+            Kind::Chained { .. } => {}
+        }
+
+        let deadline = self.next_deadline();
+        let wall_start = SystemTime::now();
+        self.clock.sleep_until(deadline).await;
+        self.suspend_gap = self.suspend_gap(now, wall_start, deadline);
+
+        match &mut self.kind {
+            Kind::Chained { next, ticked, .. } => {
+                // Slots missed while suspended are not caught up.
+                let now = if self.suspend_gap.is_some() { self.clock.now() } else { now };
+                *ticked = true;
+                *next = first_slot_after(deadline, self.period, now.max(deadline));
+                if self.suspend_gap.is_none() {
+                    self.skipped += slots_between(deadline, *next, self.period).saturating_sub(1);
+                }
+            }
+            Kind::Anchored { next, .. } => *next = next.saturating_add(1),
+        }
+        deadline
+    }
+
+    /// Wait for `delay` on the schedule's clock, e.g. for the jitter.
+    pub async fn sleep(&self, delay: Duration) {
+        if !delay.is_zero() {
+            self.clock.sleep_until(self.clock.now() + delay).await;
+        }
+    }
+
+    /// Take the number of ticks dropped for overlapping a slow heartbeat
+    /// since the last call.
+    pub fn take_skipped(&mut self) -> u64 {
+        std::mem::take(&mut self.skipped)
+    }
+
+    /// Take the gap detected by the last tick, see [`suspend_gap`](Self::suspend_gap).
+    pub const fn take_suspend_gap(&mut self) -> Option<Duration> {
+        self.suspend_gap.take()
+    }
+
+    /// How much longer than expected a wait from `started` (and `wall_start`
+    /// on the wall clock) until `deadline` took, if more than a period.
+    ///
+    /// Suspend shows up on the schedule's clock on some platforms and only
+    /// on the wall clock on others (where monotonic time stops while
+    /// suspended), so the longer of both elapsed times counts. The wall
+    /// clock is only consulted on real time, as a custom [`Clock`] does not
+    /// follow it. It cannot tell a suspend from the system clock being set
+    /// forward, e.g. by NTP or by hand, so such a step during the wait is
+    /// reported as a suspend too; a step backwards is not. Ticks that were
+    /// already due are a slow heartbeat catching up, not a suspend.
+    fn suspend_gap(
+        &self,
+        started: Instant,
+        wall_start: SystemTime,
+        deadline: Instant,
+    ) -> Option<Duration> {
+        let expected = deadline.checked_duration_since(started).filter(|d| !d.is_zero())?;
+        let waited = self.clock.now().saturating_duration_since(started);
+        let wall = wall_start.elapsed().ok().filter(|_| self.real_time).unwrap_or_default();
+        let gap = waited.max(wall).saturating_sub(expected);
+        (!self.period.is_zero() && gap > self.period).then_some(gap)
+    }
+}
+
+/// The first instant `slot + n * period` (`n >= 1`) after `now`.
+fn first_slot_after(slot: Instant, period: Duration, now: Instant) -> Instant {
+    if period.is_zero() {
+        return slot;
+    }
+    let behind = now.saturating_duration_since(slot).as_nanos() / period.as_nanos();
+    let steps = u32::try_from(behind + 1).unwrap_or(u32::MAX);
+    slot + period.saturating_mul(steps)
+}
+
+/// Number of whole periods from `from` to `to`.
+fn slots_between(from: Instant, to: Instant, period: Duration) -> u64 {
+    if period.is_zero() {
+        return 0;
+    }
+    let slots = to.saturating_duration_since(from).as_nanos() / period.as_nanos();
+    u64::try_from(slots).unwrap_or(u64::MAX)
+}
+
+/// Delay of this host's first tick with
+/// [`HeartbeatConfig::stagger_by_hostname`], zero without it. Only added
+/// to the schedule a loop starts with, not when it restarts the schedule
+/// later, e.g. on resume.
+pub fn stagger(config: &HeartbeatConfig) -> Duration {
+    if !config.stagger_by_hostname {
+        return Duration::ZERO;
+    }
+    let period = Duration::from_secs(config.interval_secs);
+    hostname().map_or(Duration::ZERO, |host| stagger_offset(&host, period))
+}
+
+/// Name of this host: the `HOSTNAME` environment variable, else the
+/// contents of `/etc/hostname`.
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Stable offset of `host` within `period`, from a 64-bit FNV-1a hash of
+/// its name so it is the same on every run and Rust version.
+fn stagger_offset(host: &str, period: Duration) -> Duration {
+    let hash = host.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let period_ms = u64::try_from(period.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(hash.checked_rem(period_ms).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ManualClock;
+
+    fn config(drift_compensation: bool) -> HeartbeatConfig {
+        HeartbeatConfig { interval_secs: 60, drift_compensation, ..HeartbeatConfig::default() }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_anchored_schedule_stays_aligned_over_many_ticks() {
+        let start = Instant::now();
+        let period = Duration::from_secs(60);
+        let mut schedule = Schedule::new(&config(true));
+
+        // Three days of ticks, each followed by a slow send
+        for n in 1..=4_320_u32 {
+            schedule.tick().await;
+            let fired_at = Instant::now();
+
+            let expected = start + period * n;
+            assert!(
+                fired_at.duration_since(expected) <= Duration::from_millis(1),
+                "tick {n} fired at {:?}, expected {:?}",
+                fired_at - start,
+                expected - start
+            );
+
+            tokio::time::sleep(Duration::from_millis(1_300)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_anchored_schedule_skips_missed_slots() {
+        let start = Instant::now();
+        let mut schedule = Schedule::new(&config(true));
+
+        schedule.tick().await;
+        // Stall for 2.5 periods; the next tick is the next aligned slot
+        tokio::time::sleep(Duration::from_secs(150)).await;
+        let deadline = schedule.tick().await;
+
+        assert_eq!(deadline - start, Duration::from_secs(240));
+        assert_eq!(schedule.take_skipped(), 2, "slots at 120s and 180s");
+        assert_eq!(schedule.take_skipped(), 0);
+    }
+
+    /// Whether the next tick is still pending after a short real-time wait.
+    async fn pending(schedule: &mut Schedule) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), schedule.tick()).await.is_err()
+    }
+
+    #[tokio::test]
+    async fn test_manual_clock_drives_exact_ticks() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let with_interval = |interval_secs| HeartbeatConfig {
+            interval_secs,
+            clock: Some(Arc::new(clock.clone())),
+            ..HeartbeatConfig::default()
+        };
+        let mut schedule = Schedule::new(&with_interval(60));
+
+        clock.advance(Duration::from_secs(59));
+        assert!(pending(&mut schedule).await);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(schedule.tick().await - start, Duration::from_secs(60));
+
+        // A slow send does not shift the chained schedule
+        clock.advance(Duration::from_secs(75));
+        assert_eq!(schedule.tick().await - start, Duration::from_secs(120));
+        assert!(pending(&mut schedule).await);
+
+        // The interval adapts: the loop restarts the schedule with the new one
+        let adapted = clock.now();
+        let mut schedule = Schedule::new(&with_interval(20));
+        assert_eq!(schedule.next_deadline() - adapted, Duration::from_secs(20));
+        for n in 1..=3 {
+            clock.advance(Duration::from_secs(20));
+            assert_eq!(schedule.tick().await - adapted, Duration::from_secs(20 * n));
+        }
+        assert!(pending(&mut schedule).await);
+    }
+
+    #[test]
+    fn test_stagger_offsets_are_stable_per_host() {
+        let period = Duration::from_secs(60);
+        let first = stagger_offset("api-1.eu-west", period);
+        let second = stagger_offset("api-2.eu-west", period);
+
+        assert_eq!(first, stagger_offset("api-1.eu-west", period));
+        assert_eq!(second, stagger_offset("api-2.eu-west", period));
+        assert_ne!(first, second);
+        assert!(first < period && second < period, "{first:?} {second:?}");
+        assert_eq!(stagger_offset("api-1.eu-west", Duration::ZERO), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_staggered_schedule_starts_at_host_offset() {
+        let Some(host) = hostname() else {
+            return;
+        };
+        let start = Instant::now();
+        let config = HeartbeatConfig {
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            stagger_by_hostname: true,
+            ..config(false)
+        };
+        let offset = stagger_offset(&host, Duration::from_secs(60));
+        assert_eq!(stagger(&config), offset);
+        assert_eq!(Schedule::delayed(&config, stagger(&config)).next_deadline() - start, offset);
+
+        // A restarted schedule does not add the offset again.
+        assert_eq!(Schedule::new(&config).next_deadline(), start);
+    }
+
+    #[tokio::test]
+    async fn test_custom_clock_ignores_the_wall_clock() {
+        let clock = ManualClock::new();
+        let mut schedule = Schedule::new(&HeartbeatConfig {
+            interval_secs: 1,
+            clock: Some(Arc::new(clock.clone())),
+            ..HeartbeatConfig::default()
+        });
+        // Real time runs past the period before the clock reaches the tick,
+        // as between the steps of a test.
+        let advance = async {
+            tokio::time::sleep(Duration::from_millis(2_200)).await;
+            clock.advance(Duration::from_secs(1));
+        };
+        tokio::join!(advance, schedule.tick());
+
+        assert_eq!(schedule.take_suspend_gap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fire_immediately_ticks_at_start() {
+        for drift_compensation in [false, true] {
+            let start = Instant::now();
+            let mut schedule = Schedule::new(&HeartbeatConfig {
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..config(drift_compensation)
+            });
+
+            assert_eq!(schedule.tick().await, start);
+            assert_eq!(schedule.tick().await - start, Duration::from_secs(60));
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! (De)serialization of [`reqwest::Method`] as its name, e.g. `"POST"`.
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(
+    method: &reqwest::Method,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(method.as_str())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<reqwest::Method, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    reqwest::Method::from_bytes(name.to_ascii_uppercase().as_bytes())
+        .map_err(serde::de::Error::custom)
+}
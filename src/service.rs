@@ -0,0 +1,119 @@
+//! [`tower::Service`] adapter sending one heartbeat per call.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::runner::{build_client, send_heartbeat};
+use crate::status::Status;
+use crate::{BoxFuture, HeartbeatConfig, HeartbeatError, HeartbeatStatusHandle};
+
+/// A [`tower::Service`] that sends one heartbeat per call.
+///
+/// Lets a heartbeat be composed with tower middleware such as timeouts,
+/// rate limits and retries. Each call is classified, logged and counted like
+/// a heartbeat of the background loop, including the crate's own retries if
+/// [`HeartbeatConfig::max_retries`] is set. Clones share the HTTP client and
+/// statistics. Requires the `tower` feature.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{HeartbeatConfig, HeartbeatService};
+/// use tower::Service;
+///
+/// # async fn run() -> Result<(), betteruptime_heartbeat::HeartbeatError> {
+/// let mut service = HeartbeatService::new(HeartbeatConfig {
+///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///     ..HeartbeatConfig::default()
+/// })?;
+///
+/// service.call(()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeartbeatService {
+    client: reqwest::Client,
+    config: Arc<HeartbeatConfig>,
+    status: Arc<Status>,
+    sequence: Arc<AtomicUsize>,
+}
+
+impl HeartbeatService {
+    /// Validate `config` and create the service's HTTP client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Config`] if the config is invalid and
+    /// [`HeartbeatError::Client`] if the HTTP client cannot be created.
+    pub fn new(config: HeartbeatConfig) -> Result<Self, HeartbeatError> {
+        let config = config.resolve_interval();
+        config.validate()?;
+        let client = build_client(&config).map_err(HeartbeatError::Client)?;
+        let status = Arc::new(Status::new(&config));
+
+        Ok(Self { client, config: Arc::new(config), status, sequence: Arc::default() })
+    }
+
+    /// A status handle observing the heartbeats sent through this service.
+    #[must_use]
+    pub fn status(&self) -> HeartbeatStatusHandle {
+        HeartbeatStatusHandle::new(Arc::clone(&self.status))
+    }
+}
+
+impl tower::Service<()> for HeartbeatService {
+    type Response = ();
+    type Error = HeartbeatError;
+    type Future = BoxFuture<'static, Result<(), HeartbeatError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, (): ()) -> Self::Future {
+        let this = self.clone();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        let send = async move {
+            send_heartbeat(&this.client, &this.config, &this.status, sequence).await.into_result()
+        };
+
+        if self.config.silent {
+            Box::pin(send.with_subscriber(NoSubscriber::default()))
+        } else {
+            Box::pin(send)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::test_util::{MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn test_oneshot_sends_one_heartbeat() {
+        let server = MockServer::start().await;
+        let service = HeartbeatService::new(HeartbeatConfig {
+            url: server.url(),
+            ..HeartbeatConfig::default()
+        })
+        .unwrap();
+        let status = service.status();
+
+        service.clone().oneshot(()).await.unwrap();
+        assert_eq!(server.request_count(), 1);
+
+        server.push_response(MockResponse::status(503));
+        let err = service.oneshot(()).await.unwrap_err();
+        assert_eq!(err, HeartbeatError::Http(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!((status.stats().successes, status.stats().failures), (1, 1));
+    }
+}
@@ -0,0 +1,181 @@
+//! One loop sending heartbeats to several URLs.
+
+use std::sync::Arc;
+
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::instrument::WithSubscriber;
+use tracing::subscriber::NoSubscriber;
+
+use crate::runner::{build_client_or_log, send_heartbeat};
+use crate::schedule::{self, Schedule};
+use crate::status::Status;
+use crate::{HeartbeatConfig, HeartbeatStatusHandle};
+
+/// Settings shared by all URLs of a [`spawn_shared`] loop.
+///
+/// This is a [`HeartbeatConfig`] whose `url` is ignored. Settings of
+/// individual requests (timeouts, retries, headers, validation, ...) and of
+/// the schedule apply to every URL; loop-level settings such as
+/// `abort_after_failures` and the failure policies are not supported.
+pub type SharedConfig = HeartbeatConfig;
+
+/// Spawn one loop that sends a heartbeat to each of `urls` every interval.
+///
+/// All URLs share one HTTP client and one timer, and the requests of a tick
+/// are sent concurrently. Returns the loop's [`JoinHandle`] together with a
+/// status handle per URL, in the order of `urls`, so outcomes are tracked
+/// separately.
+///
+/// The config of each URL, `common` with that URL, goes through the same
+/// checks as in [`spawn`](crate::spawn): a zero interval or timeout is
+/// raised to 1s, then the [config interceptor](crate::set_config_interceptor)
+/// and validation run. A refused URL is
+/// logged and never pinged, and its status handle stays at zero. The timer
+/// and the client follow the config of the first accepted URL.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use betteruptime_heartbeat::{SharedConfig, spawn_shared};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let urls = vec![
+///         "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN_A".to_string(),
+///         "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN_B".to_string(),
+///     ];
+///
+///     let (task, statuses) = spawn_shared(urls, SharedConfig::default());
+///     println!("TOKEN_B successes: {}", statuses[1].stats().successes);
+///     task.abort();
+/// }
+/// ```
+#[must_use = "dropping the handles detaches the task"]
+pub fn spawn_shared(
+    urls: Vec<String>,
+    common: SharedConfig,
+) -> (JoinHandle<()>, Vec<HeartbeatStatusHandle>) {
+    let mut targets = Vec::new();
+    let count = urls.len();
+    let statuses = urls
+        .into_iter()
+        .zip(std::iter::repeat_n(common, count))
+        .map(|(url, common)| {
+            let config = HeartbeatConfig { url, ..common };
+            let status = Arc::new(Status::new(&config));
+            match crate::checked(config) {
+                Ok(config) => targets.push((Arc::new(config), Arc::clone(&status))),
+                Err(e) => tracing::error!("Heartbeat not started: {}", e),
+            }
+            HeartbeatStatusHandle::new(status)
+        })
+        .collect();
+    let Some(common) = targets.first().map(|(config, _)| HeartbeatConfig::clone(config)) else {
+        return (tokio::spawn(async {}), statuses);
+    };
+    if !common.silent {
+        tracing::info!(
+            "Shared heartbeat task spawned: urls={}, interval={}s, timeout={}s",
+            targets.len(),
+            common.interval_secs,
+            common.timeout_secs
+        );
+    }
+
+    let task = if common.silent {
+        tokio::spawn(shared_loop(common, targets).with_subscriber(NoSubscriber::default()))
+    } else {
+        tokio::spawn(shared_loop(common, targets))
+    };
+
+    (task, statuses)
+}
+
+async fn shared_loop(common: SharedConfig, targets: Vec<(Arc<HeartbeatConfig>, Arc<Status>)>) {
+    let Some(client) = build_client_or_log(&common) else {
+        return;
+    };
+
+    let mut schedule = Schedule::delayed(&common, schedule::stagger(&common));
+    let mut sequence = 0_usize;
+    loop {
+        schedule.tick().await;
+
+        let mut sends = JoinSet::new();
+        for (config, status) in &targets {
+            let (client, config, status) = (client.clone(), Arc::clone(config), Arc::clone(status));
+            sends.spawn(
+                async move {
+                    send_heartbeat(&client, &config, &status, sequence).await;
+                }
+                .with_current_subscriber(),
+            );
+        }
+        sends.join_all().await;
+        sequence = sequence.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::FirstTickPolicy;
+    use crate::test_util::{ManualClock, MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn test_all_urls_pinged_each_tick() {
+        let servers =
+            [MockServer::start().await, MockServer::start().await, MockServer::start().await];
+        servers[1].set_default_response(MockResponse::status(503));
+        let clock = ManualClock::new();
+        let common = SharedConfig {
+            interval_secs: 1,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            clock: Some(Arc::new(clock.clone())),
+            ..SharedConfig::default()
+        };
+
+        let (task, statuses) = spawn_shared(servers.iter().map(MockServer::url).collect(), common);
+        for tick in 1..=2 {
+            for server in &servers {
+                server.wait_for_requests(tick, Duration::from_secs(5)).await;
+            }
+            // Nothing more is sent until the clock reaches the next tick.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let counts: Vec<_> = servers.iter().map(MockServer::request_count).collect();
+            assert_eq!(counts, [tick; 3], "tick {tick}");
+            clock.advance(Duration::from_secs(1));
+        }
+        task.abort();
+
+        let stats: Vec<_> = statuses.iter().map(HeartbeatStatusHandle::stats).collect();
+        assert_eq!((stats[0].successes, stats[0].failures), (2, 0));
+        assert_eq!((stats[1].successes, stats[1].failures), (0, 2));
+        assert_eq!((stats[2].successes, stats[2].failures), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn test_zero_interval_is_clamped_and_invalid_urls_left_out() {
+        let server = MockServer::start().await;
+        let clock = ManualClock::new();
+        let common = SharedConfig {
+            interval_secs: 0,
+            first_tick_policy: FirstTickPolicy::FireImmediately,
+            clock: Some(Arc::new(clock.clone())),
+            ..SharedConfig::default()
+        };
+
+        let (task, statuses) = spawn_shared(vec!["not a url".to_string(), server.url()], common);
+        server.wait_for_requests(1, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.request_count(), 1);
+        clock.advance(Duration::from_secs(1));
+        server.wait_for_requests(2, Duration::from_secs(5)).await;
+        task.abort();
+
+        assert_eq!(statuses[0].stats(), crate::HeartbeatStats::default());
+        assert_eq!(statuses[1].stats().successes, 2);
+    }
+}
@@ -0,0 +1,129 @@
+//! Parsing of compact `key=value` config specs.
+
+use std::str::FromStr;
+
+use crate::{ConfigError, HeartbeatConfig};
+
+impl FromStr for HeartbeatConfig {
+    type Err = ConfigError;
+
+    /// Parse a compact config spec such as `url=https://...;interval=60;timeout=10`.
+    ///
+    /// # Grammar
+    ///
+    /// ```text
+    /// spec  = entry *( ";" entry )
+    /// entry = key "=" value
+    /// key   = "url" | "interval" | "timeout"
+    /// ```
+    ///
+    /// - `url` (required): heartbeat URL; everything after the first `=` is
+    ///   the value, so query strings are allowed, but the URL must not contain `;`
+    /// - `interval` (optional): interval in seconds, default 60
+    /// - `timeout` (optional): timeout in seconds, default 10
+    ///
+    /// Whitespace around keys and values is ignored, as are empty entries
+    /// (e.g. a trailing `;`). Later entries override earlier ones. All other
+    /// fields keep their [`Default`] values.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::UnknownKey`] for unrecognized keys,
+    /// [`ConfigError::InvalidValue`] for non-numeric durations,
+    /// [`ConfigError::Malformed`] for entries without `=`, and
+    /// [`ConfigError::MissingUrl`] if no non-empty `url` is given.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::HeartbeatConfig;
+    ///
+    /// let config: HeartbeatConfig =
+    ///     "url=https://uptime.betterstack.com/api/v1/heartbeat/TOKEN;interval=30".parse()?;
+    /// assert_eq!(config.interval_secs, 30);
+    /// # Ok::<(), betteruptime_heartbeat::ConfigError>(())
+    /// ```
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) =
+                entry.split_once('=').ok_or_else(|| ConfigError::Malformed(entry.to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            let parse_secs = || {
+                value.parse::<u64>().map_err(|_| ConfigError::InvalidValue {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            };
+
+            match key {
+                "url" => config.url = value.to_string(),
+                "interval" => config.interval_secs = parse_secs()?,
+                "timeout" => config.timeout_secs = parse_secs()?,
+                _ => return Err(ConfigError::UnknownKey(key.to_string())),
+            }
+        }
+
+        if config.url.is_empty() {
+            return Err(ConfigError::MissingUrl);
+        }
+
+        Ok(config)
+    }
+}
+
+impl TryFrom<&str> for HeartbeatConfig {
+    type Error = ConfigError;
+
+    /// Same as [`HeartbeatConfig::from_str`].
+    fn try_from(spec: &str) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_spec() {
+        let config: HeartbeatConfig =
+            "url=https://example.com/heartbeat?a=b; interval=120 ;timeout=30;".parse().unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat?a=b");
+        assert_eq!(config.interval_secs, 120);
+        assert_eq!(config.timeout_secs, 30);
+    }
+
+    #[test]
+    fn test_parse_url_only_spec_uses_defaults() {
+        let config = HeartbeatConfig::try_from("url=https://example.com/heartbeat").unwrap();
+
+        assert_eq!(config.url, "https://example.com/heartbeat");
+        assert_eq!(config.interval_secs, 60);
+        assert_eq!(config.timeout_secs, 10);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_key() {
+        let err = "url=https://example.com;intervall=5".parse::<HeartbeatConfig>().unwrap_err();
+
+        assert_eq!(err, ConfigError::UnknownKey("intervall".to_string()));
+        assert_eq!(err.to_string(), "unknown heartbeat config key `intervall`");
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_values_and_missing_url() {
+        assert_eq!(
+            "url=https://example.com;timeout=soon".parse::<HeartbeatConfig>().unwrap_err(),
+            ConfigError::InvalidValue { key: "timeout".to_string(), value: "soon".to_string() }
+        );
+        assert_eq!("interval=5".parse::<HeartbeatConfig>().unwrap_err(), ConfigError::MissingUrl);
+        assert_eq!(
+            "url".parse::<HeartbeatConfig>().unwrap_err(),
+            ConfigError::Malformed("url".to_string())
+        );
+    }
+}
@@ -0,0 +1,998 @@
+//! Shared heartbeat status, updated by the loop and read through
+//! [`HeartbeatStatusHandle`].
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::{FailureClass, HeartbeatConfig, HeartbeatError};
+
+/// Point-in-time snapshot of heartbeat statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeartbeatStats {
+    /// Number of successful heartbeats.
+    pub successes: u64,
+    /// Number of failed heartbeats (request errors and unsuccessful responses).
+    pub failures: u64,
+    /// Number of failures since the last success.
+    pub consecutive_failures: u64,
+    /// Number of successes since the last failure.
+    pub consecutive_successes: u64,
+    /// Status code of the most recent response, if any was received.
+    pub last_status_code: Option<u16>,
+    /// Round-trip time of the most recent attempt.
+    pub last_latency: Option<Duration>,
+    /// When the most recent successful heartbeat was recorded, if any.
+    pub last_success: Option<Instant>,
+}
+
+/// Serializable summary of a heartbeat loop's state, e.g. for a JSON status
+/// endpoint.
+///
+/// Contains no configuration, so the secret heartbeat URL cannot leak
+/// through it. Derives `serde::Serialize` with the `serde` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeartbeatStatusSnapshot {
+    /// Number of successful heartbeats.
+    pub successes: u64,
+    /// Number of failed heartbeats.
+    pub failures: u64,
+    /// Number of failures since the last success.
+    pub consecutive_failures: u64,
+    /// Status code of the most recent response, if any was received.
+    pub last_status_code: Option<u16>,
+    /// Round-trip time of the most recent attempt in milliseconds.
+    pub last_latency_ms: Option<u64>,
+    /// Whether the most recent heartbeat succeeded; before the first one,
+    /// [`HeartbeatConfig::readiness_default`](crate::HeartbeatConfig::readiness_default).
+    pub healthy: bool,
+    /// Whole seconds since the last successful heartbeat, if any.
+    pub last_success_age_secs: Option<u64>,
+}
+
+/// What kind of failure a [`FailureRecord`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FailureKind {
+    /// The request did not complete within the timeout.
+    Timeout,
+    /// No connection to the heartbeat endpoint could be made.
+    Connect,
+    /// A response was received with this status, not counted as success.
+    Status(u16),
+    /// Any other failure, e.g. a response body rejected by the validator.
+    Other,
+}
+
+impl FailureKind {
+    const fn of(error: &HeartbeatError) -> Self {
+        match error {
+            HeartbeatError::Timeout => Self::Timeout,
+            HeartbeatError::Connect(_) => Self::Connect,
+            HeartbeatError::Http(status) => Self::Status(status.as_u16()),
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Why a scheduled heartbeat was not sent, as counted by
+/// [`HeartbeatStatusHandle::skip_counts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SkipReason {
+    /// The tick passed while a slow heartbeat was in flight and was dropped
+    /// per the [`OverlapPolicy`](crate::OverlapPolicy) or drift compensation.
+    Overlap,
+    /// The tick came due while the loop was paused.
+    Paused,
+    /// The system was suspended and `heartbeat_on_resume` is off.
+    Suspended,
+    /// The load average was above
+    /// [`skip_above_loadavg`](crate::HeartbeatConfig::skip_above_loadavg).
+    HighLoad,
+    /// The health check failed with
+    /// [`UnhealthyPolicy::Skip`](crate::UnhealthyPolicy::Skip).
+    Unhealthy,
+}
+
+impl SkipReason {
+    const ALL: [Self; 5] =
+        [Self::Overlap, Self::Paused, Self::Suspended, Self::HighLoad, Self::Unhealthy];
+}
+
+/// One failed heartbeat, as kept for
+/// [`HeartbeatStatusHandle::recent_failures`].
+///
+/// The message never contains the heartbeat URL. Derives
+/// `serde::Serialize` with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FailureRecord {
+    /// When the heartbeat failed, after its last retry.
+    pub at: SystemTime,
+    /// What went wrong.
+    pub kind: FailureKind,
+    /// Description of the failure.
+    pub message: String,
+}
+
+/// Why a heartbeat loop stopped by itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// [`HeartbeatConfig::abort_after_failures`](crate::HeartbeatConfig::abort_after_failures)
+    /// consecutive heartbeats failed.
+    TooManyFailures,
+    /// A [`FailurePolicy`](crate::FailurePolicy) with
+    /// [`FailureAction::Stop`](crate::FailureAction::Stop) was triggered by
+    /// failures of this class.
+    FailurePolicy(FailureClass),
+    /// [`HeartbeatConfig::total_retry_budget`](crate::HeartbeatConfig::total_retry_budget)
+    /// failed attempts were made since the loop started.
+    RetryBudgetExhausted,
+}
+
+/// A change between a healthy and a failing heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthTransition {
+    /// A heartbeat failed at startup or after a success.
+    Down,
+    /// A heartbeat succeeded after one or more failures.
+    Up,
+}
+
+/// Details of the heartbeat outcome that triggered a hook.
+///
+/// Passed to the hooks created with [`TransitionHook::with_context`] and
+/// [`FailureHook::with_context`](crate::FailureHook::with_context), e.g. to
+/// attach as tags when reporting to an error tracker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackContext {
+    /// Heartbeat URL with its token hidden, as returned by
+    /// [`HeartbeatConfig::redacted_url`].
+    pub url: String,
+    /// Kind of the failure, `None` for a success or an outcome recorded
+    /// through [`HeartbeatStatusHandle::record_outcome`].
+    pub failure: Option<FailureClass>,
+    /// Status code of the response, `None` if no response was received.
+    pub status_code: Option<u16>,
+    /// Consecutive failures including this outcome. For a
+    /// [`FailureHook`](crate::FailureHook), the streak of `failure`'s class.
+    pub consecutive_failures: u64,
+}
+
+/// Callback invoked whenever the heartbeat changes between healthy and
+/// failing.
+///
+/// The loop starts out healthy, so the first success does not fire [`HealthTransition::Up`]
+/// but the first failure fires [`HealthTransition::Down`]. The callback runs
+/// synchronously on the task recording the outcome and should return
+/// quickly. Cloning is cheap; clones share the same function.
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::{HealthTransition, HeartbeatConfig, TransitionHook};
+///
+/// let config = HeartbeatConfig {
+///     url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+///     on_transition: Some(TransitionHook::new(|transition| {
+///         if transition == HealthTransition::Down {
+///             eprintln!("heartbeat is failing");
+///         }
+///     })),
+///     ..HeartbeatConfig::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct TransitionHook(Arc<TransitionFn>);
+
+type TransitionFn = dyn Fn(HealthTransition, &CallbackContext) + Send + Sync;
+
+impl TransitionHook {
+    /// Wrap a function receiving each transition.
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(HealthTransition) + Send + Sync + 'static,
+    {
+        Self(Arc::new(move |transition, _: &CallbackContext| hook(transition)))
+    }
+
+    /// Wrap a function receiving each transition together with details of
+    /// the outcome that caused it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use betteruptime_heartbeat::{HealthTransition, TransitionHook};
+    ///
+    /// let hook = TransitionHook::with_context(|transition, context| {
+    ///     if transition == HealthTransition::Down {
+    ///         // e.g. sentry::capture_message with these as tags
+    ///         eprintln!(
+    ///             "heartbeat {} failing: {:?}, status {:?}, {} in a row",
+    ///             context.url, context.failure, context.status_code, context.consecutive_failures
+    ///         );
+    ///     }
+    /// });
+    /// ```
+    pub fn with_context<F>(hook: F) -> Self
+    where
+        F: Fn(HealthTransition, &CallbackContext) + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+
+    fn call(&self, transition: HealthTransition, context: &CallbackContext) {
+        (self.0)(transition, context);
+    }
+}
+
+impl fmt::Debug for TransitionHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TransitionHook(..)")
+    }
+}
+
+/// Counters shared between the heartbeat loop and its status handles.
+#[derive(Debug, Default)]
+pub struct Status {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    /// Failed attempts, counting every retry.
+    failed_attempts: AtomicU64,
+    consecutive_failures: AtomicU64,
+    consecutive_successes: AtomicU64,
+    /// `0` until a response has been received.
+    last_status_code: AtomicU16,
+    /// Latency in microseconds plus one, `0` until an attempt completed.
+    last_latency_micros: AtomicU64,
+    on_transition: Option<TransitionHook>,
+    /// Redacted heartbeat URL, for the transition hook's context.
+    url: String,
+    stop_reason: OnceLock<StopReason>,
+    /// Number of `mark_ready` calls so far.
+    ready_marks: watch::Sender<u32>,
+    /// Time of the last success in nanoseconds after `created` plus one,
+    /// `0` until a heartbeat succeeded.
+    last_success_nanos: AtomicU64,
+    created: CreatedAt,
+    /// End of the startup grace period, if any.
+    grace_until: Option<Instant>,
+    /// Deadline of the next scheduled heartbeat, `None` while none is scheduled.
+    next_send: Mutex<Option<Instant>>,
+    /// Most recent latencies, oldest first, at most `latency_capacity` of them.
+    latency_samples: Mutex<VecDeque<Duration>>,
+    latency_capacity: usize,
+    /// Most recent failures, oldest first, at most `failure_capacity` of them.
+    recent_failures: Mutex<VecDeque<FailureRecord>>,
+    failure_capacity: usize,
+    /// Time and success of recent outcomes, oldest first, none older than
+    /// `outcome_retention` and at most [`MAX_OUTCOMES`] of them.
+    outcomes: Mutex<VecDeque<(Instant, bool)>>,
+    outcome_retention: Duration,
+    /// Sum and number of all recorded latencies, for the average.
+    total_latency_micros: AtomicU64,
+    latency_count: AtomicU64,
+    /// End of the window set with `suppress_failures_until`, if any.
+    suppress_until: Mutex<Option<Instant>>,
+    /// Whether any outcome has been recorded since the loop started.
+    attempted: AtomicBool,
+    /// Skipped ticks, indexed by [`SkipReason`].
+    skipped: [AtomicU64; SkipReason::ALL.len()],
+    readiness_default: bool,
+}
+
+/// Most outcomes kept for availability, so frequent heartbeats or outcomes
+/// recorded from outside the loop cannot grow the history without bound.
+const MAX_OUTCOMES: usize = 10_000;
+
+/// Creation time of a [`Status`], which has no other natural default.
+#[derive(Debug)]
+struct CreatedAt(Instant);
+
+impl Default for CreatedAt {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Status {
+    /// Empty status for a loop starting now with `config`'s transition hook,
+    /// startup grace period, latency buffer size and readiness default.
+    pub fn new(config: &HeartbeatConfig) -> Self {
+        let grace_until = (config.startup_grace_secs > 0)
+            .then(|| Instant::now() + Duration::from_secs(config.startup_grace_secs));
+        Self {
+            on_transition: config.on_transition.clone(),
+            url: config.redacted_url(),
+            grace_until,
+            latency_samples: Mutex::new(VecDeque::with_capacity(config.latency_samples)),
+            latency_capacity: config.latency_samples,
+            recent_failures: Mutex::new(VecDeque::with_capacity(config.failure_history)),
+            failure_capacity: config.failure_history,
+            outcome_retention: Duration::from_secs(config.availability_history_secs),
+            readiness_default: config.readiness_default,
+            ..Self::default()
+        }
+    }
+
+    /// Record a successful heartbeat.
+    pub fn record_success(&self, status_code: u16, latency: Duration) {
+        self.record(true, None, Some(status_code));
+        self.last_status_code.store(status_code, Ordering::Relaxed);
+        self.store_latency(latency);
+    }
+
+    /// Record a failed heartbeat. `status_code` is `None` if no response was received.
+    pub fn record_failure(&self, class: FailureClass, status_code: Option<u16>, latency: Duration) {
+        self.record(false, Some(class), status_code);
+        if let Some(code) = status_code {
+            self.last_status_code.store(code, Ordering::Relaxed);
+        }
+        self.store_latency(latency);
+    }
+
+    /// Keep `error` as the most recent failure record.
+    pub fn record_error(&self, error: &HeartbeatError) {
+        if self.failure_capacity == 0 {
+            return;
+        }
+        let record = FailureRecord {
+            at: SystemTime::now(),
+            kind: FailureKind::of(error),
+            message: error.to_string(),
+        };
+        let mut records = self.recent_failures.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() == self.failure_capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Update counters and streaks and fire the transition hook.
+    ///
+    /// Failures during the startup grace period only count towards the
+    /// total, leaving streaks and transitions untouched.
+    pub fn record_outcome(&self, success: bool) {
+        self.record(success, None, None);
+    }
+
+    fn record(&self, success: bool, failure: Option<FailureClass>, status_code: Option<u16>) {
+        self.attempted.store(true, Ordering::Relaxed);
+        self.store_outcome(success);
+        let transition = if success {
+            self.successes.fetch_add(1, Ordering::Relaxed);
+            self.consecutive_successes.fetch_add(1, Ordering::Relaxed);
+            let since_created = Instant::now().saturating_duration_since(self.created.0);
+            let nanos = u64::try_from(since_created.as_nanos()).unwrap_or(u64::MAX - 1);
+            self.last_success_nanos.store(nanos + 1, Ordering::Relaxed);
+            let failed_before = self.consecutive_failures.swap(0, Ordering::Relaxed) > 0;
+            failed_before.then_some(HealthTransition::Up)
+        } else if self.in_grace() {
+            tracing::debug!("Heartbeat failed during startup grace period, streak unchanged");
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            None
+        } else {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            self.consecutive_successes.store(0, Ordering::Relaxed);
+            let healthy_before = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) == 0;
+            healthy_before.then_some(HealthTransition::Down)
+        };
+
+        if let (Some(transition), Some(hook)) = (transition, &self.on_transition) {
+            let context = CallbackContext {
+                url: self.url.clone(),
+                failure,
+                status_code,
+                consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            };
+            hook.call(transition, &context);
+        }
+    }
+
+    /// Whether the startup grace period is still running.
+    pub fn in_grace(&self) -> bool {
+        self.grace_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Treat failures as expected until `until`, see
+    /// [`HeartbeatHandle::suppress_failures_until`](crate::HeartbeatHandle::suppress_failures_until).
+    pub fn suppress_failures_until(&self, until: Instant) {
+        *self.suppress_until.lock().unwrap_or_else(PoisonError::into_inner) = Some(until);
+    }
+
+    /// Whether failures are currently expected and not recorded.
+    pub fn failures_suppressed(&self) -> bool {
+        self.suppress_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Add previously persisted lifetime counters.
+    #[cfg(feature = "serde")]
+    pub fn restore(&self, successes: u64, failures: u64) {
+        self.successes.fetch_add(successes, Ordering::Relaxed);
+        self.failures.fetch_add(failures, Ordering::Relaxed);
+    }
+
+    /// Count one passed readiness check.
+    pub fn mark_ready(&self) {
+        self.ready_marks.send_modify(|marks| *marks = marks.saturating_add(1));
+    }
+
+    /// Resolve once [`mark_ready`](Self::mark_ready) has been called `required` times.
+    pub async fn wait_ready(&self, required: u32) {
+        let mut marks = self.ready_marks.subscribe();
+        // The sender lives in `self`, so this cannot fail.
+        let _ = marks.wait_for(|&marks| marks >= required).await;
+    }
+
+    /// Publish the deadline of the next scheduled heartbeat.
+    pub fn set_next_send(&self, deadline: Option<Instant>) {
+        *self.next_send.lock().unwrap_or_else(PoisonError::into_inner) = deadline;
+    }
+
+    /// Count a failed attempt against `total_retry_budget`, returning the
+    /// number so far. Attempts during the startup grace period or a
+    /// suppression window are expected to fail and not counted.
+    pub fn record_failed_attempt(&self) -> u64 {
+        if self.in_grace() || self.failures_suppressed() {
+            return self.failed_attempts.load(Ordering::Relaxed);
+        }
+        self.failed_attempts.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Whether `total_retry_budget` failed attempts have been made.
+    pub fn retry_budget_exhausted(&self, config: &HeartbeatConfig) -> bool {
+        config
+            .total_retry_budget
+            .is_some_and(|budget| self.failed_attempts.load(Ordering::Relaxed) >= budget)
+    }
+
+    /// Record why the loop stopped. Only the first reason is kept.
+    pub fn stop(&self, reason: StopReason) {
+        let _ = self.stop_reason.set(reason);
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason.get().copied()
+    }
+
+    fn store_outcome(&self, success: bool) {
+        let now = Instant::now();
+        let mut outcomes = self.outcomes.lock().unwrap_or_else(PoisonError::into_inner);
+        while outcomes.front().is_some_and(|&(at, _)| now - at > self.outcome_retention) {
+            outcomes.pop_front();
+        }
+        if !self.outcome_retention.is_zero() {
+            if outcomes.len() == MAX_OUTCOMES {
+                outcomes.pop_front();
+            }
+            outcomes.push_back((now, success));
+        }
+    }
+
+    fn store_latency(&self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX - 1);
+        self.last_latency_micros.store(micros + 1, Ordering::Relaxed);
+        self.total_latency_micros.fetch_add(micros, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+
+        if self.latency_capacity > 0 {
+            let mut samples = self.latency_samples.lock().unwrap_or_else(PoisonError::into_inner);
+            if samples.len() == self.latency_capacity {
+                samples.pop_front();
+            }
+            samples.push_back(latency);
+        }
+    }
+
+    /// Whether the most recent heartbeat succeeded, or the configured
+    /// default before the first outcome.
+    pub fn is_ready(&self) -> bool {
+        if !self.attempted.load(Ordering::Relaxed) {
+            return self.readiness_default;
+        }
+        self.successes.load(Ordering::Relaxed) > 0
+            && self.consecutive_failures.load(Ordering::Relaxed) == 0
+    }
+
+    /// Count `ticks` scheduled heartbeats skipped for `reason`.
+    pub fn record_skips(&self, reason: SkipReason, ticks: u64) {
+        self.skipped[reason as usize].fetch_add(ticks, Ordering::Relaxed);
+    }
+
+    pub fn skip_counts(&self) -> HashMap<SkipReason, u64> {
+        SkipReason::ALL
+            .into_iter()
+            .map(|reason| (reason, self.skipped[reason as usize].load(Ordering::Relaxed)))
+            .filter(|&(_, count)| count > 0)
+            .collect()
+    }
+
+    pub fn latency_samples(&self) -> Vec<Duration> {
+        self.latency_samples
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    pub fn recent_failures(&self) -> Vec<FailureRecord> {
+        self.recent_failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn availability_over(&self, window: Duration) -> Option<f64> {
+        let now = Instant::now();
+        let (successes, total) = self
+            .outcomes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .take_while(|&&(at, _)| now - at <= window)
+            .fold((0_u32, 0_u32), |(successes, total), &(_, success)| {
+                (successes + u32::from(success), total + 1)
+            });
+        (total > 0).then(|| f64::from(successes) / f64::from(total))
+    }
+
+    /// Mean latency of all attempts recorded since the loop started.
+    pub fn average_latency(&self) -> Option<Duration> {
+        let count = self.latency_count.load(Ordering::Relaxed);
+        let total = self.total_latency_micros.load(Ordering::Relaxed);
+        (count > 0).then(|| Duration::from_micros(total / count))
+    }
+
+    pub fn snapshot(&self) -> HeartbeatStats {
+        let last_status_code = match self.last_status_code.load(Ordering::Relaxed) {
+            0 => None,
+            code => Some(code),
+        };
+        let last_latency = match self.last_latency_micros.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros - 1)),
+        };
+        let last_success = match self.last_success_nanos.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(self.created.0 + Duration::from_nanos(nanos - 1)),
+        };
+
+        HeartbeatStats {
+            successes: self.successes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            consecutive_successes: self.consecutive_successes.load(Ordering::Relaxed),
+            last_status_code,
+            last_latency,
+            last_success,
+        }
+    }
+}
+
+/// Read-only view of a running heartbeat loop's statistics.
+///
+/// The handle is cheap to clone and can outlive the loop; after the loop
+/// stops it keeps reporting the final values. Dropping it has no effect on
+/// the loop.
+#[derive(Debug, Clone)]
+pub struct HeartbeatStatusHandle {
+    inner: Arc<Status>,
+}
+
+impl HeartbeatStatusHandle {
+    pub(crate) const fn new(inner: Arc<Status>) -> Self {
+        Self { inner }
+    }
+
+    /// Current statistics.
+    #[must_use]
+    pub fn stats(&self) -> HeartbeatStats {
+        self.inner.snapshot()
+    }
+
+    /// Why the loop stopped by itself, or `None` while it is running or if
+    /// it was aborted or closed.
+    #[must_use]
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.inner.stop_reason()
+    }
+
+    /// Current state as a serializable snapshot.
+    #[must_use]
+    pub fn status_snapshot(&self) -> HeartbeatStatusSnapshot {
+        let stats = self.inner.snapshot();
+
+        HeartbeatStatusSnapshot {
+            successes: stats.successes,
+            failures: stats.failures,
+            consecutive_failures: stats.consecutive_failures,
+            last_status_code: stats.last_status_code,
+            last_latency_ms: stats
+                .last_latency
+                .map(|latency| u64::try_from(latency.as_millis()).unwrap_or(u64::MAX)),
+            healthy: self.inner.is_ready(),
+            last_success_age_secs: stats.last_success.map(|at| at.elapsed().as_secs()),
+        }
+    }
+
+    /// Whether the heartbeat is healthy, for readiness probes.
+    ///
+    /// `true` if the most recent heartbeat succeeded. Before the first
+    /// outcome is recorded, returns
+    /// [`HeartbeatConfig::readiness_default`](crate::HeartbeatConfig::readiness_default).
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.inner.is_ready()
+    }
+
+    /// Latencies of the most recent attempts, oldest first.
+    ///
+    /// Holds up to [`HeartbeatConfig::latency_samples`](crate::HeartbeatConfig::latency_samples)
+    /// entries, one per final attempt whether it succeeded or failed, e.g. for
+    /// building a latency histogram. Retried attempts are not included.
+    #[must_use]
+    pub fn latency_samples(&self) -> Vec<Duration> {
+        self.inner.latency_samples()
+    }
+
+    /// Number of scheduled heartbeats not sent, by reason, e.g. to see how
+    /// often the loop holds back. Reasons that never occurred are absent.
+    /// Manual triggers are not scheduled, and heartbeats delayed by the
+    /// global rate limit are still sent, so neither shows up here.
+    #[must_use]
+    pub fn skip_counts(&self) -> HashMap<SkipReason, u64> {
+        self.inner.skip_counts()
+    }
+
+    /// The most recent failed heartbeats, oldest first, e.g. for a debug
+    /// endpoint.
+    ///
+    /// Holds up to [`HeartbeatConfig::failure_history`](crate::HeartbeatConfig::failure_history)
+    /// records, one per heartbeat that failed after its retries. Successes,
+    /// retried attempts and failures inside a
+    /// [suppression window](crate::HeartbeatHandle::suppress_failures_until)
+    /// add none.
+    #[must_use]
+    pub fn recent_failures(&self) -> Vec<FailureRecord> {
+        self.inner.recent_failures()
+    }
+
+    /// Share of successful heartbeats among those recorded within the last
+    /// `window`, from 0.0 to 1.0, e.g. for SLO dashboards.
+    ///
+    /// Counts every recorded outcome, including failures during the startup
+    /// grace period and outcomes passed to
+    /// [`record_outcome`](Self::record_outcome). Outcomes are kept for
+    /// [`HeartbeatConfig::availability_history_secs`](crate::HeartbeatConfig::availability_history_secs),
+    /// up to the last 10 000, so a longer window covers only that span.
+    /// Returns `None` if no
+    /// heartbeat was recorded within the window.
+    #[must_use]
+    pub fn availability_over(&self, window: Duration) -> Option<f64> {
+        self.inner.availability_over(window)
+    }
+
+    /// Wall-clock time at which the next heartbeat is scheduled.
+    ///
+    /// Returns `None` while no heartbeat is scheduled: before the readiness
+    /// gate opens, while paused and after the loop stopped. The loop keeps a
+    /// monotonic deadline, which is converted to wall-clock time at the time
+    /// of the call; the result is therefore only as accurate as the current
+    /// system clock and shifts if the clock is adjusted before the send. A
+    /// time in the past means the heartbeat is due or in flight.
+    #[must_use]
+    pub fn next_send_at(&self) -> Option<SystemTime> {
+        let deadline = (*self.inner.next_send.lock().unwrap_or_else(PoisonError::into_inner))?;
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+
+        if deadline >= now {
+            Some(wall_now + deadline.duration_since(now))
+        } else {
+            wall_now.checked_sub(now.duration_since(deadline))
+        }
+    }
+
+    pub(crate) fn mark_ready(&self) {
+        self.inner.mark_ready();
+    }
+
+    pub(crate) fn suppress_failures_until(&self, until: Instant) {
+        self.inner.suppress_failures_until(until);
+    }
+
+    /// Record a heartbeat outcome observed outside the loop.
+    ///
+    /// Updates the counters and consecutive counts of this status and fires
+    /// [`HeartbeatConfig::on_transition`](crate::HeartbeatConfig::on_transition),
+    /// e.g. when the application confirmed out-of-band that the monitor was
+    /// pinged. No request is sent, and the last status code and latency are
+    /// left unchanged.
+    ///
+    /// The loop itself does not see the outcome: it does not count towards
+    /// [`HeartbeatConfig::abort_after_failures`](crate::HeartbeatConfig::abort_after_failures)
+    /// or a [`FailurePolicy`](crate::FailurePolicy), and no observer is
+    /// called.
+    pub fn record_outcome(&self, success: bool) {
+        self.inner.record_outcome(success);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_starts_empty() {
+        let status = Status::default();
+        assert_eq!(status.snapshot(), HeartbeatStats::default());
+    }
+
+    #[test]
+    fn test_status_tracks_successes_and_failures() {
+        let status = Status::default();
+
+        status.record_failure(FailureClass::Transport, None, Duration::from_millis(5));
+        status.record_failure(FailureClass::Http, Some(503), Duration::from_millis(7));
+        assert_eq!(
+            status.snapshot(),
+            HeartbeatStats {
+                successes: 0,
+                failures: 2,
+                consecutive_failures: 2,
+                consecutive_successes: 0,
+                last_status_code: Some(503),
+                last_latency: Some(Duration::from_millis(7)),
+                last_success: None,
+            }
+        );
+
+        let before = Instant::now();
+        status.record_success(200, Duration::from_millis(3));
+        let snapshot = status.snapshot();
+        assert!(snapshot.last_success.is_some_and(|at| at >= before), "{snapshot:?}");
+        assert_eq!(
+            snapshot,
+            HeartbeatStats {
+                successes: 1,
+                failures: 2,
+                consecutive_failures: 0,
+                consecutive_successes: 1,
+                last_status_code: Some(200),
+                last_latency: Some(Duration::from_millis(3)),
+                last_success: snapshot.last_success,
+            }
+        );
+    }
+
+    #[test]
+    fn test_recorded_outcomes_update_streaks_and_fire_transitions() {
+        let transitions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hook = {
+            let transitions = Arc::clone(&transitions);
+            TransitionHook::new(move |t| transitions.lock().unwrap().push(t))
+        };
+        let handle = HeartbeatStatusHandle::new(Arc::new(Status::new(&HeartbeatConfig {
+            on_transition: Some(hook),
+            ..HeartbeatConfig::default()
+        })));
+
+        handle.record_outcome(true);
+        handle.record_outcome(false);
+        handle.record_outcome(false);
+        let stats = handle.stats();
+        assert_eq!((stats.consecutive_failures, stats.consecutive_successes), (2, 0));
+
+        handle.record_outcome(true);
+        handle.record_outcome(true);
+        let stats = handle.stats();
+        assert_eq!((stats.successes, stats.failures), (3, 2));
+        assert_eq!((stats.consecutive_failures, stats.consecutive_successes), (0, 2));
+        assert_eq!(stats.last_status_code, None);
+
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![HealthTransition::Down, HealthTransition::Up]
+        );
+    }
+
+    #[test]
+    fn test_transition_hook_context_describes_failure() {
+        let contexts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hook = {
+            let contexts = Arc::clone(&contexts);
+            TransitionHook::with_context(move |transition, context| {
+                contexts.lock().unwrap().push((transition, context.clone()));
+            })
+        };
+        let status = Status::new(&HeartbeatConfig {
+            url: "https://uptime.betterstack.com/api/v1/heartbeat/TOKEN".to_string(),
+            on_transition: Some(hook),
+            ..HeartbeatConfig::default()
+        });
+
+        status.record_failure(FailureClass::Http, Some(503), Duration::from_millis(1));
+        status.record_failure(FailureClass::Transport, None, Duration::from_millis(1));
+        status.record_success(200, Duration::from_millis(1));
+
+        let url = "https://uptime.betterstack.com/***".to_string();
+        assert_eq!(
+            *contexts.lock().unwrap(),
+            vec![
+                (
+                    HealthTransition::Down,
+                    CallbackContext {
+                        url: url.clone(),
+                        failure: Some(FailureClass::Http),
+                        status_code: Some(503),
+                        consecutive_failures: 1,
+                    }
+                ),
+                (
+                    HealthTransition::Up,
+                    CallbackContext {
+                        url,
+                        failure: None,
+                        status_code: Some(200),
+                        consecutive_failures: 0,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_readiness_before_first_attempt_uses_default() {
+        for readiness_default in [false, true] {
+            let handle = HeartbeatStatusHandle::new(Arc::new(Status::new(&HeartbeatConfig {
+                readiness_default,
+                ..HeartbeatConfig::default()
+            })));
+            assert_eq!(handle.is_ready(), readiness_default);
+            assert_eq!(handle.status_snapshot().healthy, readiness_default);
+
+            handle.inner.record_failure(FailureClass::Http, Some(503), Duration::from_millis(1));
+            assert!(!handle.is_ready());
+            handle.inner.record_success(200, Duration::from_millis(1));
+            assert!(handle.is_ready());
+        }
+    }
+
+    #[test]
+    fn test_latency_samples_evict_oldest() {
+        let status =
+            Status::new(&HeartbeatConfig { latency_samples: 3, ..HeartbeatConfig::default() });
+        let handle = HeartbeatStatusHandle::new(Arc::new(status));
+        let ms = Duration::from_millis;
+
+        handle.inner.record_success(200, ms(10));
+        handle.inner.record_failure(FailureClass::Transport, None, ms(20));
+        assert_eq!(handle.latency_samples(), vec![ms(10), ms(20)]);
+
+        handle.inner.record_success(200, ms(30));
+        handle.inner.record_success(200, ms(40));
+        handle.inner.record_failure(FailureClass::Http, Some(503), ms(50));
+        assert_eq!(handle.latency_samples(), vec![ms(30), ms(40), ms(50)]);
+
+        let disabled =
+            Status::new(&HeartbeatConfig { latency_samples: 0, ..HeartbeatConfig::default() });
+        disabled.record_success(200, ms(10));
+        assert!(disabled.latency_samples().is_empty());
+    }
+
+    #[test]
+    fn test_recent_failures_evict_oldest() {
+        let status =
+            Status::new(&HeartbeatConfig { failure_history: 2, ..HeartbeatConfig::default() });
+        status.record_error(&HeartbeatError::Timeout);
+        status.record_error(&HeartbeatError::Connect("refused".into()));
+        status.record_error(&HeartbeatError::Other("body failed validation".into()));
+
+        let records = status.recent_failures();
+        let kinds: Vec<FailureKind> = records.iter().map(|record| record.kind).collect();
+        assert_eq!(kinds, vec![FailureKind::Connect, FailureKind::Other]);
+        assert_eq!(records[1].message, "heartbeat failed: body failed validation");
+
+        let disabled =
+            Status::new(&HeartbeatConfig { failure_history: 0, ..HeartbeatConfig::default() });
+        disabled.record_error(&HeartbeatError::Timeout);
+        assert!(disabled.recent_failures().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_availability_over_window() {
+        let status = Status::new(&HeartbeatConfig {
+            availability_history_secs: 600,
+            ..HeartbeatConfig::default()
+        });
+        let minutes = |n: u64| Duration::from_secs(60 * n);
+        assert_eq!(status.availability_over(minutes(5)), None);
+
+        // One outcome per minute: 4 failures, then 6 successes.
+        for success in [false, false, false, false, true, true, true, true, true, true] {
+            status.record_outcome(success);
+            tokio::time::advance(minutes(1)).await;
+        }
+
+        assert_eq!(status.availability_over(minutes(3)), Some(1.0));
+        assert_eq!(status.availability_over(minutes(8)), Some(0.75));
+        assert_eq!(status.availability_over(minutes(60)), Some(0.6));
+
+        // Outcomes older than the history are evicted.
+        tokio::time::advance(minutes(5)).await;
+        status.record_outcome(false);
+        assert_eq!(status.availability_over(minutes(60)), Some(5.0 / 6.0));
+        assert_eq!(status.outcomes.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_outcome_history_is_capped() {
+        let status = Status::new(&HeartbeatConfig::default());
+        status.record_outcome(false);
+        for _ in 0..MAX_OUTCOMES {
+            status.record_outcome(true);
+        }
+
+        // The failure recorded first was evicted.
+        assert_eq!(status.outcomes.lock().unwrap().len(), MAX_OUTCOMES);
+        assert_eq!(status.availability_over(Duration::from_secs(60)), Some(1.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_status_snapshot() {
+        let handle = HeartbeatStatusHandle::new(Arc::new(Status::default()));
+        assert!(!handle.status_snapshot().healthy);
+
+        handle.inner.record_success(200, Duration::from_millis(42));
+        tokio::time::advance(Duration::from_secs(90)).await;
+        handle.inner.record_failure(FailureClass::Http, Some(503), Duration::from_millis(7));
+
+        assert_eq!(
+            handle.status_snapshot(),
+            HeartbeatStatusSnapshot {
+                successes: 1,
+                failures: 1,
+                consecutive_failures: 1,
+                last_status_code: Some(503),
+                last_latency_ms: Some(7),
+                healthy: false,
+                last_success_age_secs: Some(90),
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_status_snapshot_serializes_to_json() {
+        let handle = HeartbeatStatusHandle::new(Arc::new(Status::default()));
+        handle.inner.record_success(204, Duration::from_millis(12));
+
+        let json = serde_json::to_value(handle.status_snapshot()).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "successes": 1,
+                "failures": 0,
+                "consecutive_failures": 0,
+                "last_status_code": 204,
+                "last_latency_ms": 12,
+                "healthy": true,
+                "last_success_age_secs": 0,
+            })
+        );
+    }
+}
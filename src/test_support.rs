@@ -0,0 +1,95 @@
+//! Helpers shared by the crate's unit tests.
+
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+/// A log event captured by [`capture_logs`].
+#[derive(Debug, Clone)]
+pub struct CapturedEvent {
+    pub level: Level,
+    /// Module path of the event, e.g. to tell the crate's events from its
+    /// dependencies'.
+    pub target: &'static str,
+    /// The event message followed by its other fields as ` key=value`.
+    pub text: String,
+    /// Fields of the spans the event was emitted in, innermost first, as
+    /// ` key=value`.
+    pub spans: String,
+}
+
+/// Events captured on the current thread while the guard is alive.
+#[derive(Debug, Clone, Default)]
+pub struct LogCapture {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl LogCapture {
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+struct CaptureLayer {
+    capture: LogCapture,
+}
+
+/// Fields of a span as ` key=value`, kept in its extensions.
+struct SpanFields(String);
+
+struct TextVisitor<'a>(&'a mut String);
+
+impl Visit for TextVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CaptureLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = String::new();
+        attrs.record(&mut TextVisitor(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut text = String::new();
+        event.record(&mut TextVisitor(&mut text));
+        let mut spans = String::new();
+        for span in ctx.event_scope(event).into_iter().flatten() {
+            if let Some(fields) = span.extensions().get::<SpanFields>() {
+                spans.push_str(&fields.0);
+            }
+        }
+        self.capture.events.lock().unwrap().push(CapturedEvent {
+            level: *event.metadata().level(),
+            target: event.metadata().target(),
+            text,
+            spans,
+        });
+    }
+}
+
+/// Capture all events emitted on the current thread until the guard is dropped.
+///
+/// Use with the default current-thread `#[tokio::test]` runtime so that
+/// spawned tasks are polled on the test thread.
+pub fn capture_logs() -> (LogCapture, DefaultGuard) {
+    let capture = LogCapture::default();
+    let subscriber = Registry::default().with(CaptureLayer { capture: capture.clone() });
+    let guard = tracing::subscriber::set_default(subscriber);
+    (capture, guard)
+}
@@ -0,0 +1,528 @@
+//! Test utilities for exercising heartbeat loops against a local HTTP server.
+//!
+//! Enabled by the `test-util` feature. [`MockServer`] is a minimal HTTP/1.1
+//! server bound to `127.0.0.1` that records every request it receives,
+//! [`check_interval_timing`] asserts that recorded requests arrived at the
+//! configured heartbeat interval, and [`ManualClock`] drives the schedule
+//! without real delays.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use betteruptime_heartbeat::test_util::MockServer;
+//! use betteruptime_heartbeat::{HeartbeatConfig, spawn};
+//!
+//! #[tokio::test]
+//! async fn heartbeat_is_sent_every_second() {
+//!     let server = MockServer::start().await;
+//!
+//!     let _heartbeat = spawn(HeartbeatConfig {
+//!         url: server.url(),
+//!         interval_secs: 1,
+//!         timeout_secs: 5,
+//!         ..HeartbeatConfig::default()
+//!     });
+//!
+//!     server.wait_for_requests(3, Duration::from_secs(10)).await;
+//!     server
+//!         .check_interval_timing(Duration::from_secs(1), Duration::from_millis(250))
+//!         .unwrap();
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::BoxFuture;
+use crate::clock::Clock;
+
+/// Canned response returned by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// Additional response headers.
+    pub headers: Vec<(String, String)>,
+    /// Response body.
+    pub body: Vec<u8>,
+    /// Delay before the response is written.
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    /// Create an empty response with the given status code.
+    #[must_use]
+    pub const fn status(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: Vec::new(), delay: Duration::ZERO }
+    }
+
+    /// Add a response header.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the response body.
+    #[must_use]
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Delay the response by `delay`.
+    #[must_use]
+    pub const fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl Default for MockResponse {
+    fn default() -> Self {
+        Self::status(200)
+    }
+}
+
+/// Request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// HTTP method, e.g. `GET`.
+    pub method: String,
+    /// Request target including the query string, e.g. `/heartbeat?ts=1`.
+    pub path: String,
+    /// Request headers with lowercased names.
+    pub headers: Vec<(String, String)>,
+    /// Request body.
+    pub body: Vec<u8>,
+    /// When the request was fully received.
+    pub received_at: Instant,
+}
+
+impl RecordedRequest {
+    /// Return the first value of the header `name` (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// Return the first value of the query parameter `name`.
+    #[must_use]
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let url = reqwest::Url::parse(&format!("http://localhost{}", self.path)).ok()?;
+        url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned())
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    requests: Vec<RecordedRequest>,
+    responses: VecDeque<MockResponse>,
+    default_response: MockResponse,
+}
+
+/// Minimal local HTTP server that records heartbeat requests.
+///
+/// Responses are taken from a queue filled by [`MockServer::push_response`];
+/// once the queue is empty the default response (`200 OK` unless changed via
+/// [`MockServer::set_default_response`]) is used. Every connection is closed
+/// after a single response. The server shuts down when dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a server answering every request with `200 OK`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    pub async fn start() -> Self {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").await.expect("failed to bind mock server listener");
+        let addr = listener.local_addr().expect("mock server listener has no local address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let task = tokio::spawn({
+            let state = Arc::clone(&state);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(stream, Arc::clone(&state)));
+                }
+            }
+        });
+
+        Self { addr, state, task }
+    }
+
+    /// Address the server is listening on.
+    #[must_use]
+    pub const fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Heartbeat URL pointing at this server (`http://127.0.0.1:{port}/heartbeat`).
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}/heartbeat", self.addr)
+    }
+
+    /// Queue a response for the next unanswered request.
+    pub fn push_response(&self, response: MockResponse) {
+        self.lock().responses.push_back(response);
+    }
+
+    /// Replace the response used once the queue is empty.
+    pub fn set_default_response(&self, response: MockResponse) {
+        self.lock().default_response = response;
+    }
+
+    /// Requests received so far, in arrival order.
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Number of requests received so far.
+    #[must_use]
+    pub fn request_count(&self) -> usize {
+        self.lock().requests.len()
+    }
+
+    /// Wait until at least `count` requests have been received.
+    ///
+    /// Returns all requests received so far, which may be fewer than `count`
+    /// if `timeout` elapses first.
+    pub async fn wait_for_requests(&self, count: usize, timeout: Duration) -> Vec<RecordedRequest> {
+        let deadline = Instant::now() + timeout;
+        while self.request_count() < count && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.requests()
+    }
+
+    /// Check that recorded requests arrived `interval` apart within `tolerance`.
+    ///
+    /// # Errors
+    ///
+    /// See [`check_interval_timing`].
+    pub fn check_interval_timing(
+        &self,
+        interval: Duration,
+        tolerance: Duration,
+    ) -> Result<(), TimingError> {
+        let timestamps: Vec<Instant> = self.lock().requests.iter().map(|r| r.received_at).collect();
+        check_interval_timing(&timestamps, interval, tolerance)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        state.requests.push(request);
+        let default = state.default_response.clone();
+        state.responses.pop_front().unwrap_or(default)
+    };
+
+    if !response.delay.is_zero() {
+        tokio::time::sleep(response.delay).await;
+    }
+
+    let reason = StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        let _ = write!(head, "{name}: {value}\r\n");
+    }
+    head.push_str("\r\n");
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0_u8; 4096];
+
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    Some(RecordedRequest { method, path, headers, body, received_at: Instant::now() })
+}
+
+/// A [`Clock`] that only moves when told to, for deterministic schedule tests.
+///
+/// Set it as [`HeartbeatConfig::clock`](crate::HeartbeatConfig::clock); the
+/// loop then sends a heartbeat only when [`advance`](Self::advance) moves
+/// the clock past its deadline. Clones share the same time.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use betteruptime_heartbeat::test_util::{ManualClock, MockServer};
+/// use betteruptime_heartbeat::{HeartbeatConfig, spawn};
+///
+/// # async fn run() {
+/// let server = MockServer::start().await;
+/// let clock = ManualClock::new();
+/// let _heartbeat = spawn(HeartbeatConfig {
+///     url: server.url(),
+///     clock: Some(Arc::new(clock.clone())),
+///     ..HeartbeatConfig::default()
+/// });
+///
+/// clock.advance(Duration::from_secs(60));
+/// server.wait_for_requests(1, Duration::from_secs(5)).await;
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<watch::Sender<Instant>>,
+}
+
+impl ManualClock {
+    /// A clock starting at the current tokio instant.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { now: Arc::new(watch::Sender::new(Instant::now())) }
+    }
+
+    /// Move the clock forward by `duration`, waking the waits it completes.
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()> {
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            // The sender lives in `self`, which outlives the future.
+            let _ = now.wait_for(|now| *now >= deadline).await;
+        })
+    }
+}
+
+/// Error returned by [`check_interval_timing`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimingError {
+    /// Fewer than two timestamps were recorded, so no interval can be measured.
+    TooFewSamples {
+        /// Number of timestamps recorded.
+        found: usize,
+    },
+    /// The gap between two consecutive sends was outside the tolerance.
+    Drift {
+        /// Index of the later send (1-based gap between `index - 1` and `index`).
+        index: usize,
+        /// Configured interval.
+        expected: Duration,
+        /// Measured gap.
+        actual: Duration,
+        /// Allowed deviation.
+        tolerance: Duration,
+    },
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooFewSamples { found } => {
+                write!(f, "need at least 2 heartbeat timestamps to check timing, found {found}")
+            }
+            Self::Drift { index, expected, actual, tolerance } => write!(
+                f,
+                "heartbeat #{index} was sent {actual:?} after heartbeat #{}, \
+                 expected {expected:?} ± {tolerance:?}",
+                index - 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimingError {}
+
+/// Check that consecutive `timestamps` are `interval` apart within `tolerance`.
+///
+/// # Errors
+///
+/// Returns [`TimingError::TooFewSamples`] if fewer than two timestamps are
+/// given and [`TimingError::Drift`] for the first gap outside the tolerance.
+pub fn check_interval_timing(
+    timestamps: &[Instant],
+    interval: Duration,
+    tolerance: Duration,
+) -> Result<(), TimingError> {
+    if timestamps.len() < 2 {
+        return Err(TimingError::TooFewSamples { found: timestamps.len() });
+    }
+
+    for (index, pair) in timestamps.windows(2).enumerate() {
+        let actual = pair[1].saturating_duration_since(pair[0]);
+        if actual.abs_diff(interval) > tolerance {
+            return Err(TimingError::Drift {
+                index: index + 1,
+                expected: interval,
+                actual,
+                tolerance,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeartbeatConfig, spawn};
+
+    #[test]
+    fn test_check_interval_timing_accepts_gaps_within_tolerance() {
+        let start = Instant::now();
+        let timestamps = [
+            start,
+            start + Duration::from_millis(1_020),
+            start + Duration::from_millis(1_990),
+            start + Duration::from_millis(3_050),
+        ];
+
+        let result =
+            check_interval_timing(&timestamps, Duration::from_secs(1), Duration::from_millis(100));
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_check_interval_timing_reports_drift() {
+        let start = Instant::now();
+        let timestamps =
+            [start, start + Duration::from_secs(1), start + Duration::from_millis(2_500)];
+
+        let err =
+            check_interval_timing(&timestamps, Duration::from_secs(1), Duration::from_millis(100))
+                .unwrap_err();
+
+        assert_eq!(
+            err,
+            TimingError::Drift {
+                index: 2,
+                expected: Duration::from_secs(1),
+                actual: Duration::from_millis(1_500),
+                tolerance: Duration::from_millis(100),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "heartbeat #2 was sent 1.5s after heartbeat #1, expected 1s ± 100ms"
+        );
+    }
+
+    #[test]
+    fn test_check_interval_timing_requires_two_samples() {
+        let err = check_interval_timing(&[Instant::now()], Duration::from_secs(1), Duration::ZERO)
+            .unwrap_err();
+        assert_eq!(err, TimingError::TooFewSamples { found: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_arrive_at_configured_interval() {
+        let server = MockServer::start().await;
+
+        let _heartbeat = spawn(HeartbeatConfig {
+            url: server.url(),
+            interval_secs: 1,
+            timeout_secs: 5,
+            ..HeartbeatConfig::default()
+        });
+
+        let requests = server.wait_for_requests(3, Duration::from_secs(10)).await;
+        assert!(requests.len() >= 3, "expected 3 heartbeats, got {}", requests.len());
+        assert!(requests.iter().all(|r| r.method == "GET" && r.path == "/heartbeat"));
+
+        server.check_interval_timing(Duration::from_secs(1), Duration::from_millis(250)).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+//! Pluggable sending of heartbeats, for loops started with
+//! [`spawn_with_transport`](crate::spawn_with_transport).
+
+use reqwest::StatusCode;
+
+use crate::runner::AttemptError;
+use crate::{BoxFuture, HeartbeatError};
+
+/// Sends the request of a heartbeat and reports the response status.
+///
+/// Loops started with [`spawn_with_transport`](crate::spawn_with_transport)
+/// ping through a transport instead of their HTTP client, e.g. a fake that
+/// records calls so a test can assert that the heartbeat is wired up,
+/// driven by a paused tokio clock. The loop still applies the timeout,
+/// retries, `success_statuses` and statistics; the request settings
+/// (method, headers, body) are up to the transport.
+///
+/// `reqwest::Client` implements it with a plain `GET`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Mutex;
+///
+/// use betteruptime_heartbeat::{BoxFuture, HeartbeatError, HeartbeatTransport};
+/// use reqwest::StatusCode;
+///
+/// #[derive(Debug, Default)]
+/// struct Recorder(Mutex<Vec<String>>);
+///
+/// impl HeartbeatTransport for Recorder {
+///     fn ping<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<StatusCode, HeartbeatError>> {
+///         self.0.lock().unwrap().push(url.to_string());
+///         Box::pin(async { Ok(StatusCode::OK) })
+///     }
+/// }
+/// ```
+pub trait HeartbeatTransport: std::fmt::Debug + Send + Sync {
+    /// Send a heartbeat to `url`, the request URL including path suffix and
+    /// query parameters, and return the response status.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HeartbeatError::Timeout`], [`HeartbeatError::Connect`] or
+    /// [`HeartbeatError::Other`] if no response was received.
+    fn ping<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<StatusCode, HeartbeatError>>;
+}
+
+impl HeartbeatTransport for reqwest::Client {
+    fn ping<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<StatusCode, HeartbeatError>> {
+        Box::pin(async move {
+            self.get(url)
+                .send()
+                .await
+                .map(|response| response.status())
+                .map_err(|e| AttemptError::from(e).to_error())
+        })
+    }
+}
@@ -0,0 +1,59 @@
+//! Response body validation.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::BoxFuture;
+
+/// Async check run on the body of every successful heartbeat response.
+///
+/// The heartbeat only counts as successful if the validator returns `true`.
+/// Cloning is cheap; clones share the same function.
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::{BodyValidator, HeartbeatConfig};
+///
+/// let config = HeartbeatConfig {
+///     url: "https://status.example.com/health".to_string(),
+///     body_validator: Some(BodyValidator::new(|body| async move { body.contains("\"ok\"") })),
+///     ..HeartbeatConfig::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct BodyValidator(Arc<dyn Fn(String) -> BoxFuture<'static, bool> + Send + Sync>);
+
+impl BodyValidator {
+    /// Wrap an async function receiving the decoded response body.
+    pub fn new<F, Fut>(validate: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        Self(Arc::new(move |body| Box::pin(validate(body))))
+    }
+
+    pub(crate) fn validate(&self, body: String) -> BoxFuture<'static, bool> {
+        (self.0)(body)
+    }
+}
+
+impl fmt::Debug for BodyValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BodyValidator(..)")
+    }
+}
+
+/// What happens when a response body is not valid UTF-8 while a
+/// [`BodyValidator`] is configured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BodyDecodePolicy {
+    /// Count the heartbeat as failed.
+    #[default]
+    Fail,
+    /// Skip body validation and classify the heartbeat by status code only.
+    IgnoreBody,
+}
@@ -0,0 +1,156 @@
+//! Heartbeats sent to custom webhooks instead of Better Uptime.
+
+use reqwest::Method;
+
+use crate::HeartbeatConfig;
+
+/// A custom webhook receiving heartbeats.
+///
+/// Assembles the request method, headers, body template and success
+/// classification of a [`HeartbeatConfig`], so the same loop, retries and
+/// status tracking can drive any HTTP endpoint. The body template uses the
+/// placeholders of [`HeartbeatConfig::body_template`].
+///
+/// # Example
+///
+/// ```rust
+/// use betteruptime_heartbeat::{HeartbeatConfig, WebhookTarget};
+///
+/// let config = WebhookTarget::new("https://hooks.example.com/alive")
+///     .header("Authorization", "Bearer TOKEN")
+///     .json_body(r#"{"service":"{{app_name}}","at":{{timestamp}}}"#)
+///     .success_statuses([202])
+///     .into_config(HeartbeatConfig { interval_secs: 30, ..HeartbeatConfig::default() });
+///
+/// assert_eq!(config.method, reqwest::Method::POST);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    url: String,
+    method: Method,
+    headers: Vec<(String, String)>,
+    body_template: Option<String>,
+    success_statuses: Vec<u16>,
+}
+
+impl WebhookTarget {
+    /// A webhook at `url` receiving an empty `POST` per heartbeat, any 2xx
+    /// status counting as success.
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            method: Method::POST,
+            headers: Vec::new(),
+            body_template: None,
+            success_statuses: Vec::new(),
+        }
+    }
+
+    /// Use `method` instead of `POST`.
+    #[must_use]
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Add a request header.
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body template.
+    #[must_use]
+    pub fn body(mut self, template: impl Into<String>) -> Self {
+        self.body_template = Some(template.into());
+        self
+    }
+
+    /// Set the body template and a `Content-Type: application/json` header.
+    #[must_use]
+    pub fn json_body(self, template: impl Into<String>) -> Self {
+        self.header("content-type", "application/json").body(template)
+    }
+
+    /// Count only these response statuses as success.
+    #[must_use]
+    pub fn success_statuses(mut self, statuses: impl IntoIterator<Item = u16>) -> Self {
+        self.success_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Apply the webhook settings to `base`, keeping its schedule, retry
+    /// and other settings. Headers are added to those already in `base`.
+    #[must_use]
+    pub fn into_config(self, base: HeartbeatConfig) -> HeartbeatConfig {
+        let mut headers = base.headers;
+        headers.extend(self.headers);
+        HeartbeatConfig {
+            url: self.url,
+            method: self.method,
+            headers,
+            body_template: self.body_template,
+            success_statuses: self.success_statuses,
+            ..base
+        }
+    }
+}
+
+impl From<WebhookTarget> for HeartbeatConfig {
+    /// The webhook settings on top of the default config.
+    fn from(target: WebhookTarget) -> Self {
+        target.into_config(Self::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockResponse, MockServer};
+    use crate::{FirstTickPolicy, run_report};
+
+    #[tokio::test]
+    async fn test_loop_satisfies_custom_webhook() {
+        let server = MockServer::start().await;
+        server.set_default_response(MockResponse::status(202));
+
+        let config = WebhookTarget::new(server.url())
+            .method(Method::PUT)
+            .header("X-Api-Key", "secret")
+            .json_body(r#"{"service":"{{app_name}}","seq":{{sequence}}}"#)
+            .success_statuses([202])
+            .into_config(HeartbeatConfig {
+                interval_secs: 1,
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                app_name: Some("billing".to_string()),
+                ..HeartbeatConfig::default()
+            });
+
+        let report = run_report(config, 2).await;
+        assert_eq!((report.successes, report.failures), (2, 0));
+
+        for (sequence, request) in server.requests().iter().enumerate() {
+            assert_eq!(request.method, "PUT");
+            assert_eq!(request.header("x-api-key"), Some("secret"));
+            assert_eq!(request.header("content-type"), Some("application/json"));
+            let expected = format!(r#"{{"service":"billing","seq":{sequence}}}"#);
+            assert_eq!(String::from_utf8_lossy(&request.body), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_success_status_fails() {
+        let server = MockServer::start().await;
+        let config =
+            WebhookTarget::new(server.url()).success_statuses([202]).into_config(HeartbeatConfig {
+                first_tick_policy: FirstTickPolicy::FireImmediately,
+                ..HeartbeatConfig::default()
+            });
+
+        let report = run_report(config, 1).await;
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.statuses, vec![Some(200)]);
+    }
+}